@@ -1,18 +1,22 @@
 use std::{
+	collections::HashMap,
 	fmt::{self, Display, Formatter},
-	fs::read_to_string,
+	fs::{read_to_string, write},
 	path::PathBuf,
 };
 
 use clap::{Parser, Subcommand, ValueEnum};
 use dyn_core::{
-	ArgumentValues, BuiltinFunction, FunctionValue, Interpreter, Value,
+	ArgumentValues, Arity, Backend, BuiltinFunction, CallContext,
+	FunctionValue, Interpreter, InterpreterError, RuntimeError, Value,
+	DEFAULT_MAX_CALL_DEPTH,
 };
 use dyn_lexer::{lexer::LexerConfig, SpannedLexer};
 use dyn_parser::ast::Ident;
 use maplit::hashmap;
-use miette::Report;
+use miette::{Diagnostic, Report, Severity};
 use rustyline::DefaultEditor;
+use serde_json::json;
 
 #[derive(Debug, Parser)]
 struct Args {
@@ -21,6 +25,14 @@ struct Args {
 	#[arg(short, long, default_value_t = false)]
 	print_last_expression: bool,
 
+	/// Maximum number of nested function calls
+	#[arg(long, default_value_t = DEFAULT_MAX_CALL_DEPTH)]
+	max_call_depth: usize,
+
+	/// How scripts are evaluated
+	#[arg(long, default_value_t = BackendArg::TreeWalker)]
+	backend: BackendArg,
+
 	#[command(subcommand)]
 	command: Option<Commands>,
 }
@@ -45,6 +57,22 @@ enum Commands {
 		#[arg(short, long, default_value_t = false)]
 		asi: bool,
 	},
+	/// Reports mistakes in a script without running it
+	Check {
+		source_path: PathBuf,
+
+		#[arg(short, long, default_value_t = CheckFormat::Human)]
+		format: CheckFormat,
+	},
+	/// Formats scripts in place
+	Fmt {
+		#[arg(required = true)]
+		source_paths: Vec<PathBuf>,
+
+		/// List unformatted scripts instead of formatting them
+		#[arg(long, default_value_t = false)]
+		check: bool,
+	},
 }
 
 #[derive(Debug, Clone, ValueEnum)]
@@ -55,14 +83,49 @@ enum LexerFormat {
 
 impl Display for LexerFormat {
 	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-		write!(
-			f,
-			"{}",
-			match self {
-				LexerFormat::Ndjson => "ndjson",
-				LexerFormat::Csv => "csv",
-			}
-		)
+		write!(f, "{}", match self {
+			LexerFormat::Ndjson => "ndjson",
+			LexerFormat::Csv => "csv",
+		})
+	}
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum CheckFormat {
+	Human,
+	Json,
+}
+
+impl Display for CheckFormat {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", match self {
+			CheckFormat::Human => "human",
+			CheckFormat::Json => "json",
+		})
+	}
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum BackendArg {
+	TreeWalker,
+	Bytecode,
+}
+
+impl Display for BackendArg {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", match self {
+			BackendArg::TreeWalker => "tree-walker",
+			BackendArg::Bytecode => "bytecode",
+		})
+	}
+}
+
+impl From<BackendArg> for Backend {
+	fn from(value: BackendArg) -> Self {
+		match value {
+			BackendArg::TreeWalker => Backend::TreeWalker,
+			BackendArg::Bytecode => Backend::Bytecode,
+		}
 	}
 }
 
@@ -70,16 +133,24 @@ impl Display for LexerFormat {
 struct Printer;
 
 impl Printer {
-	fn new() -> Box<Self> {
-		Box::new(Self)
-	}
+	fn new() -> Box<Self> { Box::new(Self) }
 }
 
 impl BuiltinFunction for Printer {
-	fn call(&mut self, args: ArgumentValues) -> Value {
-		let msg = args.0.into_iter().nth(0).unwrap();
-		println!("{}", msg.clone());
-		msg
+	fn arity(&self) -> Arity { Arity::exactly(1) }
+
+	fn call(
+		&mut self,
+		_: &mut CallContext,
+		args: ArgumentValues,
+	) -> Result<Value, RuntimeError> {
+		let msg = args
+			.0
+			.into_iter()
+			.next()
+			.unwrap_or(Value::Nil);
+		println!("{msg}");
+		Ok(msg)
 	}
 }
 
@@ -109,6 +180,112 @@ fn lexer_cmd(
 	Ok(())
 }
 
+fn builtins() -> HashMap<Ident, Value> {
+	hashmap! {
+		Ident::new_dummy("print") => Value::Function(
+			FunctionValue::Builtin(Printer::new())
+		),
+	}
+}
+
+/// Checks the script at `source_path`, returning whether it is free
+/// of errors. Warnings alone don't fail the check.
+fn check_cmd(
+	source_path: PathBuf,
+	format: CheckFormat,
+) -> anyhow::Result<bool> {
+	let source = read_to_string(&source_path)?;
+	let mut intpr = Interpreter::init_with_builtins(builtins()).unwrap();
+
+	let lints = intpr.check(&source);
+
+	let ok = lints
+		.iter()
+		.all(|lint| lint.severity() != Some(Severity::Error));
+
+	match format {
+		CheckFormat::Human =>
+			for lint in lints {
+				let rep = Report::new(lint).with_source_code(source.clone());
+				println!("{rep:?}");
+			},
+		CheckFormat::Json => {
+			let lints = lints
+				.iter()
+				.map(|lint| {
+					let severity = match lint.severity() {
+						Some(Severity::Error) => "error",
+						_ => "warning",
+					};
+					let labels =
+						lint.labels()
+							.into_iter()
+							.flatten()
+							.map(|label| {
+								json!({
+									"start": label.offset(),
+									"end": label.offset() + label.len(),
+									"message": label.label(),
+								})
+							});
+
+					json!({
+						"path": source_path,
+						"code": lint.code().map(|code| code.to_string()),
+						"severity": severity,
+						"message": lint.to_string(),
+						"labels": labels.collect::<Vec<_>>(),
+					})
+				})
+				.collect::<Vec<_>>();
+
+			println!(
+				"{}",
+				serde_json::to_string_pretty(&lints)?
+			);
+		},
+	}
+
+	Ok(ok)
+}
+
+/// Formats the scripts at `source_paths`, returning whether all of
+/// them parsed and, with `check`, were already formatted.
+fn fmt_cmd(source_paths: Vec<PathBuf>, check: bool) -> anyhow::Result<bool> {
+	let mut ok = true;
+
+	for source_path in source_paths {
+		let source = read_to_string(&source_path)?;
+
+		let formatted = match dyn_fmt::format(&source) {
+			Ok(formatted) => formatted,
+			Err(err) => {
+				let rep: Report =
+					InterpreterError::ParseError(vec![err.into()]).into();
+				println!("{:?}", rep.with_source_code(source));
+				ok = false;
+				continue;
+			},
+		};
+
+		if formatted == source {
+			continue;
+		}
+
+		if check {
+			println!(
+				"{} is not formatted",
+				source_path.display()
+			);
+			ok = false;
+		} else {
+			write(&source_path, formatted)?;
+		}
+	}
+
+	Ok(ok)
+}
+
 fn main() -> anyhow::Result<()> {
 	let args = Args::parse();
 
@@ -131,22 +308,33 @@ fn main() -> anyhow::Result<()> {
 					asi,
 				},
 			)?,
+			Commands::Check {
+				source_path,
+				format,
+			} =>
+				if !check_cmd(source_path, format)? {
+					std::process::exit(1);
+				},
+			Commands::Fmt {
+				source_paths,
+				check,
+			} =>
+				if !fmt_cmd(source_paths, check)? {
+					std::process::exit(1);
+				},
 		}
 
 		return Ok(());
 	}
 
-	let mut intpr = Interpreter::init_with_builtins(hashmap! {
-		Ident::new_dummy("print") => Value::Function(
-			FunctionValue::Builtin(Printer::new())
-		),
-	})
-	.unwrap();
+	let mut intpr = Interpreter::init_with_builtins(builtins()).unwrap();
+	intpr.set_max_call_depth(args.max_call_depth);
+	intpr.set_backend(args.backend.into());
 
 	if let Some(p) = args.source_path {
-		let source = read_to_string(p).unwrap();
+		let source = read_to_string(&p).unwrap();
 
-		let res = intpr.run(&source);
+		let res = intpr.run_file(&p, &source);
 
 		if let Err(diag) = res {
 			let rep: Report = diag.into();
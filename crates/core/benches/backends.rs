@@ -0,0 +1,72 @@
+//! Compares the tree walker with the bytecode backend.
+//!
+//! Run with `cargo bench -p dyn-core`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use dyn_core::{Backend, Interpreter};
+
+const PROGRAMS: &[(&str, &str)] = &[
+	(
+		"fib",
+		r#"
+let fib = |n| -> if n <= 1 { n } else { fib(n - 1) + fib(n - 2) }
+fib(15)
+"#,
+	),
+	(
+		"loop",
+		r#"
+let! sum = 0
+iter range(0, 1000) of i {
+	if i % 3 == 0 { continue nil }
+	sum += i * 2
+}
+sum
+"#,
+	),
+	(
+		"closures",
+		r#"
+let! count = 0
+let next = -> count += 1
+iter range(0, 1000) of i { next() }
+reduce(map(range(0, 200), |x| -> x * x), |a, b| -> a + b, 0)
+"#,
+	),
+	(
+		"records",
+		r#"
+let! points = []
+iter range(0, 200) of i {
+	push(points, (x: i, y: i * 2))
+}
+let! total = 0
+iter points of p { total += p.x + p.y }
+total
+"#,
+	),
+];
+
+fn backends(c: &mut Criterion) {
+	for (name, code) in PROGRAMS {
+		let mut group = c.benchmark_group(*name);
+
+		for backend in [Backend::TreeWalker, Backend::Bytecode] {
+			group.bench_function(
+				BenchmarkId::from_parameter(format!("{backend:?}")),
+				|b| {
+					b.iter(|| {
+						let mut interpreter = Interpreter::init();
+						interpreter.set_backend(backend);
+						interpreter.run(code).unwrap()
+					})
+				},
+			);
+		}
+
+		group.finish();
+	}
+}
+
+criterion_group!(benches, backends);
+criterion_main!(benches);
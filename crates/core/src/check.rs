@@ -0,0 +1,694 @@
+//! Static checks run by `dyn check`, which find mistakes without
+//! running a script.
+
+use std::collections::HashMap;
+
+use dyn_parser::{
+	ast::{
+		Code, Else, Expr, ExprKind, Function, Ident, MatchArm, Pattern, Place,
+		PlaceKind, Use,
+	},
+	parse_code_recovering, walk_expr, Visit,
+};
+use dyn_span::{HasSpan, Span};
+use miette::{Diagnostic, LabeledSpan, Severity};
+use thiserror::Error;
+
+use crate::{
+	error::ParseError,
+	operator,
+	resolver::{declarations, pattern_declarations},
+	Value,
+};
+
+/// A problem found by [`check`].
+#[derive(Debug, PartialEq, Error)]
+pub enum Lint {
+	#[error("syntax error")]
+	SyntaxError { error: ParseError },
+
+	#[error("undefined variable `{}`", ident.symbol())]
+	UndefinedIdentifier { ident: Ident },
+
+	#[error("unused variable `{}`", ident.symbol())]
+	UnusedVariable { ident: Ident },
+
+	#[error("assignment to immutable variable `{}`", ident.symbol())]
+	AssignmentToImmutableVariable { ident: Ident, declaration: Span },
+
+	#[error("`{}` is declared twice in the same scope", ident.symbol())]
+	Redeclaration {
+		ident: Ident,
+		/// Earlier declaration, `None` for builtins.
+		previous: Option<Span>,
+	},
+
+	#[error("unreachable code")]
+	UnreachableCode { span: Span, exit: Span },
+
+	#[error("condition is always `{value}`")]
+	ConstantCondition { value: bool, span: Span },
+}
+
+impl Lint {
+	pub fn span(&self) -> Span {
+		match self {
+			Lint::SyntaxError { error } => error.span(),
+			Lint::UndefinedIdentifier { ident }
+			| Lint::UnusedVariable { ident }
+			| Lint::AssignmentToImmutableVariable { ident, .. }
+			| Lint::Redeclaration { ident, .. } => ident.span(),
+			Lint::UnreachableCode { span, .. }
+			| Lint::ConstantCondition { span, .. } => *span,
+		}
+	}
+}
+
+impl Diagnostic for Lint {
+	fn code<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+		let code = match self {
+			Lint::SyntaxError { .. } => "syntax-error",
+			Lint::UndefinedIdentifier { .. } => "undefined-identifier",
+			Lint::UnusedVariable { .. } => "unused-variable",
+			Lint::AssignmentToImmutableVariable { .. } =>
+				"immutable-assignment",
+			Lint::Redeclaration { .. } => "redeclaration",
+			Lint::UnreachableCode { .. } => "unreachable-code",
+			Lint::ConstantCondition { .. } => "constant-condition",
+		};
+
+		Some(Box::new(code))
+	}
+
+	fn severity(&self) -> Option<Severity> {
+		match self {
+			Lint::SyntaxError { .. }
+			| Lint::UndefinedIdentifier { .. }
+			| Lint::AssignmentToImmutableVariable { .. }
+			| Lint::Redeclaration { .. } => Some(Severity::Error),
+			Lint::UnusedVariable { .. }
+			| Lint::UnreachableCode { .. }
+			| Lint::ConstantCondition { .. } => Some(Severity::Warning),
+		}
+	}
+
+	fn help<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+		match self {
+			Lint::UnusedVariable { ident } => Some(Box::new(format!(
+				"if this is intentional, prefix it with an underscore: `_{}`",
+				ident.symbol()
+			))),
+			Lint::AssignmentToImmutableVariable { .. } => Some(Box::new(
+				"declare it with `let!` to make it mutable",
+			)),
+			_ => None,
+		}
+	}
+
+	fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+		match self {
+			Lint::SyntaxError { error } => error.labels(),
+			Lint::UndefinedIdentifier { ident } => Some(Box::new(
+				[LabeledSpan::at(
+					ident.span(),
+					format!(
+						"can't find `{}` in this scope",
+						ident.symbol()
+					),
+				)]
+				.into_iter(),
+			)),
+			Lint::UnusedVariable { ident } => Some(Box::new(
+				[LabeledSpan::at(ident.span(), "never read")].into_iter(),
+			)),
+			Lint::AssignmentToImmutableVariable { ident, declaration } =>
+				Some(Box::new(
+					[
+						LabeledSpan::at(ident.span(), "assigned here"),
+						LabeledSpan::at(
+							*declaration,
+							"declared immutable here",
+						),
+					]
+					.into_iter(),
+				)),
+			Lint::Redeclaration { ident, previous } => Some(Box::new(
+				[LabeledSpan::at(ident.span(), "declared again here")]
+					.into_iter()
+					.chain(previous.map(|previous| {
+						LabeledSpan::at(previous, "first declared here")
+					})),
+			)),
+			Lint::UnreachableCode { span, exit } => Some(Box::new(
+				[
+					LabeledSpan::at(
+						*exit,
+						"any code after this is unreachable",
+					),
+					LabeledSpan::at(*span, "unreachable code"),
+				]
+				.into_iter(),
+			)),
+			Lint::ConstantCondition { value, span } => Some(Box::new(
+				[LabeledSpan::at(
+					*span,
+					format!("this always evaluates to `{value}`"),
+				)]
+				.into_iter(),
+			)),
+		}
+	}
+}
+
+/// Checks `code` without running it, where `defined` tells which
+/// top-level variables already exist, such as builtins.
+///
+/// Lints are sorted by where they appear.
+pub fn check<F>(code: &Code, defined: F) -> Vec<Lint>
+where F: Fn(&str) -> bool {
+	let mut checker = Checker {
+		scopes: vec![],
+		defined,
+		lints: vec![],
+	};
+
+	checker.push_scope(declarations(&code.stmts), false);
+	checker.visit_code(code);
+	checker.pop_scope();
+
+	let mut lints = checker.lints;
+	lints.sort_by_key(Lint::span);
+	lints
+}
+
+/// Checks `source`, reporting all of its syntax errors along with the
+/// lints of the statements that do parse.
+///
+/// Statements with syntax errors are skipped, so what they declare or
+/// use would be reported as undefined or unused. Those lints are left
+/// out until the syntax errors are fixed.
+pub fn check_source<F>(source: &str, defined: F) -> Vec<Lint>
+where F: Fn(&str) -> bool {
+	let (code, errors) = parse_code_recovering(source);
+	if errors.is_empty() {
+		return check(&code, defined);
+	}
+
+	let mut lints = check(&code, defined)
+		.into_iter()
+		.filter(|lint| {
+			!matches!(
+				lint,
+				Lint::UndefinedIdentifier { .. } | Lint::UnusedVariable { .. }
+			)
+		})
+		.chain(
+			errors
+				.into_iter()
+				.map(|error| Lint::SyntaxError {
+					error: ParseError(error),
+				}),
+		)
+		.collect::<Vec<_>>();
+	lints.sort_by_key(Lint::span);
+	lints
+}
+
+struct Variable {
+	/// Where the variable was last declared, `None` until then.
+	declaration: Option<Span>,
+	mutable: bool,
+	/// Whether unused declarations should be reported, which is only
+	/// done for `let`s.
+	binding: bool,
+	used: bool,
+	/// Assignments seen before the declaration, from closures.
+	assignments: Vec<Ident>,
+}
+
+struct Scope {
+	variables: HashMap<String, Variable>,
+	/// Whether this is the outermost scope of a closure.
+	function: bool,
+}
+
+struct Checker<F> {
+	/// Scopes from the top level inwards, mirroring the runtime.
+	scopes: Vec<Scope>,
+	defined: F,
+	lints: Vec<Lint>,
+}
+
+impl<F> Checker<F>
+where F: Fn(&str) -> bool
+{
+	fn push_scope(&mut self, idents: Vec<Ident>, function: bool) {
+		let variables = idents
+			.into_iter()
+			.map(|ident| {
+				(ident.into_symbol(), Variable {
+					declaration: None,
+					mutable: false,
+					binding: false,
+					used: false,
+					assignments: vec![],
+				})
+			})
+			.collect();
+
+		self.scopes.push(Scope {
+			variables,
+			function,
+		});
+	}
+
+	/// Leaves the innermost scope, reporting what can only be known
+	/// once all of it has been seen.
+	fn pop_scope(&mut self) {
+		let scope = self.scopes.pop().unwrap();
+
+		for (name, variable) in scope.variables {
+			let Some(declaration) = variable.declaration else {
+				continue;
+			};
+
+			if variable.binding && !variable.used && !name.starts_with('_') {
+				self.lints.push(Lint::UnusedVariable {
+					ident: Ident::new(name, declaration),
+				});
+			}
+
+			if !variable.mutable {
+				self.lints
+					.extend(
+						variable
+							.assignments
+							.into_iter()
+							.map(
+								|ident| Lint::AssignmentToImmutableVariable {
+									ident,
+									declaration,
+								},
+							),
+					);
+			}
+		}
+	}
+
+	fn declare(&mut self, ident: &Ident, mutable: bool, binding: bool) {
+		let top_level = self.scopes.len() == 1;
+		let scope = self.scopes.last_mut().unwrap();
+		let variable = scope
+			.variables
+			.get_mut(&ident.symbol)
+			.expect("declarations are collected before the scope");
+
+		if let Some(previous) = variable.declaration {
+			self.lints.push(Lint::Redeclaration {
+				ident: ident.clone(),
+				previous: Some(previous),
+			});
+		} else if top_level && (self.defined)(&ident.symbol) {
+			self.lints.push(Lint::Redeclaration {
+				ident: ident.clone(),
+				previous: None,
+			});
+		}
+
+		variable.declaration = Some(ident.span);
+		variable.mutable = mutable;
+		variable.binding = binding;
+	}
+
+	/// Finds the variable `ident` refers to, the same way the
+	/// resolver does, reporting it if there is none.
+	fn lookup(&mut self, ident: &Ident) -> Option<&mut Variable> {
+		let mut same_function = true;
+		let mut found = None;
+
+		for (i, scope) in self.scopes.iter().enumerate().rev() {
+			if let Some(variable) = scope.variables.get(&ident.symbol) {
+				if variable.declaration.is_some() || !same_function {
+					found = Some(i);
+					break;
+				}
+			}
+
+			if scope.function {
+				same_function = false;
+			}
+		}
+
+		match found {
+			Some(i) => self.scopes[i]
+				.variables
+				.get_mut(&ident.symbol),
+			None => {
+				if !(self.defined)(&ident.symbol) {
+					self.lints
+						.push(Lint::UndefinedIdentifier {
+							ident: ident.clone(),
+						});
+				}
+				None
+			},
+		}
+	}
+
+	fn use_ident(&mut self, ident: &Ident) {
+		if let Some(variable) = self.lookup(ident) {
+			variable.used = true;
+		}
+	}
+
+	/// Visits `code` in a scope of its own.
+	fn visit_scope(&mut self, code: &Code) {
+		self.push_scope(declarations(&code.stmts), false);
+		self.visit_code(code);
+		self.pop_scope();
+	}
+
+	/// Visits one branch of a conditional. Declarations in it land in
+	/// the enclosing scope, but only one branch runs, so they don't
+	/// clash with those of the other branch.
+	fn visit_branch<V>(&mut self, visit: V) -> Vec<(String, Span)>
+	where V: FnOnce(&mut Self) {
+		let before = self.declared();
+		visit(self);
+		let after = self.declared();
+
+		let scope = self.scopes.last_mut().unwrap();
+		after
+			.into_iter()
+			.filter(|(name, span)| before.get(name) != Some(span))
+			.map(|(name, span)| {
+				scope
+					.variables
+					.get_mut(&name)
+					.unwrap()
+					.declaration = before.get(&name).copied();
+				(name, span)
+			})
+			.collect()
+	}
+
+	fn declared(&self) -> HashMap<String, Span> {
+		self.scopes
+			.last()
+			.unwrap()
+			.variables
+			.iter()
+			.filter_map(|(name, variable)| {
+				Some((name.clone(), variable.declaration?))
+			})
+			.collect()
+	}
+
+	fn visit_conditional(
+		&mut self,
+		condition: &Expr,
+		yes: &Code,
+		no: Option<&Else>,
+	) {
+		self.visit_expr(condition);
+
+		if let Some(Value::Boolean(value)) = constant(condition) {
+			self.lints
+				.push(Lint::ConstantCondition {
+					value,
+					span: condition.span(),
+				});
+		}
+
+		let mut declared = self.visit_branch(|c| c.visit_code(yes));
+		match no {
+			Some(Else::Block(no)) =>
+				declared.extend(self.visit_branch(|c| c.visit_code(no))),
+			Some(Else::If(no)) =>
+				declared.extend(self.visit_branch(|c| c.visit_expr(no))),
+			None => {},
+		}
+
+		let scope = self.scopes.last_mut().unwrap();
+		for (name, span) in declared {
+			scope
+				.variables
+				.get_mut(&name)
+				.unwrap()
+				.declaration = Some(span);
+		}
+	}
+
+	/// Declares the bindings of `pattern`, then visits its defaults,
+	/// each declaring the binding it is for.
+	fn bind_pattern(
+		&mut self,
+		pattern: &Pattern,
+		mutable: bool,
+		binding: bool,
+	) {
+		let defaults = pattern.defaults();
+		for ident in pattern.bindings() {
+			if defaults
+				.iter()
+				.all(|(defaulted, _)| *defaulted != ident)
+			{
+				self.declare(ident, mutable, binding);
+			}
+		}
+		for (ident, default) in defaults {
+			self.visit_expr(default);
+			self.declare(ident, mutable, binding);
+		}
+	}
+
+	fn assign(&mut self, place: &Place, value: &Expr, compound: bool) {
+		self.visit_place(place);
+		self.visit_expr(value);
+
+		let (root, path) = root(place);
+		let Some(variable) = self.lookup(root) else {
+			return;
+		};
+
+		variable.used |= compound || path;
+		match variable.declaration {
+			Some(declaration) if !variable.mutable =>
+				self.lints
+					.push(Lint::AssignmentToImmutableVariable {
+						ident: root.clone(),
+						declaration,
+					}),
+			Some(_) => {},
+			None => variable.assignments.push(root.clone()),
+		}
+	}
+}
+
+impl<F> Visit for Checker<F>
+where F: Fn(&str) -> bool
+{
+	fn visit_function(&mut self, i: &Function) {
+		let positional = i.parameters.positional.iter();
+		let mut idents = positional
+			.clone()
+			.flat_map(|parameter| pattern_declarations(&parameter.pattern))
+			.chain(i.parameters.rest.clone())
+			.collect::<Vec<_>>();
+		for parameter in positional.clone() {
+			if let Some(default) = &parameter.default {
+				idents.extend(declarations([default]));
+			}
+		}
+		idents.extend(declarations([&*i.body]));
+
+		self.push_scope(idents, true);
+
+		for parameter in positional {
+			if let Some(default) = &parameter.default {
+				self.visit_expr(default);
+			}
+			self.bind_pattern(&parameter.pattern, false, false);
+		}
+		if let Some(rest) = &i.parameters.rest {
+			self.declare(rest, false, false);
+		}
+		self.visit_expr(&i.body);
+
+		self.pop_scope();
+	}
+
+	fn visit_use(&mut self, i: &Use) {
+		for ident in &i.imports {
+			self.declare(ident, false, false);
+		}
+	}
+
+	fn visit_match_arm(&mut self, i: &MatchArm) {
+		let mut idents = pattern_declarations(&i.pattern);
+		idents.extend(declarations(
+			i.guard.iter().chain([&i.body]),
+		));
+
+		self.push_scope(idents, false);
+		self.bind_pattern(&i.pattern, false, false);
+		if let Some(guard) = &i.guard {
+			self.visit_expr(guard);
+		}
+		self.visit_expr(&i.body);
+		self.pop_scope();
+	}
+
+	fn visit_place(&mut self, i: &Place) {
+		match &i.kind {
+			PlaceKind::Ident(_) => {},
+			PlaceKind::Prop(i, _) => self.visit_place(i),
+			PlaceKind::Index(i, j) => {
+				self.visit_place(i);
+				self.visit_expr(j);
+			},
+		}
+	}
+
+	fn visit_expr(&mut self, i: &Expr) {
+		match &i.kind {
+			ExprKind::Ident(i) => self.use_ident(i),
+			ExprKind::Prop(i, _) => self.visit_expr(i),
+			ExprKind::Assign(place, value) => self.assign(place, value, false),
+			ExprKind::AddAssign(place, value)
+			| ExprKind::SubAssign(place, value)
+			| ExprKind::MulAssign(place, value)
+			| ExprKind::DivAssign(place, value)
+			| ExprKind::ModAssign(place, value) => self.assign(place, value, true),
+			ExprKind::Declare(ident, value) => {
+				self.visit_expr(value);
+				self.declare(ident, false, true);
+			},
+			ExprKind::DeclareMut(ident, value) => {
+				self.visit_expr(value);
+				self.declare(ident, true, true);
+			},
+			ExprKind::Destructure {
+				pattern,
+				value,
+				mutable,
+			} => {
+				self.visit_expr(value);
+				self.bind_pattern(pattern, *mutable, true);
+			},
+			ExprKind::Export(declaration) => {
+				self.visit_expr(declaration);
+
+				if let ExprKind::Declare(ident, _)
+				| ExprKind::DeclareMut(ident, _) = &declaration.kind
+				{
+					self.use_ident(ident);
+				}
+			},
+			ExprKind::Block(code) => self.visit_scope(code),
+			ExprKind::If { condition, yes, no } =>
+				self.visit_conditional(condition, yes, no.as_ref()),
+			ExprKind::For {
+				collection,
+				item,
+				body,
+			} => {
+				self.visit_expr(collection);
+
+				let mut idents = pattern_declarations(item);
+				idents.extend(declarations(&body.stmts));
+				self.push_scope(idents, false);
+				self.bind_pattern(item, true, false);
+				self.visit_code(body);
+				self.pop_scope();
+			},
+			_ => walk_expr(self, i),
+		}
+	}
+
+	fn visit_code(&mut self, i: &Code) {
+		for (n, stmt) in i.stmts.iter().enumerate() {
+			self.visit_expr(stmt);
+
+			let rest = &i.stmts[n + 1..];
+			if let (true, Some(first), Some(last)) = (
+				diverges(stmt),
+				rest.first(),
+				rest.last(),
+			) {
+				self.lints.push(Lint::UnreachableCode {
+					span: first.span + last.span,
+					exit: stmt.span,
+				});
+
+				rest.iter()
+					.for_each(|stmt| self.visit_expr(stmt));
+				break;
+			}
+		}
+	}
+}
+
+/// The variable a place belongs to, and whether the place is a path
+/// into it rather than the variable itself.
+fn root(place: &Place) -> (&Ident, bool) {
+	match &place.kind {
+		PlaceKind::Ident(ident) => (ident, false),
+		PlaceKind::Prop(i, _) | PlaceKind::Index(i, _) => (root(i).0, true),
+	}
+}
+
+/// Whether evaluating `expr` always leaves the code it appears in.
+fn diverges(expr: &Expr) -> bool {
+	match &expr.kind {
+		ExprKind::Return(_)
+		| ExprKind::Break(_)
+		| ExprKind::Continue(_)
+		| ExprKind::Panic(_) => true,
+		ExprKind::Block(code) => code.stmts.iter().any(diverges),
+		ExprKind::If {
+			yes, no: Some(no), ..
+		} =>
+			yes.stmts.iter().any(diverges)
+				&& match no {
+					Else::Block(no) => no.stmts.iter().any(diverges),
+					Else::If(no) => diverges(no),
+				},
+		// no arm matching is an error too
+		ExprKind::Match { arms, .. } => arms
+			.iter()
+			.all(|arm| diverges(&arm.body)),
+		_ => false,
+	}
+}
+
+/// The value of `expr` if it only combines literals.
+fn constant(expr: &Expr) -> Option<Value> {
+	match &expr.kind {
+		ExprKind::Literal(i) => Some(Value::from_literal(i.clone())),
+		ExprKind::UnaryExpr(i) =>
+			operator::unary(i.op, constant(&i.expr)?, i.expr.span()).ok(),
+		ExprKind::BinExpr(i) if i.op.is_logical() => {
+			let lhs = constant(&i.lhs)?;
+
+			match operator::short_circuits(&i.op, &lhs, i.lhs.span()).ok()? {
+				true => Some(lhs),
+				false => operator::logical(
+					i.op.clone(),
+					constant(&i.rhs)?,
+					i.rhs.span(),
+				)
+				.ok(),
+			}
+		},
+		ExprKind::BinExpr(i) => operator::binary(
+			i.op.clone(),
+			constant(&i.lhs)?,
+			i.lhs.span(),
+			constant(&i.rhs)?,
+			i.rhs.span(),
+		)
+		.ok(),
+		_ => None,
+	}
+}
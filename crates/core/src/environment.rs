@@ -3,165 +3,250 @@ use std::{
 	sync::{Arc, RwLock},
 };
 
-use dyn_parser::ast::{Ident, Parameters};
+use dyn_parser::ast::Ident;
+use dyn_span::Span;
 
-use crate::{ArgumentValues, ReferenceError, RuntimeError, SymbolInfo, Value};
-
-type IndexedStack<T> = Vec<T>;
+use crate::{
+	resolver::{Binding, Resolution},
+	ReferenceError, RuntimeError, SymbolInfo, Value,
+};
 
 #[derive(Debug)]
-struct Scope(HashMap<Ident, SymbolInfo>);
+struct Scope {
+	/// Variables indexed by the slots the resolver gave them.
+	slots: Vec<Option<SymbolInfo>>,
+	parent: Option<Arc<RwLock<Scope>>>,
+}
 
-#[derive(Debug)]
+impl Scope {
+	fn new(parent: Option<Arc<RwLock<Scope>>>) -> Arc<RwLock<Self>> {
+		Arc::new(RwLock::new(Self {
+			slots: vec![],
+			parent,
+		}))
+	}
+}
+
+type Globals = Arc<RwLock<HashMap<Ident, SymbolInfo>>>;
+
+/// Everything a closure can see, captured when it is created.
+#[derive(Debug, Clone)]
 pub struct Frame {
-	scope_stack: IndexedStack<Scope>,
-	parent: Option<Arc<RwLock<Frame>>>,
+	/// Innermost local scope, `None` at the top level.
+	scope: Option<Arc<RwLock<Scope>>>,
+	globals: Globals,
+	/// Bindings of the code the closure was defined in.
+	resolution: Arc<Resolution>,
 }
 
 impl Frame {
-	fn rec_lookup<F, T>(
-		&mut self,
-		ident: &Ident,
-		cb: F,
-	) -> Result<T, RuntimeError>
-	where
-		F: Fn(&mut SymbolInfo) -> Result<T, RuntimeError>,
-	{
-		for scope in self.scope_stack.iter_mut().rev() {
-			if let Entry::Occupied(mut v) = scope.0.entry(ident.to_owned()) {
-				return cb(v.get_mut());
-			};
-		}
+	fn lookup<F, T>(&self, ident: &Ident, cb: F) -> Result<T, RuntimeError>
+	where F: FnOnce(&mut SymbolInfo) -> Result<T, RuntimeError> {
+		let undefined = || {
+			RuntimeError::ReferenceError(ReferenceError::UndefinedIdentifier {
+				ident: ident.clone(),
+			})
+		};
 
-		let Some(parent) = &self.parent else {
-			return Err(RuntimeError::ReferenceError(
-				ReferenceError::UndefinedIdentifier {
-					ident: ident.clone(),
-				},
-			));
+		let Some(Binding { depth, slot }) = self.resolution.get(ident) else {
+			let mut globals = self.globals.write().unwrap();
+			return globals
+				.get_mut(ident)
+				.map_or_else(|| Err(undefined()), cb);
 		};
 
-		let parent = parent.clone();
-		let mut parent = parent.write().unwrap();
-		parent.rec_lookup(ident, cb)
+		let mut scope = self
+			.scope
+			.clone()
+			.expect("locals are in a scope");
+		for _ in 0..depth {
+			let parent = scope.read().unwrap().parent.clone();
+			scope = parent.expect("resolved scopes exist");
+		}
+
+		let mut scope = scope.write().unwrap();
+		match scope.slots.get_mut(slot) {
+			Some(Some(symbol)) => cb(symbol),
+			_ => Err(undefined()),
+		}
 	}
 }
 
 #[derive(Debug)]
 pub struct Environment {
-	call_stack: IndexedStack<Arc<RwLock<Frame>>>,
+	frame: Frame,
+	/// Frames of the callers of the closures being evaluated.
+	call_stack: Vec<Frame>,
 }
 
 impl Environment {
 	pub fn new() -> Self {
 		Self {
-			call_stack: vec![Arc::new(RwLock::new(Frame {
-				scope_stack: vec![Scope(HashMap::new())],
-				parent: None,
-			}))],
+			frame: Frame {
+				scope: None,
+				globals: Arc::default(),
+				resolution: Arc::default(),
+			},
+			call_stack: vec![],
 		}
 	}
 
-	pub fn top_frame(&self) -> Arc<RwLock<Frame>> {
-		// WARN: unwrap
-		self.call_stack.last().unwrap().clone()
+	/// Uses `resolution` to find the variables of the code evaluated
+	/// from now on.
+	pub fn enter(&mut self, resolution: Resolution) {
+		self.frame.resolution = Arc::new(resolution);
 	}
 
+	pub fn top_frame(&self) -> Frame { self.frame.clone() }
+
 	pub fn declare(
 		&mut self,
 		ident: &Ident,
 		value: Value,
 		mutable: bool,
 	) -> Result<(), RuntimeError> {
-		// WARN: unwrap
-		let top = self.top_frame();
-		let mut top = top.write().unwrap();
-		let Entry::Vacant(v) = top
-			.scope_stack
-			.last_mut()
-			.unwrap()
-			.0
-			.entry(ident.to_owned())
+		let symbol = SymbolInfo {
+			mutable,
+			value,
+			declaration: Some(ident.span),
+		};
+
+		let Some(Binding { slot, .. }) = self.frame.resolution.get(ident)
 		else {
-			return Err(RuntimeError::AlreadyDeclared);
+			let mut globals = self.frame.globals.write().unwrap();
+			let Entry::Vacant(v) = globals.entry(ident.to_owned()) else {
+				return Err(RuntimeError::AlreadyDeclared);
+			};
+
+			v.insert(symbol);
+			return Ok(());
 		};
 
-		v.insert(SymbolInfo { mutable, value });
+		let scope = self
+			.frame
+			.scope
+			.as_ref()
+			.expect("locals are in a scope");
+		let slots = &mut scope.write().unwrap().slots;
+		if slots.len() <= slot {
+			slots.resize_with(slot + 1, || None);
+		}
+
+		if slots[slot].is_some() {
+			return Err(RuntimeError::AlreadyDeclared);
+		}
+
+		slots[slot] = Some(symbol);
 
 		Ok(())
 	}
 
+	/// Declares a builtin, which has no declaration in the source.
+	pub fn declare_builtin(&mut self, ident: &Ident, value: Value) {
+		let symbol = SymbolInfo {
+			mutable: false,
+			value,
+			declaration: None,
+		};
+
+		self.frame
+			.globals
+			.write()
+			.unwrap()
+			.insert(ident.to_owned(), symbol);
+	}
+
 	pub fn assign(
 		&mut self,
 		ident: &Ident,
 		value: Value,
 	) -> Result<(), RuntimeError> {
-		let top = self.top_frame();
-		let mut top = top.write().unwrap();
-		top.rec_lookup(ident, move |e| {
+		self.frame.lookup(ident, move |e| {
 			if !e.mutable {
-				return Err(RuntimeError::AssignmentToImmutableVariable);
+				return Err(
+					RuntimeError::AssignmentToImmutableVariable {
+						span: ident.span,
+						declaration: e.declaration,
+					},
+				);
 			}
 
-			e.value = value.clone();
+			e.value = value;
 
 			Ok(())
-		})?;
+		})
+	}
 
-		Ok(())
+	/// Fails unless `ident` can be assigned through, reporting the
+	/// assignment to the place at `span`.
+	pub fn check_mutable(
+		&self,
+		ident: &Ident,
+		span: Span,
+	) -> Result<(), RuntimeError> {
+		self.frame
+			.lookup(ident, |e| match e.mutable {
+				true => Ok(()),
+				false => Err(
+					RuntimeError::AssignmentToImmutableVariable {
+						span,
+						declaration: e.declaration,
+					},
+				),
+			})
 	}
 
-	pub fn load(&self, ident: &Ident) -> Result<Value, RuntimeError> {
-		let top = self.top_frame();
-		let mut top = top.write().unwrap();
+	/// Whether a top-level variable called `name` has been declared.
+	pub fn is_defined(&self, name: &str) -> bool {
+		self.frame
+			.globals
+			.read()
+			.unwrap()
+			.contains_key(&Ident::new_dummy(name))
+	}
 
-		top.rec_lookup(ident, |e| Ok(e.value.clone()))
+	/// Whether declarations would land in the outermost scope of the
+	/// program, outside of any block or closure.
+	pub fn is_top_level(&self) -> bool {
+		self.call_stack.is_empty() && self.frame.scope.is_none()
 	}
 
-	pub fn call(
-		&mut self,
-		capture: Arc<RwLock<Frame>>,
-		parameters: Parameters,
-		arguments: ArgumentValues,
-	) {
-		self.call_stack
-			.push(Arc::new(RwLock::new(Frame {
-				scope_stack: vec![Scope(
-					parameters
-						.0
-						.into_iter()
-						.zip(
-							arguments
-								.0
-								.into_iter()
-								.map(|value| SymbolInfo {
-									mutable: false,
-									value,
-								}),
-						)
-						.collect::<HashMap<_, _>>(),
-				)],
-				parent: Some(capture),
-			})));
+	pub fn load(&self, ident: &Ident) -> Result<Value, RuntimeError> {
+		self.frame
+			.lookup(ident, |e| Ok(e.value.clone()))
+	}
+
+	/// Enters a function whose body closes over `capture`. Parameters
+	/// are declared into the new scope afterwards.
+	pub fn call(&mut self, capture: Frame) {
+		let frame = Frame {
+			scope: Some(Scope::new(capture.scope)),
+			..capture
+		};
+
+		self.call_stack.push(std::mem::replace(
+			&mut self.frame,
+			frame,
+		));
 	}
 
 	pub fn ret(&mut self) {
-		self.call_stack.pop();
+		self.frame = self
+			.call_stack
+			.pop()
+			.expect("returning from a call");
 	}
 
 	pub fn push_scope(&mut self) {
-		self.top_frame()
-			.write()
-			.unwrap()
-			.scope_stack
-			.push(Scope(HashMap::new()));
+		self.frame.scope = Some(Scope::new(self.frame.scope.take()));
 	}
 
 	pub fn pop_scope(&mut self) {
-		self.top_frame()
-			.write()
-			.unwrap()
-			.scope_stack
-			.pop();
+		let scope = self
+			.frame
+			.scope
+			.take()
+			.expect("popping a scope");
+		self.frame.scope = scope.read().unwrap().parent.clone();
 	}
 }
@@ -1,78 +1,181 @@
-use std::fmt::{self, Display, Formatter};
+use std::{
+	fmt::{self, Display, Formatter},
+	path::PathBuf,
+};
 
 use dyn_lexer::LexError;
-use dyn_parser::ast::{BinExprKind, Ident, UnaryExprKind};
+use dyn_parser::{
+	ast::{BinExprKind, Ident, UnaryExprKind},
+	SyntaxError,
+};
 use dyn_span::{HasSpan, Span};
-use miette::{Diagnostic, LabeledSpan};
+use miette::{Diagnostic, LabeledSpan, Severity};
+use num_bigint::BigInt;
 use thiserror::Error;
 
-use crate::Value;
+use crate::{Arity, Value};
 
 #[derive(Debug, PartialEq, Error)]
 #[error("InterpreterError")]
 pub enum InterpreterError {
-	ParseError(#[from] ParseError),
+	/// Every syntax error in the code, in order. Never empty.
+	ParseError(Vec<ParseError>),
 	RuntimeError(#[from] RuntimeError),
+	/// A runtime error raised inside closure calls, along with those
+	/// calls, innermost first.
+	Traced {
+		#[source]
+		error: RuntimeError,
+		trace: Vec<TraceFrame>,
+	},
+}
+
+impl InterpreterError {
+	pub fn runtime_error(&self) -> Option<&RuntimeError> {
+		match self {
+			Self::ParseError(_) => None,
+			Self::RuntimeError(t) | Self::Traced { error: t, .. } => Some(t),
+		}
+	}
 }
 
 impl Diagnostic for InterpreterError {
 	fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
 		match self {
-			Self::ParseError(t) => t.labels(),
-			Self::RuntimeError(t) => t.labels(),
+			Self::ParseError(errors) => errors.first()?.labels(),
+			Self::RuntimeError(t) | Self::Traced { error: t, .. } => t.labels(),
 		}
 	}
+
+	fn help<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+		match self {
+			Self::ParseError(errors) => errors.first()?.help(),
+			Self::RuntimeError(t) | Self::Traced { error: t, .. } => t.help(),
+		}
+	}
+
+	fn related<'a>(
+		&'a self,
+	) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+		match self {
+			Self::ParseError(errors) => Some(Box::new(
+				errors
+					.iter()
+					.skip(1)
+					.map(|t| t as &dyn Diagnostic),
+			)),
+			Self::RuntimeError(t) => t.related(),
+			Self::Traced { error, trace } => Some(Box::new(
+				error
+					.related()
+					.into_iter()
+					.flatten()
+					.chain(
+						trace
+							.iter()
+							.map(|t| t as &dyn Diagnostic),
+					),
+			)),
+		}
+	}
+}
+
+/// A closure call that a runtime error unwound through.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub struct TraceFrame {
+	/// Name of the called closure, if it was bound with `let`.
+	pub function: Option<String>,
+	/// Span of the call expression.
+	pub span: Span,
+	/// How many more times the same call was made right above this
+	/// one, as in a recursive closure.
+	pub repeated: usize,
+}
+
+impl Display for TraceFrame {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match &self.function {
+			Some(name) => write!(f, "in `{name}`")?,
+			None => write!(f, "in anonymous function")?,
+		}
+
+		match self.repeated {
+			0 => {},
+			1 => write!(f, ", repeated 1 more time")?,
+			n => write!(f, ", repeated {n} more times")?,
+		}
+
+		Ok(())
+	}
+}
+
+impl Diagnostic for TraceFrame {
+	fn severity(&self) -> Option<Severity> { Some(Severity::Advice) }
+
+	fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+		Some(Box::new(
+			[LabeledSpan::at(self.span, "called here")].into_iter(),
+		))
+	}
 }
 
 #[derive(Debug, PartialEq, Error)]
 #[error("ParseError")]
 pub struct ParseError(#[from] pub dyn_parser::ParseError);
 
-impl Diagnostic for ParseError {
-	fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+impl ParseError {
+	/// Where the error is reported.
+	pub fn span(&self) -> Span {
 		use dyn_parser::ParseError;
 
 		match &self.0 {
-			ParseError::InvalidToken { .. } => unreachable!(),
-			ParseError::UnrecognizedEof { location, expected } => {
-				Some(Box::new(
-					[LabeledSpan::at(
-						*location..location + 1,
-						format!(
-							"Expected one of {} but found EOF",
-							display_vec(expected)
-						),
-					)]
-					.into_iter(),
-				))
+			ParseError::InvalidToken { location }
+			| ParseError::UnrecognizedEof { location, .. } =>
+				(*location..location + 1).into(),
+			ParseError::UnrecognizedToken { token, .. }
+			| ParseError::ExtraToken { token } => (token.0..token.2).into(),
+			ParseError::User { error } => match error.get() {
+				SyntaxError::LexError(
+					LexError::InvalidDigit { span, .. }
+					| LexError::MissingDigits { span }
+					| LexError::FloatOutOfRange { span },
+				) => *span,
+				_ => error.span(),
 			},
-			ParseError::UnrecognizedToken { token, expected } => {
-				Some(Box::new(
-					[LabeledSpan::at(
-						token.0..token.2,
-						format!(
-							"Expected one of {} but found {:?}",
-							display_vec(expected),
-							token.1
-						),
-					)]
-					.into_iter(),
-				))
-			},
-			ParseError::ExtraToken { .. } => unimplemented!(),
-			ParseError::User { error } => {
-				let span = error.span();
+		}
+	}
+}
 
-				let m = match error.get() {
-					LexError::InvalidIdentifier => "Invalid Identifier",
-					LexError::InvalidToken => "Invalid Token",
-				};
+impl Diagnostic for ParseError {
+	fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+		use dyn_parser::ParseError;
 
-				Some(Box::new(
-					[LabeledSpan::at(span, m)].into_iter(),
-				))
+		let m = match &self.0 {
+			ParseError::InvalidToken { .. } => "Invalid Token".to_owned(),
+			ParseError::UnrecognizedEof { expected, .. } => format!(
+				"Expected one of {} but found EOF",
+				display_vec(expected)
+			),
+			ParseError::UnrecognizedToken { token, expected } => format!(
+				"Expected one of {} but found {:?}",
+				display_vec(expected),
+				token.1
+			),
+			ParseError::ExtraToken { token } => {
+				format!("Expected EOF but found {:?}", token.1)
 			},
-		}
+			ParseError::User { error } => match error.get() {
+				SyntaxError::LexError(LexError::InvalidIdentifier) =>
+					"Invalid Identifier".to_owned(),
+				SyntaxError::LexError(LexError::InvalidToken) =>
+					"Invalid Token".to_owned(),
+				e => e.to_string(),
+			},
+		};
+
+		Some(Box::new(
+			[LabeledSpan::at(self.span(), m)].into_iter(),
+		))
 	}
 }
 
@@ -83,13 +186,72 @@ pub enum RuntimeError {
 	ReferenceError(#[from] ReferenceError),
 
 	#[error("Assignment to immutable variable")]
-	AssignmentToImmutableVariable,
+	AssignmentToImmutableVariable {
+		span: Span,
+		/// Where the variable was declared, if it isn't a builtin.
+		declaration: Option<Span>,
+	},
 
 	#[error("Already declared")]
 	AlreadyDeclared,
 
 	#[error("Type Error")]
 	TypeError(#[from] TypeError),
+
+	#[error("Arithmetic Error")]
+	ArithmeticError(#[from] ArithmeticError),
+
+	#[error("`return` outside of function")]
+	ReturnOutsideFunction { span: Span },
+
+	#[error("`break` outside of loop")]
+	BreakOutsideLoop { span: Span },
+
+	#[error("`continue` outside of loop")]
+	ContinueOutsideLoop { span: Span },
+
+	#[error("panicked: {message}")]
+	Panic { message: Value, span: Span },
+
+	#[error("assertion failed")]
+	AssertionFailed { value: Value, span: Span },
+
+	#[error("no arm matched")]
+	NoMatch { value: Value, span: Span },
+
+	#[error("value doesn't match the pattern")]
+	PatternMismatch { value: Value, span: Span },
+
+	#[error("index must not be negative")]
+	NegativeIndex { index: BigInt, span: Span },
+
+	#[error("index out of bounds")]
+	IndexOutOfBounds {
+		index: usize,
+		len: usize,
+		span: Span,
+	},
+
+	#[error("range step must not be zero")]
+	ZeroStep { span: Span },
+
+	#[error("Module Error")]
+	ModuleError(#[from] ModuleError),
+
+	#[error("`export` outside of module top level")]
+	ExportOutsideTopLevel { span: Span },
+
+	#[error("wrong number of arguments")]
+	ArgumentCount {
+		expected: Arity,
+		found: usize,
+		span: Span,
+		/// Parameters of the called closure, if it is one.
+		definition: Option<Span>,
+	},
+
+	#[error("maximum call depth exceeded")]
+	StackOverflow { depth: usize, span: Span },
 }
 
 impl Diagnostic for RuntimeError {
@@ -97,6 +259,30 @@ impl Diagnostic for RuntimeError {
 		match self {
 			Self::TypeError(t) => Some(t),
 			Self::ReferenceError(t) => Some(t),
+			Self::ArithmeticError(t) => Some(t),
+			Self::ModuleError(t) => Some(t),
+			_ => None,
+		}
+	}
+
+	fn help<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+		match self {
+			Self::AssignmentToImmutableVariable {
+				declaration: Some(_),
+				..
+			} => Some(Box::new(
+				"declare it with `let!` to make it mutable",
+			)),
+			_ => None,
+		}
+	}
+
+	fn related<'a>(
+		&'a self,
+	) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+		match self {
+			Self::ReferenceError(t) => t.related(),
+			Self::ModuleError(t) => t.related(),
 			_ => None,
 		}
 	}
@@ -105,6 +291,98 @@ impl Diagnostic for RuntimeError {
 		match self {
 			RuntimeError::TypeError(t) => t.labels(),
 			Self::ReferenceError(t) => t.labels(),
+			Self::ArithmeticError(t) => t.labels(),
+			Self::ModuleError(t) => t.labels(),
+			Self::AssignmentToImmutableVariable { span, declaration } =>
+				Some(Box::new(
+					[LabeledSpan::at(*span, "assigned here")]
+						.into_iter()
+						.chain(declaration.map(|declaration| {
+							LabeledSpan::at(
+								declaration,
+								"declared immutable here",
+							)
+						})),
+				)),
+			Self::ReturnOutsideFunction { span } => Some(Box::new(
+				[LabeledSpan::at(*span, "cannot `return` from here")]
+					.into_iter(),
+			)),
+			Self::BreakOutsideLoop { span } => Some(Box::new(
+				[LabeledSpan::at(*span, "cannot `break` from here")]
+					.into_iter(),
+			)),
+			Self::ContinueOutsideLoop { span } => Some(Box::new(
+				[LabeledSpan::at(*span, "cannot `continue` from here")]
+					.into_iter(),
+			)),
+			Self::Panic { message, span } => Some(Box::new(
+				[LabeledSpan::at(*span, message.to_string())].into_iter(),
+			)),
+			Self::NegativeIndex { index, span } => Some(Box::new(
+				[LabeledSpan::at(
+					*span,
+					format!("{index} is less than zero"),
+				)]
+				.into_iter(),
+			)),
+			Self::IndexOutOfBounds { index, len, span } => Some(Box::new(
+				[LabeledSpan::at(
+					*span,
+					format!("the length is {len} but the index is {index}"),
+				)]
+				.into_iter(),
+			)),
+			Self::ZeroStep { span } => Some(Box::new(
+				[LabeledSpan::at(*span, "this evaluated to 0")].into_iter(),
+			)),
+			Self::AssertionFailed { value, span }
+			| Self::NoMatch { value, span } => Some(Box::new(
+				[LabeledSpan::at(
+					*span,
+					format!("this evaluated to {}", value.to_debug()),
+				)]
+				.into_iter(),
+			)),
+			Self::PatternMismatch { value, span } => Some(Box::new(
+				[LabeledSpan::at(
+					*span,
+					format!(
+						"this doesn't match {}",
+						value.to_debug()
+					),
+				)]
+				.into_iter(),
+			)),
+			Self::ArgumentCount {
+				expected,
+				found,
+				span,
+				definition,
+			} => Some(Box::new(
+				[LabeledSpan::at(
+					*span,
+					format!("expected {expected}, found {found}"),
+				)]
+				.into_iter()
+				.chain(definition.map(|definition| {
+					LabeledSpan::at(definition, "parameters defined here")
+				})),
+			)),
+			Self::StackOverflow { depth, span } => Some(Box::new(
+				[LabeledSpan::at(
+					*span,
+					format!("this call is nested more than {depth} deep"),
+				)]
+				.into_iter(),
+			)),
+			Self::ExportOutsideTopLevel { span } => Some(Box::new(
+				[LabeledSpan::at(
+					*span,
+					"only top-level bindings can be exported",
+				)]
+				.into_iter(),
+			)),
 			_ => None,
 		}
 	}
@@ -124,6 +402,46 @@ pub enum TypeError {
 		rhs: Value,
 		rhs_span: Span,
 	},
+	NotCallable {
+		value: Value,
+		span: Span,
+	},
+	PropertyAccess {
+		prop: Ident,
+		value: Value,
+		span: Span,
+	},
+	NotIndexable {
+		value: Value,
+		span: Span,
+	},
+	InvalidIndex {
+		index: Value,
+		span: Span,
+	},
+	NonBooleanCondition {
+		value: Value,
+		span: Span,
+	},
+	NonBooleanOperand {
+		op: BinExprKind,
+		value: Value,
+		span: Span,
+	},
+	NotIterable {
+		value: Value,
+		span: Span,
+	},
+	InvalidRangeBound {
+		value: Value,
+		span: Span,
+	},
+	InvalidArgument {
+		function: &'static str,
+		expected: &'static str,
+		value: Value,
+		span: Span,
+	},
 }
 
 impl Display for TypeError {
@@ -174,6 +492,49 @@ impl Display for TypeError {
 					)
 				},
 			},
+			TypeError::NotCallable { value, .. } => format!(
+				"cannot call value of type `{}`",
+				value.get_type().type_name()
+			),
+			TypeError::PropertyAccess { prop, value, .. } => format!(
+				"cannot access property `{}` of type `{}`",
+				prop.symbol(),
+				value.get_type().type_name()
+			),
+			TypeError::NotIndexable { value, .. } => format!(
+				"cannot index into value of type `{}`",
+				value.get_type().type_name()
+			),
+			TypeError::InvalidIndex { index, .. } => format!(
+				"cannot index array with value of type `{}`",
+				index.get_type().type_name()
+			),
+			TypeError::NonBooleanCondition { value, .. } => format!(
+				"expected condition of type `bool`, found `{}`",
+				value.get_type().type_name()
+			),
+			TypeError::NonBooleanOperand { op, value, .. } => format!(
+				"expected operand of `{}` to be of type `bool`, found `{}`",
+				op.as_ref(),
+				value.get_type().type_name()
+			),
+			TypeError::NotIterable { value, .. } => format!(
+				"cannot iterate over value of type `{}`",
+				value.get_type().type_name()
+			),
+			TypeError::InvalidRangeBound { value, .. } => format!(
+				"expected range bound of type `integer`, found `{}`",
+				value.get_type().type_name()
+			),
+			TypeError::InvalidArgument {
+				function,
+				expected,
+				value,
+				..
+			} => format!(
+				"`{function}` expected {expected}, found `{}`",
+				value.get_type().type_name()
+			),
 		};
 
 		write!(f, "{m}")
@@ -201,6 +562,88 @@ impl Diagnostic for TypeError {
 				]
 				.into_iter(),
 			)),
+			TypeError::NotCallable { value, span }
+			| TypeError::PropertyAccess { value, span, .. }
+			| TypeError::NotIndexable { value, span }
+			| TypeError::InvalidIndex { index: value, span }
+			| TypeError::NonBooleanCondition { value, span }
+			| TypeError::NonBooleanOperand { value, span, .. }
+			| TypeError::NotIterable { value, span }
+			| TypeError::InvalidRangeBound { value, span }
+			| TypeError::InvalidArgument { value, span, .. } => Some(Box::new(
+				[value_to_message(*span, value.clone())].into_iter(),
+			)),
+		}
+	}
+}
+
+#[derive(Debug, PartialEq, Error)]
+pub enum ArithmeticError {
+	DivisionByZero {
+		op: BinExprKind,
+		lhs: Value,
+		lhs_span: Span,
+		rhs_span: Span,
+	},
+	Overflow {
+		op: BinExprKind,
+		lhs: Value,
+		lhs_span: Span,
+		rhs: Value,
+		rhs_span: Span,
+	},
+}
+
+impl Display for ArithmeticError {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		let m = match self {
+			ArithmeticError::DivisionByZero { op, .. } => match op {
+				BinExprKind::Mod =>
+					"attempt to calculate the remainder with a \
+				                     divisor of zero",
+				_ => "attempt to divide by zero",
+			},
+			ArithmeticError::Overflow { op, .. } => match op {
+				BinExprKind::Add => "attempt to add with overflow",
+				BinExprKind::Sub => "attempt to subtract with overflow",
+				BinExprKind::Mul => "attempt to multiply with overflow",
+				BinExprKind::Div => "attempt to divide with overflow",
+				_ => "attempt to calculate the remainder with overflow",
+			},
+		};
+
+		write!(f, "{m}")
+	}
+}
+
+impl Diagnostic for ArithmeticError {
+	fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+		match self {
+			ArithmeticError::DivisionByZero {
+				lhs,
+				lhs_span,
+				rhs_span,
+				..
+			} => Some(Box::new(
+				[
+					value_to_message(*lhs_span, lhs.clone()),
+					LabeledSpan::at(*rhs_span, "this evaluates to zero"),
+				]
+				.into_iter(),
+			)),
+			ArithmeticError::Overflow {
+				lhs,
+				lhs_span,
+				rhs,
+				rhs_span,
+				..
+			} => Some(Box::new(
+				[
+					value_to_message(*lhs_span, lhs.clone()),
+					value_to_message(*rhs_span, rhs.clone()),
+				]
+				.into_iter(),
+			)),
 		}
 	}
 }
@@ -209,6 +652,11 @@ impl Diagnostic for TypeError {
 pub enum ReferenceError {
 	#[error("UndefinedIdentifier")]
 	UndefinedIdentifier { ident: Ident },
+	/// Every undefined identifier found before running a script, in
+	/// order, when there is more than one. Each is an
+	/// `UndefinedIdentifier`.
+	#[error("UndefinedIdentifier")]
+	UndefinedIdentifiers(Vec<ReferenceError>),
 }
 
 impl Diagnostic for ReferenceError {
@@ -224,8 +672,137 @@ impl Diagnostic for ReferenceError {
 				)]
 				.into_iter(),
 			)),
+			ReferenceError::UndefinedIdentifiers(errors) =>
+				errors.first()?.labels(),
 		}
 	}
+
+	fn related<'a>(
+		&'a self,
+	) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+		match self {
+			ReferenceError::UndefinedIdentifiers(errors) => Some(Box::new(
+				errors
+					.iter()
+					.skip(1)
+					.map(|t| t as &dyn Diagnostic),
+			)),
+			_ => None,
+		}
+	}
+}
+
+#[derive(Debug, PartialEq, Error)]
+pub enum ModuleError {
+	#[error("cannot read module `{}`", path.display())]
+	NotFound {
+		path: PathBuf,
+		reason: String,
+		span: Span,
+	},
+
+	#[error("import cycle")]
+	Cycle { cycle: Vec<PathBuf>, span: Span },
+
+	#[error("error in module `{}`", failure.path.display())]
+	Failed {
+		failure: Box<ModuleFailure>,
+		span: Span,
+	},
+
+	/// Raised inside a closure from another module.
+	#[error("error in module `{}`", failure.path.display())]
+	Raised {
+		failure: Box<ModuleFailure>,
+		span: Span,
+	},
+
+	#[error("`{}` is not exported by `{}`", ident.symbol(), path.display())]
+	MissingExport { ident: Ident, path: PathBuf },
+}
+
+impl Diagnostic for ModuleError {
+	fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+		let (span, m) = match self {
+			ModuleError::NotFound { reason, span, .. } =>
+				(*span, reason.clone()),
+			ModuleError::Cycle { cycle, span } => (
+				*span,
+				format!(
+					"imports a module that is still being loaded: {}",
+					cycle
+						.iter()
+						.map(|p| format!("`{}`", p.display()))
+						.collect::<Vec<_>>()
+						.join(" -> ")
+				),
+			),
+			ModuleError::Failed { span, .. } => (
+				*span,
+				"while loading this module".to_owned(),
+			),
+			ModuleError::Raised { span, .. } => (
+				*span,
+				"while calling a closure from this module".to_owned(),
+			),
+			ModuleError::MissingExport { ident, .. } => (
+				ident.span(),
+				"not exported by this module".to_owned(),
+			),
+		};
+
+		Some(Box::new(
+			[LabeledSpan::at(span, m)].into_iter(),
+		))
+	}
+
+	fn related<'a>(
+		&'a self,
+	) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+		match self {
+			ModuleError::Failed { failure, .. }
+			| ModuleError::Raised { failure, .. } => Some(Box::new(
+				[&**failure as &dyn Diagnostic].into_iter(),
+			)),
+			_ => None,
+		}
+	}
+}
+
+/// An error raised while evaluating an imported module, along with
+/// that module's source so its labels can be rendered.
+#[derive(Debug, PartialEq, Error)]
+#[error("in `{}`", path.display())]
+pub struct ModuleFailure {
+	pub path: PathBuf,
+	pub code: String,
+	pub error: InterpreterError,
+}
+
+impl Diagnostic for ModuleFailure {
+	fn source_code(&self) -> Option<&dyn miette::SourceCode> {
+		Some(&self.code)
+	}
+
+	fn diagnostic_source(&self) -> Option<&dyn Diagnostic> {
+		match &self.error {
+			InterpreterError::ParseError(errors) => errors
+				.first()
+				.map(|t| t as &dyn Diagnostic),
+			InterpreterError::RuntimeError(t)
+			| InterpreterError::Traced { error: t, .. } => Some(t),
+		}
+	}
+
+	fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+		self.error.labels()
+	}
+
+	fn related<'a>(
+		&'a self,
+	) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+		self.error.related()
+	}
 }
 
 fn value_to_message(span: Span, v: Value) -> LabeledSpan {
@@ -240,9 +817,7 @@ fn value_to_message(span: Span, v: Value) -> LabeledSpan {
 }
 
 fn display_vec<T>(v: &[T]) -> String
-where
-	T: Display,
-{
+where T: Display {
 	v.iter()
 		.map(|e| e.to_string())
 		.collect::<Vec<_>>()
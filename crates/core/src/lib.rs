@@ -1,34 +1,60 @@
-use std::collections::HashMap;
+use std::{
+	collections::HashMap,
+	fs,
+	path::{Path, PathBuf},
+	sync::Arc,
+};
 
+use check::Lint;
 use dyn_parser::{
 	ast::{
-		self, Array, BinExpr, BinExprKind, Boolean, Code, Expr, ExprKind,
-		Function, Ident, Integer, Literal, Nil, StringT, TemplateString,
-		UnaryExpr, UnaryExprKind,
+		self, Array, BinExpr, BinExprKind, Boolean, Code, Else, Expr, ExprKind,
+		Float, Function, Ident, Integer, Literal, Nil, Parameter, Parameters,
+		Pattern, PatternKind, Place, PlaceKind, StringT, TemplateString,
+		UnaryExpr, Use,
 	},
-	parse_code,
+	parse_code_recovering,
 };
-use dyn_span::HasSpan;
-use environment::Environment;
-pub use error::{InterpreterError, ReferenceError, RuntimeError};
-use error::{ParseError, TypeError};
+use dyn_span::{HasSpan, Span};
+use environment::{Environment, Frame};
+pub use error::{InterpreterError, ReferenceError, RuntimeError, TraceFrame};
+use error::{ModuleError, ModuleFailure, ParseError, TypeError};
+use module::{Modules, Source};
+use pattern::destructure;
+use place::{to_index, Accessor};
+use resolver::Resolver;
+use stdlib::Arguments;
 use value::Record;
-pub use value::{ArgumentValues, BuiltinFunction, FunctionValue, Value};
+pub use value::{
+	ArgumentValues, Arity, BuiltinFunction, CallContext, FunctionValue, Value,
+};
+use vm::{Globals, Vm};
 
+pub mod check;
 mod environment;
 pub mod error;
+mod module;
+mod operator;
+mod pattern;
+mod place;
+mod resolver;
+mod stdlib;
 mod value;
+mod vm;
 
 #[derive(Debug)]
 struct SymbolInfo {
 	mutable: bool,
 	value: Value,
+	/// Span of the identifier that declared it, `None` for builtins.
+	declaration: Option<Span>,
 }
 
 enum Tree {
 	Nil(Nil),
 	Boolean(Boolean),
 	Integer(Integer),
+	Float(Float),
 	StringT(StringT),
 	Literal(Literal),
 	Ident(Ident),
@@ -43,556 +69,3517 @@ enum Tree {
 	Code(Code),
 }
 
+/// Non-local exit from [`Interpreter::eval`].
+///
+/// Control flow expressions travel up the same path as errors until
+/// they reach the closure call or loop that handles them.
+enum Unwind {
+	/// Boxed so that every `Result` returned while evaluating stays
+	/// small, as errors are rare.
+	Error(Box<RuntimeError>),
+	Return(Value, Span),
+	Break(Value, Span),
+	Continue(Span),
+}
+
+impl Unwind {
+	/// Converts control flow that escaped its enclosing construct
+	/// into the corresponding error.
+	fn into_error(self) -> RuntimeError {
+		match self {
+			Unwind::Error(e) => *e,
+			Unwind::Return(_, span) =>
+				RuntimeError::ReturnOutsideFunction { span },
+			Unwind::Break(_, span) => RuntimeError::BreakOutsideLoop { span },
+			Unwind::Continue(span) =>
+				RuntimeError::ContinueOutsideLoop { span },
+		}
+	}
+}
+
+impl<T> From<T> for Unwind
+where T: Into<RuntimeError>
+{
+	fn from(value: T) -> Self { Self::Error(Box::new(value.into())) }
+}
+
+fn closure_arity(parameters: &Parameters) -> Arity {
+	match parameters.rest {
+		Some(_) => Arity::at_least(parameters.required()),
+		None => Arity::between(
+			parameters.required(),
+			parameters.positional.len(),
+		),
+	}
+}
+
+/// Closure calls allowed to be active at once unless configured with
+/// [`Interpreter::set_max_call_depth`].
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+
+/// Stack left before a closure call switches to a fresh segment.
+const STACK_RED_ZONE: usize = 1024 * 1024;
+
+/// Size of each stack segment allocated for deep closure calls.
+const STACK_SEGMENT_SIZE: usize = 16 * 1024 * 1024;
+
+/// A fresh global scope with `builtins` declared in it.
+fn global_environment(builtins: &HashMap<Ident, Value>) -> Environment {
+	let mut mem = Environment::new();
+
+	for (ident, value) in builtins {
+		mem.declare_builtin(ident, value.clone());
+	}
+
+	mem
+}
+
+/// How an [`Interpreter`] evaluates code.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Backend {
+	/// Walks the syntax tree directly.
+	#[default]
+	TreeWalker,
+	/// Compiles code to bytecode first and runs it on a virtual
+	/// machine.
+	Bytecode,
+}
+
 pub struct Interpreter {
+	backend: Backend,
 	mem: Environment,
+	vm: Vm,
+	/// Standard library and user builtins, declared in every module.
+	builtins: HashMap<Ident, Value>,
+	/// Bindings exported from the module being evaluated.
+	exports: Vec<Ident>,
+	modules: Modules,
+	/// Closure calls the current error has unwound through.
+	trace: Vec<TraceFrame>,
+	/// Module whose code is being evaluated, `None` for code that
+	/// wasn't read from a file.
+	source: Option<Arc<Source>>,
+	/// Number of closure calls currently being evaluated.
+	depth: usize,
+	max_call_depth: usize,
 }
 
 impl Interpreter {
 	pub fn init() -> Self {
-		Self {
-			mem: Environment::new(),
-		}
+		Self::init_with_builtins(HashMap::new())
+			.expect("standard library builtins have distinct names")
 	}
 
+	/// Creates an interpreter with `builtin` declared next to the
+	/// standard library.
 	pub fn init_with_builtins(
 		builtin: HashMap<Ident, Value>,
 	) -> Result<Self, RuntimeError> {
-		let mut mem = Environment::new();
+		Self::init_with_globals(stdlib::builtins(), builtin)
+	}
+
+	/// Creates an interpreter with only `builtin` declared, for
+	/// embedders that want full control over what scripts can call.
+	pub fn init_without_stdlib(
+		builtin: HashMap<Ident, Value>,
+	) -> Result<Self, RuntimeError> {
+		Self::init_with_globals(HashMap::new(), builtin)
+	}
 
+	fn init_with_globals(
+		mut builtins: HashMap<Ident, Value>,
+		builtin: HashMap<Ident, Value>,
+	) -> Result<Self, RuntimeError> {
 		for (ident, value) in builtin {
-			mem.declare(&ident, value, false)?;
+			if builtins.insert(ident, value).is_some() {
+				return Err(RuntimeError::AlreadyDeclared);
+			}
 		}
 
-		Ok(Self { mem })
+		Ok(Self {
+			backend: Backend::default(),
+			mem: global_environment(&builtins),
+			vm: Vm::new(Globals::shared(&builtins)),
+			builtins,
+			exports: vec![],
+			modules: Modules::default(),
+			trace: vec![],
+			source: None,
+			depth: 0,
+			max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+		})
+	}
+
+	/// Limits how many closure calls can be active at once. Calls
+	/// beyond the limit fail with [`RuntimeError::StackOverflow`].
+	pub fn set_max_call_depth(&mut self, depth: usize) {
+		self.max_call_depth = depth;
 	}
 
+	/// Selects how code is evaluated from now on. Each backend keeps
+	/// its own global scope.
+	pub fn set_backend(&mut self, backend: Backend) { self.backend = backend; }
+
+	/// Parses `code`, reporting all of its syntax errors rather than
+	/// only the first one.
 	fn parse_code(&mut self, code: &str) -> Result<Code, InterpreterError> {
-		parse_code(code)
-			.map_err(|e| InterpreterError::ParseError(ParseError(e)))
+		let (ast, errors) = parse_code_recovering(code);
+
+		match errors.is_empty() {
+			true => Ok(ast),
+			false => Err(InterpreterError::ParseError(
+				errors
+					.into_iter()
+					.map(ParseError)
+					.collect(),
+			)),
+		}
 	}
 
 	pub fn run(&mut self, code: &str) -> Result<Value, InterpreterError> {
 		let ast = self.parse_code(code)?;
 
+		self.trace.clear();
 		let res = self
-			.eval(Tree::Code(ast))
-			.map_err(InterpreterError::RuntimeError)?;
+			.eval_code(ast)
+			.map_err(|e| self.traced(e))?;
 
 		Ok(res)
 	}
 
-	fn eval(&mut self, ast: Tree) -> Result<Value, RuntimeError> {
+	/// Evaluates a whole script, failing before anything runs if it
+	/// uses a variable that isn't declared anywhere it could be seen.
+	fn eval_code(&mut self, ast: Code) -> Result<Value, RuntimeError> {
+		let (resolution, undefined) =
+			Resolver::resolve(&ast, |name| self.is_defined(name));
+
+		let mut undefined = undefined
+			.into_iter()
+			.map(|ident| ReferenceError::UndefinedIdentifier { ident })
+			.collect::<Vec<_>>();
+
+		match undefined.len() {
+			0 => {},
+			1 => Err(undefined.remove(0))?,
+			_ => Err(ReferenceError::UndefinedIdentifiers(
+				undefined,
+			))?,
+		}
+
+		match self.backend {
+			Backend::TreeWalker => {
+				self.mem.enter(resolution);
+				self.eval(Tree::Code(ast))
+					.map_err(Unwind::into_error)
+			},
+			Backend::Bytecode => self.run_bytecode(&ast),
+		}
+	}
+
+	/// Whether a top-level variable called `name` has been declared.
+	fn is_defined(&self, name: &str) -> bool {
+		match self.backend {
+			Backend::TreeWalker => self.mem.is_defined(name),
+			Backend::Bytecode => self
+				.vm
+				.globals
+				.read()
+				.unwrap()
+				.contains(name),
+		}
+	}
+
+	/// Reads a top-level binding of the code being evaluated.
+	fn load_global(&self, ident: &Ident) -> Result<Value, RuntimeError> {
+		match self.backend {
+			Backend::TreeWalker => Ok(self.mem.load(ident)?),
+			Backend::Bytecode => self
+				.vm
+				.globals
+				.read()
+				.unwrap()
+				.load(ident),
+		}
+	}
+
+	/// Checks `code` for mistakes without running it, treating the
+	/// variables declared so far as defined.
+	pub fn check(&mut self, code: &str) -> Vec<Lint> {
+		check::check_source(code, |name| self.is_defined(name))
+	}
+
+	/// Attaches the closure calls collected while unwinding to
+	/// `error`.
+	fn traced(&mut self, error: RuntimeError) -> InterpreterError {
+		let trace = std::mem::take(&mut self.trace);
+
+		if trace.is_empty() {
+			InterpreterError::RuntimeError(error)
+		} else {
+			InterpreterError::Traced { error, trace }
+		}
+	}
+
+	/// Runs `code` as the contents of the file at `path`, so that it
+	/// can `use` other files relative to it.
+	pub fn run_file<P>(
+		&mut self,
+		path: P,
+		code: &str,
+	) -> Result<Value, InterpreterError>
+	where
+		P: AsRef<Path>,
+	{
+		let path = path.as_ref();
+		let path = path
+			.canonicalize()
+			.unwrap_or_else(|_| path.to_owned());
+		let source = Arc::new(Source {
+			path: path.clone(),
+			code: code.to_owned(),
+		});
+
+		let outer = self.source.replace(source);
+		self.modules.enter(path);
+		let res = self.run(code);
+		self.modules.exit();
+		self.source = outer;
+
+		res
+	}
+
+	/// Wraps `error` raised by a call at `span` to a closure defined
+	/// in `source` if the caller is in another module, so that the
+	/// error and the calls it unwound through inside that module are
+	/// rendered against the module's code.
+	fn leave_module(
+		&mut self,
+		source: Option<&Arc<Source>>,
+		caller: Option<&Arc<Source>>,
+		error: RuntimeError,
+		span: Span,
+	) -> RuntimeError {
+		let Some(source) = source else {
+			return error;
+		};
+
+		if caller.is_some_and(|caller| Arc::ptr_eq(source, caller)) {
+			return error;
+		}
+
+		ModuleError::Raised {
+			failure: Box::new(ModuleFailure {
+				path: source.path.clone(),
+				code: source.code.clone(),
+				error: self.traced(error),
+			}),
+			span,
+		}
+		.into()
+	}
+
+	// NOTE: every closure call recurses through `eval` and `eval_expr`
+	// several times, so they only dispatch and leave anything needing
+	// locals or `?` to other functions; otherwise debug builds, which
+	// give each temporary its own stack slot, use hundreds of kilobytes
+	// of stack per call
+	fn eval(&mut self, ast: Tree) -> Result<Value, Unwind> {
 		match ast {
 			Tree::Nil(_) => Ok(Value::Nil),
 			Tree::Boolean(i) => Ok(Value::Boolean(i.value())),
 			Tree::Integer(i) => Ok(Value::Integer(i.value)),
+			Tree::Float(i) => Ok(Value::Float(i.value)),
 			Tree::StringT(i) => Ok(Value::String(i.value)),
 			Tree::Literal(i) => match i {
 				Literal::Nil(i) => self.eval(Tree::Nil(i)),
 				Literal::Boolean(i) => self.eval(Tree::Boolean(i)),
 				Literal::Integer(i) => self.eval(Tree::Integer(i)),
+				Literal::Float(i) => self.eval(Tree::Float(i)),
 				Literal::String(i) => self.eval(Tree::StringT(i)),
 			},
-			Tree::Ident(ident) => Ok(self.mem.load(&ident)?),
-			Tree::TemplateString(TemplateString {
-				fragments, values, ..
-			}) => {
-				let mut buf = String::new();
+			Tree::Ident(ident) => self
+				.mem
+				.load(&ident)
+				.map_err(Unwind::from),
+			Tree::TemplateString(i) => self.eval_template_string(i),
+			Tree::Array(i) => self.eval_array(i),
+			Tree::Record(i) => self.eval_record(i),
+			Tree::Function(_) => todo!(),
+			Tree::UnaryExpr(un) => self.eval_unary_expr(un),
+			Tree::BinExpr(bin) => self.eval_bin_expr(bin),
+			Tree::Expr(i) => self.eval_expr(i),
+			Tree::Code(i) => self.eval_stmts(i),
+		}
+	}
 
-				let mut fragments_iter = fragments.iter();
+	fn eval_expr(&mut self, i: Expr) -> Result<Value, Unwind> {
+		let span = i.span;
+
+		match i.kind {
+			ExprKind::Literal(i) => self.eval(Tree::Literal(i)),
+			ExprKind::Ident(i) => self.eval(Tree::Ident(i)),
+			ExprKind::UnaryExpr(i) => self.eval(Tree::UnaryExpr(i)),
+			ExprKind::TemplateString(i) => self.eval(Tree::TemplateString(i)),
+			ExprKind::Array(i) => self.eval(Tree::Array(i)),
+			ExprKind::Record(i) => self.eval(Tree::Record(i)),
+			ExprKind::Function(f) => Ok(Value::Function(
+				FunctionValue::Closure {
+					body: f,
+					capture: self.mem.top_frame(),
+					name: None,
+					source: self.source.clone(),
+				},
+			)),
+			ExprKind::Call(callee, arguments) =>
+				self.eval_call(*callee, arguments.0, span),
+			ExprKind::Prop(i, j) => self.eval_prop(*i, j),
+			ExprKind::Index(i, j) => self.eval_index_expr(*i, *j),
+			ExprKind::BinExpr(i) => self.eval(Tree::BinExpr(i)),
+			ExprKind::Range {
+				start,
+				end,
+				step,
+				inclusive,
+			} => self.eval_range(
+				*start,
+				*end,
+				step.map(|step| *step),
+				inclusive,
+			),
+			ExprKind::Assign(place, j) => self.assign(place, None, *j),
+			ExprKind::AddAssign(place, j) =>
+				self.assign(place, Some(BinExprKind::Add), *j),
+			ExprKind::SubAssign(place, j) =>
+				self.assign(place, Some(BinExprKind::Sub), *j),
+			ExprKind::MulAssign(place, j) =>
+				self.assign(place, Some(BinExprKind::Mul), *j),
+			ExprKind::DivAssign(place, j) =>
+				self.assign(place, Some(BinExprKind::Div), *j),
+			ExprKind::ModAssign(place, j) =>
+				self.assign(place, Some(BinExprKind::Mod), *j),
+			ExprKind::Block(b) => self.eval_block(b),
+			ExprKind::Declare(ident, value) =>
+				self.eval_declare(ident, *value, false),
+			ExprKind::DeclareMut(ident, value) =>
+				self.eval_declare(ident, *value, true),
+			ExprKind::Destructure {
+				pattern,
+				value,
+				mutable,
+			} => self.eval_destructure(&pattern, *value, mutable),
+			ExprKind::If { condition, yes, no } =>
+				self.eval_if(*condition, yes, no),
+			ExprKind::For {
+				collection,
+				item,
+				body,
+			} => self.eval_for(*collection, &item, body),
+			ExprKind::Match { scrutinee, arms } =>
+				self.eval_match(*scrutinee, arms),
+			ExprKind::Panic(message) => self
+				.eval(Tree::Expr(*message))
+				.and_then(|message| {
+					Err(RuntimeError::Panic { message, span }.into())
+				}),
+			ExprKind::Assert(condition) => self.eval_assert(*condition),
+			ExprKind::Return(value) => self
+				.eval(Tree::Expr(*value))
+				.and_then(|value| Err(Unwind::Return(value, span))),
+			ExprKind::Break(value) => self
+				.eval(Tree::Expr(*value))
+				.and_then(|value| Err(Unwind::Break(value, span))),
+			ExprKind::Continue(value) => self
+				.eval(Tree::Expr(*value))
+				.and_then(|_| Err(Unwind::Continue(span))),
+			ExprKind::Use(u) => self.eval_use(u),
+			ExprKind::Export(declaration) =>
+				self.eval_export(*declaration, span),
+		}
+	}
 
-				for value in values {
-					buf.push_str(fragments_iter.next().unwrap());
+	fn eval_stmts(&mut self, code: Code) -> Result<Value, Unwind> {
+		let mut last = Value::Nil;
+		for i in code.stmts {
+			last = self.eval(Tree::Expr(i))?;
+		}
+		Ok(last)
+	}
 
-					let value = self.eval(Tree::Expr(value))?;
-					buf.push_str(&value.to_string());
-				}
+	fn eval_block(&mut self, code: Code) -> Result<Value, Unwind> {
+		self.mem.push_scope();
+		let v = self.eval(Tree::Code(code));
+		self.mem.pop_scope();
 
-				buf.push_str(fragments_iter.next().unwrap());
+		v
+	}
 
-				Ok(Value::String(buf))
-			},
-			Tree::Array(i) => Ok(Value::Array(
-				i.elements
-					.into_iter()
-					.map(|e| self.eval(Tree::Expr(e)))
-					.collect::<Result<Vec<_>, RuntimeError>>()?,
-			)),
-			Tree::Record(i) => Ok(Value::Record(Record {
-				fields: i.fields.into_iter().try_fold(
-					HashMap::<Ident, Value>::new(),
-					|mut h, (k, v)| -> Result<HashMap<_, _>, RuntimeError> {
-						h.insert(k, self.eval(Tree::Expr(v))?);
-						Ok(h)
-					},
-				)?,
-			})),
-			Tree::Function(_) => todo!(),
-			Tree::UnaryExpr(un) => {
-				let span = un.span();
-				let op = un.op;
-				let expr = *un.expr;
+	fn eval_array(&mut self, i: Array) -> Result<Value, Unwind> {
+		Ok(Value::Array(
+			i.elements
+				.into_iter()
+				.map(|e| self.eval(Tree::Expr(e)))
+				.collect::<Result<Vec<_>, Unwind>>()?
+				.into(),
+		))
+	}
 
-				let i = self.eval(Tree::Expr(expr))?;
+	fn eval_unary_expr(&mut self, un: UnaryExpr) -> Result<Value, Unwind> {
+		let span = un.span();
+		let i = self.eval(Tree::Expr(*un.expr))?;
 
-				Ok(match (op, i.clone()) {
-					(UnaryExprKind::Minus, Value::Integer(i)) => {
-						Value::Integer(-i)
-					},
-					(UnaryExprKind::Not, Value::Boolean(i)) => {
-						Value::Boolean(!i)
-					},
-					_ => Err(TypeError::UnaryOp {
-						op,
-						expr: i,
-						expr_span: span,
-					})?,
-				})
-			},
-			Tree::BinExpr(bin) => {
-				// let span = bin.span();
-				let lhs = *bin.lhs;
-				let rhs = *bin.rhs;
+		Ok(operator::unary(un.op, i, span)?)
+	}
 
-				let op = bin.op;
+	fn eval_prop(&mut self, i: Expr, j: Ident) -> Result<Value, Unwind> {
+		let span = i.span();
+		let i = self.eval(Tree::Expr(i))?;
 
-				let lhs_span = lhs.span();
-				let rhs_span = rhs.span();
+		Ok(Accessor::Prop(j).get(i, span)?)
+	}
 
-				let i = self.eval(Tree::Expr(lhs))?;
-				let j = self.eval(Tree::Expr(rhs))?;
+	fn eval_destructure(
+		&mut self,
+		pattern: &Pattern,
+		value: Expr,
+		mutable: bool,
+	) -> Result<Value, Unwind> {
+		let value = self.eval(Tree::Expr(value))?;
+		self.bind_pattern(pattern, value.clone(), mutable)?;
+		Ok(value)
+	}
 
-				Ok(match (i, op, j) {
-					(
-						Value::Integer(i),
-						BinExprKind::Add,
-						Value::Integer(j),
-					) => Value::Integer(i + j),
-					(Value::Integer(i), BinExprKind::Add, Value::String(s)) => {
-						Value::String(format!("{i}{s}"))
-					},
-					(Value::String(s), BinExprKind::Add, Value::Integer(i)) => {
-						Value::String(format!("{s}{i}"))
-					},
-					(Value::String(s), BinExprKind::Add, Value::String(s2)) => {
-						Value::String(format!("{s}{s2}"))
-					},
-					(
-						Value::Integer(i),
-						BinExprKind::Sub,
-						Value::Integer(j),
-					) => Value::Integer(i - j),
-					(
-						Value::Integer(i),
-						BinExprKind::Mul,
-						Value::Integer(j),
-					) => Value::Integer(i * j),
-					(
-						Value::Integer(i),
-						BinExprKind::Div,
-						Value::Integer(j),
-					) => Value::Integer(i / j),
-					(
-						Value::Integer(i),
-						BinExprKind::Mod,
-						Value::Integer(j),
-					) => Value::Integer(i % j),
-					(i, BinExprKind::Equal, j) => Value::Boolean(i == j),
-					(i, BinExprKind::NotEqual, j) => Value::Boolean(i != j),
-					(
-						Value::Integer(i),
-						BinExprKind::LessThan,
-						Value::Integer(j),
-					) => Value::Boolean(i < j),
-					(
-						Value::Integer(i),
-						BinExprKind::GreaterThan,
-						Value::Integer(j),
-					) => Value::Boolean(i > j),
-					(
-						Value::Integer(i),
-						BinExprKind::LessThanEqual,
-						Value::Integer(j),
-					) => Value::Boolean(i <= j),
-					(
-						Value::Integer(i),
-						BinExprKind::GreaterThanEqual,
-						Value::Integer(j),
-					) => Value::Boolean(i >= j),
-					(
-						Value::Boolean(i),
-						BinExprKind::And,
-						Value::Boolean(j),
-					) => Value::Boolean(i && j),
-					(Value::Boolean(i), BinExprKind::Or, Value::Boolean(j)) => {
-						Value::Boolean(i || j)
-					},
-					(i, op, j) => Err(TypeError::BinOp {
-						op,
-						lhs: i,
-						lhs_span,
-						rhs: j,
-						rhs_span,
-					})?,
-				})
-			},
-			Tree::Expr(i) => match i.kind {
-				ExprKind::Literal(i) => Ok(self.eval(Tree::Literal(i))?),
-				ExprKind::Ident(i) => Ok(self.eval(Tree::Ident(i))?),
-				ExprKind::UnaryExpr(i) => self.eval(Tree::UnaryExpr(i)),
-				ExprKind::TemplateString(i) => {
-					self.eval(Tree::TemplateString(i))
-				},
-				ExprKind::Array(i) => self.eval(Tree::Array(i)),
-				ExprKind::Record(i) => self.eval(Tree::Record(i)),
-				ExprKind::Function(f) => Ok(Value::Function(
-					FunctionValue::Closure {
-						body: f,
-						capture: self.mem.top_frame(),
-					},
-				)),
-				ExprKind::Call(i, j) => {
-					let j = ArgumentValues(
-						j.0.into_iter()
-							.map(|a| self.eval(Tree::Expr(a)))
-							.collect::<Result<Vec<_>, RuntimeError>>()?,
-					);
-					let Value::Function(i) = self.eval(Tree::Expr(*i))? else {
-						panic!();
-					};
-
-					match i {
-						FunctionValue::Builtin(mut i) => Ok(i.call(j)),
-						FunctionValue::Closure {
-							body: Function { parameters, body },
-							capture,
-						} => {
-							self.mem.call(capture, parameters, j);
-							let v = self.eval(Tree::Expr(*body));
-							self.mem.ret();
-							v
-						},
-					}
-				},
-				ExprKind::Prop(i, j) => {
-					let Value::Record(i) = self.eval(Tree::Expr(*i))? else {
-						panic!()
-					};
-
-					Ok(i.fields
-						.get(&j)
-						.unwrap_or(&Value::Nil)
-						.clone())
-				},
-				ExprKind::Index(i, j) => {
-					let Value::Array(i) = self.eval(Tree::Expr(*i))? else {
-						panic!()
-					};
-					let Value::Integer(j) = self.eval(Tree::Expr(*j))? else {
-						panic!()
-					};
-					if j < 0 {
-						panic!("index is less than zero: {j}")
-					}
-					Ok(i.into_iter()
-						.nth(j as usize)
-						.unwrap_or(Value::Nil))
-				},
-				ExprKind::BinExpr(i) => self.eval(Tree::BinExpr(i)),
-				ExprKind::Assign(ident, j) => {
-					let Value::Integer(j) = self.eval(Tree::Expr(*j))? else {
-						panic!()
-					};
-					let v = Value::Integer(j);
-					self.mem.assign(&ident, v.clone())?;
-					Ok(v)
-				},
-				ExprKind::AddAssign(ident, j) => {
-					let Value::Integer(i) = self.mem.load(&ident)? else {
-						panic!()
-					};
-					let Value::Integer(j) = self.eval(Tree::Expr(*j))? else {
-						panic!()
-					};
-					let v = Value::Integer(i + j);
-					self.mem.assign(&ident, v.clone())?;
-					Ok(v)
-				},
-				ExprKind::SubAssign(ident, j) => {
-					let Value::Integer(i) = self.mem.load(&ident)? else {
-						panic!()
-					};
-					let Value::Integer(j) = self.eval(Tree::Expr(*j))? else {
-						panic!()
-					};
-					let v = Value::Integer(i - j);
-					self.mem.assign(&ident, v.clone())?;
-					Ok(v)
-				},
-				ExprKind::MulAssign(ident, j) => {
-					let Value::Integer(i) = self.mem.load(&ident)? else {
-						panic!()
-					};
-					let Value::Integer(j) = self.eval(Tree::Expr(*j))? else {
-						panic!()
-					};
-					let v = Value::Integer(i * j);
-					self.mem.assign(&ident, v.clone())?;
-					Ok(v)
-				},
-				ExprKind::DivAssign(ident, j) => {
-					let Value::Integer(i) = self.mem.load(&ident)? else {
-						panic!()
-					};
-					let Value::Integer(j) = self.eval(Tree::Expr(*j))? else {
-						panic!()
-					};
-					let v = Value::Integer(i / j);
-					self.mem.assign(&ident, v.clone())?;
-					Ok(v)
-				},
-				ExprKind::ModAssign(ident, j) => {
-					let Value::Integer(i) = self.mem.load(&ident)? else {
-						panic!()
-					};
-					let Value::Integer(j) = self.eval(Tree::Expr(*j))? else {
-						panic!()
-					};
-					let v = Value::Integer(i % j);
-					self.mem.assign(&ident, v.clone())?;
-					Ok(v)
-				},
-				ExprKind::Block(b) => {
-					self.mem.push_scope();
-					let v = self.eval(Tree::Code(b))?;
-					self.mem.pop_scope();
+	fn eval_if(
+		&mut self,
+		condition: Expr,
+		yes: Code,
+		no: Option<Else>,
+	) -> Result<Value, Unwind> {
+		if self.eval_condition(condition)? {
+			return self.eval(Tree::Code(yes));
+		}
 
-					Ok(v)
-				},
-				ExprKind::Declare(ident, value) => {
-					let value = self.eval(Tree::Expr(*value))?;
-					self.mem
-						.declare(&ident, value.clone(), false)?;
-					Ok(value)
-				},
-				ExprKind::DeclareMut(ident, value) => {
-					let value = self.eval(Tree::Expr(*value))?;
-					self.mem
-						.declare(&ident, value.clone(), true)?;
-					Ok(value)
-				},
-				ExprKind::If { condition, yes } => {
-					let Value::Boolean(condition) =
-						self.eval(Tree::Expr(*condition))?
-					else {
-						panic!()
-					};
-					Ok(if condition {
-						self.eval(Tree::Code(yes))?
-					} else {
-						Value::Nil
-					})
-				},
-				ExprKind::IfElse { condition, yes, no } => {
-					let Value::Boolean(condition) =
-						self.eval(Tree::Expr(*condition))?
-					else {
-						panic!()
-					};
-					Ok(if condition {
-						self.eval(Tree::Code(yes))?
-					} else {
-						self.eval(Tree::Code(no))?
-					})
-				},
-				ExprKind::For {
-					collection,
-					item,
-					body,
-				} => {
-					let Value::Array(collection) =
-						self.eval(Tree::Expr(*collection))?
-					else {
-						panic!()
-					};
-					self.mem
-						.declare(&item, Value::Nil, true)?;
-					for i in collection {
-						self.mem.assign(&item, i)?;
-						self.eval(Tree::Code(body.clone()))?;
-					}
-					Ok(Value::Nil)
-				},
-				ExprKind::Panic(_) => todo!(),
-				ExprKind::Assert(_) => todo!(),
-				ExprKind::Return(_) => todo!(),
-				ExprKind::Break(_) => todo!(),
-				ExprKind::Continue(_) => todo!(),
-			},
-			Tree::Code(i) => {
-				let mut last = Value::Nil;
-				for i in i.stmts {
-					last = self.eval(Tree::Expr(i))?;
-				}
-				Ok(last)
-			},
+		match no {
+			Some(Else::Block(no)) => self.eval(Tree::Code(no)),
+			Some(Else::If(no)) => self.eval(Tree::Expr(*no)),
+			None => Ok(Value::Nil),
 		}
 	}
-}
 
-#[cfg(test)]
-mod tests {
-	use indoc::indoc;
-	use maplit::hashmap;
-	use pretty_assertions::assert_eq;
+	fn eval_assert(&mut self, condition: Expr) -> Result<Value, Unwind> {
+		let span = condition.span();
+		match self.eval(Tree::Expr(condition))? {
+			Value::Boolean(true) => Ok(Value::Nil),
+			value => Err(RuntimeError::AssertionFailed { value, span })?,
+		}
+	}
 
-	use super::*;
-	use crate::value::BuiltinFunction;
+	fn eval_template_string(
+		&mut self,
+		TemplateString {
+			fragments, values, ..
+		}: TemplateString,
+	) -> Result<Value, Unwind> {
+		let mut buf = String::new();
 
-	#[derive(Clone)]
-	struct Print(Vec<Value>);
+		let mut fragments_iter = fragments.iter();
 
-	impl Print {
-		fn new() -> Box<Self> {
-			Box::new(Self(vec![]))
+		for value in values {
+			buf.push_str(fragments_iter.next().unwrap());
+
+			let value = self.eval(Tree::Expr(value))?;
+			buf.push_str(&value.to_string());
 		}
+
+		buf.push_str(fragments_iter.next().unwrap());
+
+		Ok(Value::String(buf))
 	}
 
-	impl BuiltinFunction for Print {
-		fn call(&mut self, args: ArgumentValues) -> Value {
-			let msg = args.0.into_iter().nth(0).unwrap();
-			self.0.push(msg.clone());
-			msg
-		}
+	fn eval_record(&mut self, i: ast::Record) -> Result<Value, Unwind> {
+		Ok(Value::Record(
+			i.fields
+				.into_iter()
+				.try_fold(
+					HashMap::<Ident, Value>::new(),
+					|mut h, (k, v)| -> Result<HashMap<_, _>, Unwind> {
+						h.insert(k, self.eval(Tree::Expr(v))?);
+						Ok(h)
+					},
+				)?
+				.into(),
+		))
 	}
 
-	#[test]
-	#[ignore]
-	fn run_interpreter() {
-		let mut interpreter = Interpreter::init_with_builtins(hashmap! {
-			Ident::new_dummy("print") => Value::Function(
-				FunctionValue::Builtin(Print::new())
-			)
-		})
-		.unwrap();
+	fn eval_bin_expr(&mut self, bin: BinExpr) -> Result<Value, Unwind> {
+		let lhs = *bin.lhs;
+		let rhs = *bin.rhs;
 
-		let res = interpreter.run(indoc! {r#"
-			let! x = 10
-			x += 20
-			let y = x * 3
-			print(y - x)
-		"#});
+		let op = bin.op;
 
-		assert_eq!(res, Ok(Value::Integer(60)));
-	}
+		let lhs_span = lhs.span();
+		let rhs_span = rhs.span();
 
-	#[test]
-	fn one_plus_one_should_be_two() {
-		let mut interpreter = Interpreter::init();
+		let i = self.eval(Tree::Expr(lhs))?;
 
-		let res = interpreter.run(indoc! {r#"
-			1 + 1 == 2
-		"#});
+		if op.is_logical() {
+			if operator::short_circuits(&op, &i, lhs_span)? {
+				return Ok(i);
+			}
 
-		assert_eq!(res, Ok(Value::Boolean(true)));
-	}
+			let j = self.eval(Tree::Expr(rhs))?;
 
-	#[test]
-	fn one_should_be_less_than_ten() {
-		let mut interpreter = Interpreter::init();
+			return operator::logical(op, j, rhs_span).map_err(Unwind::from);
+		}
 
-		let res = interpreter.run(indoc! {r#"
-			0 < 10
-		"#});
+		let j = self.eval(Tree::Expr(rhs))?;
 
-		assert_eq!(res, Ok(Value::Boolean(true)));
+		operator::binary(op, i, lhs_span, j, rhs_span).map_err(Unwind::from)
 	}
 
-	#[test]
-	fn one_should_be_less_than_or_equal_to_ten() {
-		let mut interpreter = Interpreter::init();
+	fn eval_call(
+		&mut self,
+		callee: Expr,
+		arguments: Vec<Expr>,
+		span: Span,
+	) -> Result<Value, Unwind> {
+		let arguments = arguments
+			.into_iter()
+			.map(|a| {
+				let span = a.span();
+				Ok((self.eval(Tree::Expr(a))?, span))
+			})
+			.collect::<Result<Vec<_>, Unwind>>()?;
+		let callee_span = callee.span();
+		let callee = match self.eval(Tree::Expr(callee))? {
+			Value::Function(callee) => callee,
+			value => Err(TypeError::NotCallable {
+				value,
+				span: callee_span,
+			})?,
+		};
+
+		self.call(callee, arguments, span)
+	}
 
-		let res = interpreter.run(indoc! {r#"
-			0 <= 10
-		"#});
+	fn eval_index_expr(&mut self, i: Expr, j: Expr) -> Result<Value, Unwind> {
+		let span = i.span();
+		let i = self.eval(Tree::Expr(i))?;
+		let index_span = j.span();
+		let j = self.eval(Tree::Expr(j))?;
 
-		assert_eq!(res, Ok(Value::Boolean(true)));
+		Ok(place::index(i, span, j, index_span)?)
 	}
 
-	#[test]
-	fn one_should_not_be_greater_than_ten() {
-		let mut interpreter = Interpreter::init();
+	fn eval_range(
+		&mut self,
+		start: Expr,
+		end: Expr,
+		step: Option<Expr>,
+		inclusive: bool,
+	) -> Result<Value, Unwind> {
+		let start_span = start.span();
+		let start = self.eval(Tree::Expr(start))?;
+		let end_span = end.span();
+		let end = self.eval(Tree::Expr(end))?;
+		let step = match step {
+			Some(step) => {
+				let span = step.span();
+				Some((self.eval(Tree::Expr(step))?, span))
+			},
+			None => None,
+		};
 
-		let res = interpreter.run(indoc! {r#"
-			0 > 10
-		"#});
+		Ok(operator::range(
+			start, start_span, end, end_span, step, inclusive,
+		)?)
+	}
 
-		assert_eq!(res, Ok(Value::Boolean(false)));
+	fn eval_declare(
+		&mut self,
+		ident: Ident,
+		value: Expr,
+		mutable: bool,
+	) -> Result<Value, Unwind> {
+		let value = self
+			.eval(Tree::Expr(value))?
+			.named(&ident);
+		self.mem
+			.declare(&ident, value.clone(), mutable)?;
+		Ok(value)
 	}
 
-	#[test]
-	fn one_should_not_be_greater_than_or_equal_to_ten() {
-		let mut interpreter = Interpreter::init();
+	fn eval_for(
+		&mut self,
+		collection: Expr,
+		item: &Pattern,
+		body: Code,
+	) -> Result<Value, Unwind> {
+		let span = collection.span();
+		let collection = self.eval(Tree::Expr(collection))?;
+		let Some(items) = collection.items() else {
+			Err(TypeError::NotIterable {
+				value: collection,
+				span,
+			})?
+		};
+		for i in items {
+			self.mem.push_scope();
+			let v = self
+				.bind_pattern(item, i, true)
+				.and_then(|_| self.eval(Tree::Code(body.clone())));
+			self.mem.pop_scope();
+
+			match v {
+				Err(Unwind::Break(v, _)) => return Ok(v),
+				Err(Unwind::Continue(_)) | Ok(_) => {},
+				Err(e) => return Err(e),
+			}
+		}
+		Ok(Value::Nil)
+	}
 
-		let res = interpreter.run(indoc! {r#"
-			0 >= 10
-		"#});
+	fn eval_match(
+		&mut self,
+		scrutinee: Expr,
+		arms: Vec<ast::MatchArm>,
+	) -> Result<Value, Unwind> {
+		let span = scrutinee.span();
+		let value = self.eval(Tree::Expr(scrutinee))?;
+
+		for arm in arms {
+			let mut bindings = vec![];
+			let pattern = &arm.pattern;
+			if destructure(pattern, value.clone(), &mut bindings).is_err() {
+				continue;
+			}
+
+			self.mem.push_scope();
+			let v = self.match_arm(pattern, bindings, arm.guard, arm.body);
+			self.mem.pop_scope();
+
+			if let Some(v) = v? {
+				return Ok(v);
+			}
+		}
 
-		assert_eq!(res, Ok(Value::Boolean(false)));
+		Err(RuntimeError::NoMatch { value, span })?
 	}
 
-	#[test]
-	fn two_should_not_be_less_than_two() {
-		let mut interpreter = Interpreter::init();
+	fn eval_export(
+		&mut self,
+		declaration: Expr,
+		span: Span,
+	) -> Result<Value, Unwind> {
+		if !self.mem.is_top_level() {
+			Err(RuntimeError::ExportOutsideTopLevel { span })?
+		}
 
-		let res = interpreter.run(indoc! {r#"
-			2 < 2
-		"#});
+		let ident = match &declaration.kind {
+			ExprKind::Declare(ident, _) | ExprKind::DeclareMut(ident, _) =>
+				ident.clone(),
+			_ => unreachable!("only declarations can be exported"),
+		};
 
-		assert_eq!(res, Ok(Value::Boolean(false)));
+		let value = self.eval(Tree::Expr(declaration))?;
+		self.exports.push(ident);
+		Ok(value)
 	}
 
-	#[test]
-	fn two_should_be_less_than_or_equal_to_two() {
-		let mut interpreter = Interpreter::init();
+	/// Evaluates a `use`, loading the module unless it has been
+	/// loaded before, and declares the imported bindings.
+	fn eval_use(&mut self, u: Use) -> Result<Value, Unwind> {
+		let (path, exports) = self.load_module(&u.path)?;
 
-		let res = interpreter.run(indoc! {r#"
-			2 <= 2
-		"#});
+		for ident in u.imports {
+			let Some(value) = exports.get(&ident) else {
+				Err(ModuleError::MissingExport { ident, path })?
+			};
 
-		assert_eq!(res, Ok(Value::Boolean(true)));
+			self.mem.declare(&ident, value, false)?;
+		}
+
+		Ok(Value::Record(exports))
 	}
 
-	#[test]
-	fn two_should_not_be_greater_than_two() {
-		let mut interpreter = Interpreter::init();
+	/// Loads the module `path` refers to unless it has been loaded
+	/// before, returning where it was found and its exports.
+	fn load_module(
+		&mut self,
+		path: &StringT,
+	) -> Result<(PathBuf, Record), RuntimeError> {
+		let span = path.span();
+		let path = self.modules.resolve(&path.value);
+		let path = path
+			.canonicalize()
+			.map_err(|e| ModuleError::NotFound {
+				path,
+				reason: e.to_string(),
+				span,
+			})?;
+
+		if let Some(exports) = self.modules.get(&path) {
+			return Ok((path, exports));
+		}
 
-		let res = interpreter.run(indoc! {r#"
-			2 > 2
-		"#});
+		if let Some(cycle) = self.modules.cycle(&path) {
+			Err(ModuleError::Cycle { cycle, span })?
+		}
 
-		assert_eq!(res, Ok(Value::Boolean(false)));
+		let code =
+			fs::read_to_string(&path).map_err(|e| ModuleError::NotFound {
+				path: path.clone(),
+				reason: e.to_string(),
+				span,
+			})?;
+
+		let source = Arc::new(Source {
+			path: path.clone(),
+			code,
+		});
+		let exports = self
+			.eval_module(&source)
+			.map_err(|error| ModuleError::Failed {
+				failure: Box::new(ModuleFailure {
+					path: path.clone(),
+					code: source.code.clone(),
+					error,
+				}),
+				span,
+			})?;
+
+		self.modules
+			.insert(path.clone(), exports.clone());
+		Ok((path, exports))
 	}
 
-	#[test]
-	fn two_should_be_greater_than_or_equal_to_two() {
-		let mut interpreter = Interpreter::init();
+	/// Evaluates a module in its own global scope and collects its
+	/// exports.
+	fn eval_module(
+		&mut self,
+		source: &Arc<Source>,
+	) -> Result<Record, InterpreterError> {
+		let ast = self.parse_code(&source.code)?;
+
+		let globals = global_environment(&self.builtins);
+		let mem = std::mem::replace(&mut self.mem, globals);
+		let globals = Globals::shared(&self.builtins);
+		let vm_globals = std::mem::replace(&mut self.vm.globals, globals);
+		let exports = std::mem::take(&mut self.exports);
+		let trace = std::mem::take(&mut self.trace);
+		let outer = self.source.replace(source.clone());
+		self.modules.enter(source.path.clone());
+
+		let res = self.eval_code(ast).and_then(|_| {
+			self.exports
+				.iter()
+				.map(|ident| Ok((ident.clone(), self.load_global(ident)?)))
+				.collect::<Result<HashMap<_, _>, RuntimeError>>()
+		});
+
+		let res = res.map_err(|e| self.traced(e));
+
+		self.modules.exit();
+		self.source = outer;
+		self.exports = exports;
+		self.trace = trace;
+		self.vm.globals = vm_globals;
+		self.mem = mem;
+
+		Ok(res?.into())
+	}
 
-		let res = interpreter.run(indoc! {r#"
-			2 >= 2
-		"#});
+	/// Calls `function` with already evaluated arguments, each paired
+	/// with the span it came from.
+	fn call(
+		&mut self,
+		function: FunctionValue,
+		arguments: Vec<(Value, Span)>,
+		span: Span,
+	) -> Result<Value, Unwind> {
+		let (arity, definition) = match &function {
+			FunctionValue::Builtin(f) => (f.arity(), None),
+			FunctionValue::Native(f) => (f.arity, None),
+			FunctionValue::Closure { body, .. } => (
+				closure_arity(&body.parameters),
+				Some(body.parameters.span),
+			),
+			FunctionValue::Compiled(closure) => (
+				closure.proto.arity,
+				Some(closure.proto.parameters),
+			),
+		};
+
+		if !arity.accepts(arguments.len()) {
+			Err(RuntimeError::ArgumentCount {
+				expected: arity,
+				found: arguments.len(),
+				span,
+				definition,
+			})?
+		}
 
-		assert_eq!(res, Ok(Value::Boolean(true)));
-	}
+		match function {
+			FunctionValue::Builtin(mut f) => {
+				let (values, argument_spans) = arguments.into_iter().unzip();
+				let mut cx = CallContext {
+					interpreter: self,
+					span,
+					argument_spans,
+				};
 
-	#[test]
-	fn negative_zero_should_not_be_less_than_positive_zero() {
-		let mut interpreter = Interpreter::init();
+				Ok(f.call(&mut cx, ArgumentValues(values))?)
+			},
+			FunctionValue::Native(f) => {
+				let (values, argument_spans) = arguments.into_iter().unzip();
+				let mut cx = CallContext {
+					interpreter: self,
+					span,
+					argument_spans,
+				};
+				let args = Arguments::new(f.name, values, &cx);
+
+				Ok((f.call)(&mut cx, args)?)
+			},
+			FunctionValue::Closure {
+				body,
+				capture,
+				name,
+				source,
+			} => self.call_closure(
+				body, capture, name, source, arguments, span,
+			),
+			FunctionValue::Compiled(closure) => {
+				let arguments = arguments
+					.into_iter()
+					.map(|(v, _)| v)
+					.collect();
 
-		let res = interpreter.run(indoc! {r#"
-			-0 < 0
-		"#});
+				Ok(self.call_compiled(closure, arguments, span)?)
+			},
+		}
+	}
 
-		assert_eq!(res, Ok(Value::Boolean(false)));
+	fn call_closure(
+		&mut self,
+		Function { parameters, body }: Function,
+		capture: Frame,
+		name: Option<String>,
+		source: Option<Arc<Source>>,
+		arguments: Vec<(Value, Span)>,
+		span: Span,
+	) -> Result<Value, Unwind> {
+		if self.depth >= self.max_call_depth {
+			Err(RuntimeError::StackOverflow {
+				depth: self.max_call_depth,
+				span,
+			})?
+		}
+
+		let arguments = arguments
+			.into_iter()
+			.map(|(v, _)| v)
+			.collect();
+
+		self.depth += 1;
+		let caller = std::mem::replace(&mut self.source, source);
+		self.mem.call(capture);
+		// NOTE: each call takes several `eval` frames, so the
+		// native stack is grown on the heap instead of relying on
+		// the size of the calling thread's stack
+		let v = stacker::maybe_grow(
+			STACK_RED_ZONE,
+			STACK_SEGMENT_SIZE,
+			|| {
+				self.bind_parameters(parameters, arguments)
+					.and_then(|_| self.eval(Tree::Expr(*body)))
+			},
+		);
+		self.mem.ret();
+		let source = std::mem::replace(&mut self.source, caller);
+		self.depth -= 1;
+
+		let v = match v {
+			Err(Unwind::Return(v, _)) => Ok(v),
+			Err(Unwind::Error(e)) => Err(*e),
+			Err(e) => Err(e.into_error()),
+			Ok(v) => Ok(v),
+		};
+
+		v.map_err(|e| {
+			let caller = self.source.clone();
+			let e = self.leave_module(
+				source.as_ref(),
+				caller.as_ref(),
+				e,
+				span,
+			);
+			self.push_trace(name, span);
+			e.into()
+		})
+	}
+
+	/// Records that the current error unwound through a call to
+	/// `function` at `span`.
+	fn push_trace(&mut self, function: Option<String>, span: Span) {
+		// NOTE: frames are collected while unwinding, so the innermost
+		// call comes first
+		match self.trace.last_mut() {
+			Some(frame) if frame.span == span && frame.function == function =>
+				frame.repeated += 1,
+			_ => self.trace.push(TraceFrame {
+				function,
+				span,
+				repeated: 0,
+			}),
+		}
+	}
+
+	/// Declares `parameters` in the current frame, evaluating
+	/// defaults for missing arguments and collecting extra ones into
+	/// the rest parameter. The number of arguments must already be
+	/// checked.
+	fn bind_parameters(
+		&mut self,
+		parameters: Parameters,
+		arguments: Vec<Value>,
+	) -> Result<(), Unwind> {
+		let mut arguments = arguments.into_iter();
+
+		for Parameter { pattern, default } in parameters.positional {
+			let value = match (arguments.next(), default) {
+				(Some(value), _) => value,
+				(None, Some(default)) => self.eval(Tree::Expr(default))?,
+				(None, None) => unreachable!("arity is checked before binding"),
+			};
+
+			self.bind_pattern(&pattern, value, false)?;
+		}
+
+		if let Some(rest) = parameters.rest {
+			let rest_values = arguments.collect::<Vec<_>>();
+			self.mem.declare(
+				&rest,
+				Value::Array(rest_values.into()),
+				false,
+			)?;
+		}
+
+		Ok(())
+	}
+
+	/// Declares what `pattern` binds in `value`, failing if it
+	/// doesn't match.
+	fn bind_pattern(
+		&mut self,
+		pattern: &Pattern,
+		value: Value,
+		mutable: bool,
+	) -> Result<(), Unwind> {
+		// a plain name, like most parameters, always matches
+		if let PatternKind::Binding(ident) = &pattern.kind {
+			return Ok(self
+				.mem
+				.declare(ident, value, mutable)?);
+		}
+
+		let mut bindings = vec![];
+		destructure(pattern, value, &mut bindings).map_err(
+			|(span, value)| RuntimeError::PatternMismatch { value, span },
+		)?;
+
+		self.declare_bindings(pattern, bindings, mutable)
+	}
+
+	/// Declares the bindings of a pattern that matched, then
+	/// evaluates the defaults of the fields that were missing, in
+	/// order.
+	fn declare_bindings(
+		&mut self,
+		pattern: &Pattern,
+		bindings: Vec<(&Ident, Value)>,
+		mutable: bool,
+	) -> Result<(), Unwind> {
+		let mut bound = vec![];
+		for (ident, value) in bindings {
+			self.mem
+				.declare(ident, value, mutable)?;
+			bound.push(ident);
+		}
+
+		for (ident, default) in pattern.defaults() {
+			if !bound.contains(&ident) {
+				let value = self.eval(Tree::Expr(default.clone()))?;
+				self.mem
+					.declare(ident, value, mutable)?;
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Evaluates the body of an arm whose pattern matched, unless its
+	/// guard doesn't hold.
+	fn match_arm(
+		&mut self,
+		pattern: &Pattern,
+		bindings: Vec<(&Ident, Value)>,
+		guard: Option<Expr>,
+		body: Expr,
+	) -> Result<Option<Value>, Unwind> {
+		self.declare_bindings(pattern, bindings, false)?;
+
+		if let Some(guard) = guard {
+			if !self.eval_condition(guard)? {
+				return Ok(None);
+			}
+		}
+
+		Ok(Some(self.eval(Tree::Expr(body))?))
+	}
+
+	fn eval_condition(&mut self, condition: Expr) -> Result<bool, Unwind> {
+		let span = condition.span();
+
+		match self.eval(Tree::Expr(condition))? {
+			Value::Boolean(condition) => Ok(condition),
+			value => Err(TypeError::NonBooleanCondition { value, span })?,
+		}
+	}
+
+	fn eval_index(&mut self, index: Expr) -> Result<usize, Unwind> {
+		let span = index.span();
+		let index = self.eval(Tree::Expr(index))?;
+
+		Ok(to_index(index, span)?)
+	}
+
+	/// Evaluates the indices in `place`, returning the variable it
+	/// belongs to and the path into that variable's value.
+	fn resolve_place(
+		&mut self,
+		place: Place,
+	) -> Result<(Ident, Vec<(Accessor, Span)>), Unwind> {
+		match place.kind {
+			PlaceKind::Ident(ident) => Ok((ident, vec![])),
+			PlaceKind::Prop(i, prop) => {
+				let span = i.span();
+				let (root, mut path) = self.resolve_place(*i)?;
+				path.push((Accessor::Prop(prop), span));
+				Ok((root, path))
+			},
+			PlaceKind::Index(i, j) => {
+				let span = i.span();
+				let (root, mut path) = self.resolve_place(*i)?;
+				let index_span = j.span();
+				let index = self.eval_index(*j)?;
+				path.push((Accessor::Index(index, index_span), span));
+				Ok((root, path))
+			},
+		}
+	}
+
+	/// Assigns `rhs` to `place`, combining it with the current value
+	/// first for compound assignments.
+	///
+	/// Arrays and records are shared, but `arr[0] = 1` is still
+	/// rejected when `arr` itself was declared with `let`.
+	fn assign(
+		&mut self,
+		place: Place,
+		op: Option<BinExprKind>,
+		rhs: Expr,
+	) -> Result<Value, Unwind> {
+		let lhs_span = place.span();
+		let rhs_span = rhs.span();
+		let (root, path) = self.resolve_place(place)?;
+
+		let value = match op {
+			Some(op) => {
+				let i = path.iter().try_fold(
+					self.mem.load(&root)?,
+					|v, (accessor, span)| accessor.get(v, *span),
+				)?;
+				let j = self.eval(Tree::Expr(rhs))?;
+
+				operator::binary(op, i, lhs_span, j, rhs_span)?
+			},
+			None => self.eval(Tree::Expr(rhs))?,
+		};
+
+		match path.split_last() {
+			None => self.mem.assign(&root, value.clone())?,
+			Some(((accessor, span), path)) => {
+				self.mem
+					.check_mutable(&root, lhs_span)?;
+
+				let target = path.iter().try_fold(
+					self.mem.load(&root)?,
+					|v, (accessor, span)| accessor.get(v, *span),
+				)?;
+				accessor.set(target, *span, value.clone())?;
+			},
+		}
+
+		Ok(value)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::path::PathBuf;
+
+	use dyn_parser::SyntaxError;
+	use dyn_span::Spanned;
+	use indoc::indoc;
+	use maplit::hashmap;
+	use miette::Diagnostic;
+	use pretty_assertions::assert_eq;
+
+	use super::*;
+	use crate::{error::ArithmeticError, value::BuiltinFunction};
+
+	/// Runs every script on both backends, checking that they agree.
+	struct Interpreter {
+		tree: super::Interpreter,
+		bytecode: super::Interpreter,
+	}
+
+	impl Interpreter {
+		fn new(
+			init: impl Fn() -> Result<super::Interpreter, RuntimeError>,
+		) -> Result<Self, RuntimeError> {
+			let mut bytecode = init()?;
+			bytecode.set_backend(Backend::Bytecode);
+
+			Ok(Self {
+				tree: init()?,
+				bytecode,
+			})
+		}
+
+		fn init() -> Self {
+			Self::new(|| Ok(super::Interpreter::init())).unwrap()
+		}
+
+		fn init_with_builtins(
+			builtin: HashMap<Ident, Value>,
+		) -> Result<Self, RuntimeError> {
+			Self::new(|| {
+				super::Interpreter::init_with_builtins(builtin.clone())
+			})
+		}
+
+		fn init_without_stdlib(
+			builtin: HashMap<Ident, Value>,
+		) -> Result<Self, RuntimeError> {
+			Self::new(|| {
+				super::Interpreter::init_without_stdlib(builtin.clone())
+			})
+		}
+
+		fn set_max_call_depth(&mut self, depth: usize) {
+			self.tree.set_max_call_depth(depth);
+			self.bytecode.set_max_call_depth(depth);
+		}
+
+		fn run(&mut self, code: &str) -> Result<Value, InterpreterError> {
+			let res = self.tree.run(code);
+			assert_eq!(
+				self.bytecode.run(code),
+				res,
+				"backends disagree"
+			);
+			res
+		}
+
+		fn run_file(
+			&mut self,
+			path: impl AsRef<Path>,
+			code: &str,
+		) -> Result<Value, InterpreterError> {
+			let res = self.tree.run_file(&path, code);
+			assert_eq!(
+				self.bytecode.run_file(&path, code),
+				res,
+				"backends disagree"
+			);
+			res
+		}
+
+		fn check(&mut self, code: &str) -> Vec<Lint> { self.tree.check(code) }
+	}
+
+	/// Writes `files` into a fresh directory and returns its path.
+	fn write_modules(name: &str, files: &[(&str, &str)]) -> PathBuf {
+		let dir = std::env::temp_dir().join(format!(
+			"dyn-{}-{name}",
+			std::process::id()
+		));
+		fs::create_dir_all(&dir).unwrap();
+
+		for (path, code) in files {
+			let path = dir.join(path);
+			fs::create_dir_all(path.parent().unwrap()).unwrap();
+			fs::write(path, code).unwrap();
+		}
+
+		dir.canonicalize().unwrap()
+	}
+
+	#[derive(Clone)]
+	struct Print(Vec<Value>);
+
+	impl Print {
+		fn new() -> Box<Self> { Box::new(Self(vec![])) }
+	}
+
+	impl BuiltinFunction for Print {
+		fn arity(&self) -> Arity { Arity::exactly(1) }
+
+		fn call(
+			&mut self,
+			_: &mut CallContext,
+			args: ArgumentValues,
+		) -> Result<Value, RuntimeError> {
+			let msg = args.0.into_iter().nth(0).unwrap();
+			self.0.push(msg.clone());
+			Ok(msg)
+		}
+	}
+
+	#[test]
+	#[ignore]
+	fn run_interpreter() {
+		let mut interpreter = Interpreter::init_with_builtins(hashmap! {
+			Ident::new_dummy("print") => Value::Function(
+				FunctionValue::Builtin(Print::new())
+			)
+		})
+		.unwrap();
+
+		let res = interpreter.run(indoc! {r#"
+			let! x = 10
+			x += 20
+			let y = x * 3
+			print(y - x)
+		"#});
+
+		assert_eq!(res, Ok(Value::Integer(60.into())));
+	}
+
+	#[test]
+	fn one_plus_one_should_be_two() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {r#"
+			1 + 1 == 2
+		"#});
+
+		assert_eq!(res, Ok(Value::Boolean(true)));
+	}
+
+	#[test]
+	fn one_should_be_less_than_ten() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {r#"
+			0 < 10
+		"#});
+
+		assert_eq!(res, Ok(Value::Boolean(true)));
+	}
+
+	#[test]
+	fn one_should_be_less_than_or_equal_to_ten() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {r#"
+			0 <= 10
+		"#});
+
+		assert_eq!(res, Ok(Value::Boolean(true)));
+	}
+
+	#[test]
+	fn one_should_not_be_greater_than_ten() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {r#"
+			0 > 10
+		"#});
+
+		assert_eq!(res, Ok(Value::Boolean(false)));
+	}
+
+	#[test]
+	fn one_should_not_be_greater_than_or_equal_to_ten() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {r#"
+			0 >= 10
+		"#});
+
+		assert_eq!(res, Ok(Value::Boolean(false)));
+	}
+
+	#[test]
+	fn two_should_not_be_less_than_two() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {r#"
+			2 < 2
+		"#});
+
+		assert_eq!(res, Ok(Value::Boolean(false)));
+	}
+
+	#[test]
+	fn two_should_be_less_than_or_equal_to_two() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {r#"
+			2 <= 2
+		"#});
+
+		assert_eq!(res, Ok(Value::Boolean(true)));
+	}
+
+	#[test]
+	fn two_should_not_be_greater_than_two() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {r#"
+			2 > 2
+		"#});
+
+		assert_eq!(res, Ok(Value::Boolean(false)));
+	}
+
+	#[test]
+	fn two_should_be_greater_than_or_equal_to_two() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {r#"
+			2 >= 2
+		"#});
+
+		assert_eq!(res, Ok(Value::Boolean(true)));
+	}
+
+	#[test]
+	fn negative_zero_should_not_be_less_than_positive_zero() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {r#"
+			-0 < 0
+		"#});
+
+		assert_eq!(res, Ok(Value::Boolean(false)));
+	}
+
+	#[test]
+	fn return_should_exit_closure_early() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {r#"
+			let f = |x| -> {
+				if x > 0 {
+					return 'positive'
+				}
+				'non-positive'
+			}
+			f(1) + ' ' + f(0)
+		"#});
+
+		assert_eq!(
+			res,
+			Ok(Value::String(
+				"positive non-positive".to_owned()
+			))
+		);
+	}
+
+	#[test]
+	fn bare_return_should_yield_nil() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {r#"
+			let f = -> {
+				return
+				123
+			}
+			f()
+		"#});
+
+		assert_eq!(res, Ok(Value::Nil));
+	}
+
+	#[test]
+	fn break_should_stop_loop_with_value() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {r#"
+			let! sum = 0
+			let last = iter [1, 2, 3, 4] of x {
+				if x == 3 {
+					break x
+				}
+				sum += x
+			}
+			sum * 10 + last
+		"#});
+
+		assert_eq!(res, Ok(Value::Integer(33.into())));
+	}
+
+	#[test]
+	fn continue_should_skip_rest_of_iteration() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {r#"
+			let! sum = 0
+			iter [1, 2, 3, 4] of x {
+				let y = x * 2
+				if x == 2 {
+					continue
+				}
+				sum += y
+			}
+			sum
+		"#});
+
+		assert_eq!(res, Ok(Value::Integer(16.into())));
+	}
+
+	#[test]
+	fn return_should_unwind_through_loops() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {r#"
+			let find = |arr, v| -> {
+				iter arr of x {
+					if x == v {
+						return true
+					}
+				}
+				false
+			}
+			find([1, 2, 3], 2) && !find([1, 2, 3], 4)
+		"#});
+
+		assert_eq!(res, Ok(Value::Boolean(true)));
+	}
+
+	#[test]
+	fn return_outside_function_should_be_error() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run("return 1");
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::ReturnOutsideFunction {
+					span: (0..8).into()
+				}
+			))
+		);
+	}
+
+	#[test]
+	fn break_should_not_cross_closure_boundary() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {r#"
+			iter [1] of x {
+				let f = -> break x
+				f()
+			}
+		"#});
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::Traced {
+				error: RuntimeError::BreakOutsideLoop {
+					span: (28..35).into()
+				},
+				trace: vec![TraceFrame {
+					function: Some("f".to_owned()),
+					span: (37..40).into(),
+					repeated: 0,
+				}]
+			})
+		);
+	}
+
+	#[test]
+	fn continue_outside_loop_should_be_error() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run("{ continue }");
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::ContinueOutsideLoop {
+					span: (2..10).into()
+				}
+			))
+		);
+	}
+
+	#[test]
+	fn panic_should_carry_message() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run("panic 'oops: ' + 1");
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::Panic {
+					message: Value::String("oops: 1".to_owned()),
+					span: (0..18).into()
+				}
+			))
+		);
+	}
+
+	#[test]
+	fn passing_assertion_should_evaluate_to_nil() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run("assert 1 + 1 == 2");
+
+		assert_eq!(res, Ok(Value::Nil));
+	}
+
+	#[test]
+	fn failed_assertion_should_point_at_condition() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run("assert 1 > 2");
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::AssertionFailed {
+					value: Value::Boolean(false),
+					span: (7..12).into()
+				}
+			))
+		);
+	}
+
+	#[test]
+	fn calling_non_function_should_be_type_error() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run("let a = 1\na(2)");
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::TypeError(TypeError::NotCallable {
+					value: Value::Integer(1.into()),
+					span: (10..11).into()
+				})
+			))
+		);
+	}
+
+	#[test]
+	fn indexing_with_string_should_be_type_error() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run("[1, 2]['a']");
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::TypeError(TypeError::InvalidIndex {
+					index: Value::String("a".to_owned()),
+					span: (7..10).into()
+				})
+			))
+		);
+	}
+
+	#[test]
+	fn negative_index_should_be_error() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run("[1, 2][-1]");
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::NegativeIndex {
+					index: (-1).into(),
+					span: (7..9).into()
+				}
+			))
+		);
+	}
+
+	#[test]
+	fn non_boolean_condition_should_be_type_error() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run("if nil { 1 } else { 2 }");
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::TypeError(TypeError::NonBooleanCondition {
+					value: Value::Nil,
+					span: (3..6).into()
+				})
+			))
+		);
+	}
+
+	#[test]
+	fn else_if_chain_should_evaluate_first_matching_branch() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {r#"
+			let grade = |n| -> if n >= 90 {
+				"A"
+			} else if n >= 80 {
+				"B"
+			} else if n >= 70 {
+				"C"
+			} else {
+				"F"
+			}
+			[
+				grade(95)
+				grade(85)
+				grade(75)
+				grade(10)
+				if false { 1 } else if false { 2 }
+				10 + if false { 1 } else { 2 }
+			]
+		"#});
+
+		assert_eq!(
+			res,
+			Ok(array(vec![
+				Value::String("A".to_owned()),
+				Value::String("B".to_owned()),
+				Value::String("C".to_owned()),
+				Value::String("F".to_owned()),
+				Value::Nil,
+				Value::Integer(12.into()),
+			]))
+		);
+	}
+
+	#[test]
+	fn match_should_evaluate_first_matching_arm() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {r#"
+			let describe = |v| -> match v {
+				nil -> "nil"
+				0 -> "zero"
+				-1.5 -> "negative"
+				n if n == 1 -> "one"
+				[] -> "empty"
+				[_, [x]] -> x
+				[first, ...rest] -> [first, rest]
+				(id: 7, version) -> version
+				_ -> "other"
+			}
+			[
+				describe(nil)
+				describe(0.0)
+				describe(-1.5)
+				describe(1)
+				describe(2)
+				describe([])
+				describe([1, [nil]])
+				describe([1, 2, 3])
+				describe([1])
+				describe((id: 7, version: 2, license: nil))
+				describe((id: 8, version: 2))
+			]
+		"#});
+
+		assert_eq!(
+			res,
+			Ok(array(vec![
+				Value::String("nil".to_owned()),
+				Value::String("zero".to_owned()),
+				Value::String("negative".to_owned()),
+				Value::String("one".to_owned()),
+				Value::String("other".to_owned()),
+				Value::String("empty".to_owned()),
+				Value::Nil,
+				array(vec![
+					Value::Integer(1.into()),
+					array(vec![
+						Value::Integer(2.into()),
+						Value::Integer(3.into()),
+					]),
+				]),
+				array(vec![
+					Value::Integer(1.into()),
+					array(vec![])
+				]),
+				Value::Integer(2.into()),
+				Value::String("other".to_owned()),
+			]))
+		);
+	}
+
+	#[test]
+	fn match_bindings_should_be_scoped_to_their_arm() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {"
+			let x = 0
+			let! closures = []
+			iter [1, 2, 3] of i {
+				match [i, i * 10] {
+					[x, y] if y > 10 -> push(closures, -> x + y)
+					[x, _] -> {
+						let y = x
+						push(closures, -> y)
+					}
+				}
+			}
+			[closures[0](), closures[1](), closures[2](), x]
+		"});
+
+		assert_eq!(
+			res,
+			Ok(array(vec![
+				Value::Integer(1.into()),
+				Value::Integer(22.into()),
+				Value::Integer(33.into()),
+				Value::Integer(0.into()),
+			]))
+		);
+	}
+
+	#[test]
+	fn unmatched_value_should_be_no_match_error() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run("match [1, 2] {\n[x] -> x\n}");
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::NoMatch {
+					value: array(vec![
+						Value::Integer(1.into()),
+						Value::Integer(2.into()),
+					]),
+					span: (6..12).into(),
+				}
+			))
+		);
+	}
+
+	#[test]
+	fn destructuring_should_bind_elements_and_fields() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {"
+			let! calls = 0
+			let fallback = -> {
+				calls += 1
+				0
+			}
+			let [a, [b, ...rest]] = [1, [2, 3, 4]]
+			let (x, y: [z], w = fallback()) = (x: 5, y: [6], w: 7)
+			let! (v = fallback()) = ()
+			v += 1
+			let add = |(x, y), [k] = [10]| -> x + y + k
+			let! sum = 0
+			iter [[1, 2], [3, 4]] of [i, j] {
+				sum += i * j
+			}
+			[a, b, rest, x, z, w, v, calls, add((x: 1, y: 2)), sum]
+		"});
+
+		assert_eq!(
+			res,
+			Ok(array(vec![
+				Value::Integer(1.into()),
+				Value::Integer(2.into()),
+				array(vec![
+					Value::Integer(3.into()),
+					Value::Integer(4.into()),
+				]),
+				Value::Integer(5.into()),
+				Value::Integer(6.into()),
+				Value::Integer(7.into()),
+				Value::Integer(1.into()),
+				Value::Integer(1.into()),
+				Value::Integer(13.into()),
+				Value::Integer(14.into()),
+			]))
+		);
+	}
+
+	#[test]
+	fn shape_mismatch_should_be_pattern_mismatch_error() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run("let (a, b: [c]) = (a: 1, b: 2)");
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::PatternMismatch {
+					value: Value::Integer(2.into()),
+					span: (11..14).into(),
+				}
+			))
+		);
+	}
+
+	#[test]
+	fn ranges_should_iterate_index_and_slice_lazily() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {"
+			let! sum = 0
+			iter 0..1000000000000 of i {
+				if i == 4 { break }
+				sum += i
+			}
+			let! odd = []
+			iter 9..=1..-2 of i {
+				push(odd, i)
+			}
+			let xs = [1, 2, 3, 4, 5]
+			let r = 0..=10..5
+			[
+				sum, odd, len(1..=10), r[1], r[3],
+				xs[1..3], xs[3..0..-1], 'hello'[1..=3], xs[2..2],
+			]
+		"});
+
+		assert_eq!(
+			res,
+			Ok(array(vec![
+				Value::Integer(6.into()),
+				array(vec![
+					Value::Integer(9.into()),
+					Value::Integer(7.into()),
+					Value::Integer(5.into()),
+					Value::Integer(3.into()),
+					Value::Integer(1.into()),
+				]),
+				Value::Integer(10.into()),
+				Value::Integer(5.into()),
+				Value::Nil,
+				array(vec![
+					Value::Integer(2.into()),
+					Value::Integer(3.into()),
+				]),
+				array(vec![
+					Value::Integer(4.into()),
+					Value::Integer(3.into()),
+					Value::Integer(2.into()),
+				]),
+				Value::String("ell".to_owned()),
+				array(vec![]),
+			]))
+		);
+	}
+
+	#[test]
+	fn index_past_the_end_should_be_nil() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run("[[1, 2, 3][3], (0..3)[5]]");
+
+		assert_eq!(
+			res,
+			Ok(array(vec![Value::Nil, Value::Nil]))
+		);
+	}
+
+	#[test]
+	fn range_slice_past_the_end_should_stop_at_the_end() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {r#"
+			let arr = [1, 2, 3]
+			[
+				arr[1..=3],
+				arr[5..10],
+				arr[10..=0..-2],
+				arr[0..1000000000000000000000],
+				"abc"[1..5],
+			]
+		"#});
+
+		assert_eq!(
+			res,
+			Ok(array(vec![
+				array(vec![int(2), int(3)]),
+				array(vec![]),
+				array(vec![int(3), int(1)]),
+				array(vec![int(1), int(2), int(3)]),
+				string("bc"),
+			]))
+		);
+	}
+
+	#[test]
+	fn zero_step_should_be_error() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run("let s = 0\n0..10..s");
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::ZeroStep {
+					span: (17..18).into(),
+				}
+			))
+		);
+	}
+
+	#[test]
+	fn logical_operators_should_short_circuit() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {"
+			let! calls = 0
+			let hit = -> {
+				calls += 1
+				true
+			}
+			let r = (a: nil)
+			[
+				false && hit()
+				true || hit()
+				nil ?? hit()
+				1 ?? hit()
+				false ?? hit()
+				r != nil && r.a == nil
+				r.a ?? 2
+				calls
+			]
+		"});
+
+		assert_eq!(
+			res,
+			Ok(array(vec![
+				Value::Boolean(false),
+				Value::Boolean(true),
+				Value::Boolean(true),
+				Value::Integer(1.into()),
+				Value::Boolean(false),
+				Value::Boolean(true),
+				Value::Integer(2.into()),
+				Value::Integer(1.into()),
+			]))
+		);
+	}
+
+	#[test]
+	fn non_boolean_operand_should_be_type_error() {
+		let mut interpreter = Interpreter::init();
+
+		assert_eq!(
+			interpreter.run("true || 1"),
+			Ok(Value::Boolean(true))
+		);
+		assert_eq!(
+			interpreter.run("1 && true"),
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::TypeError(TypeError::NonBooleanOperand {
+					op: BinExprKind::And,
+					value: Value::Integer(1.into()),
+					span: (0..1).into()
+				})
+			))
+		);
+		assert_eq!(
+			interpreter.run("false || nil"),
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::TypeError(TypeError::NonBooleanOperand {
+					op: BinExprKind::Or,
+					value: Value::Nil,
+					span: (9..12).into()
+				})
+			))
+		);
+	}
+
+	#[test]
+	fn compound_assignment_should_follow_binary_operator_rules() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {r#"
+			let! s = 'a'
+			s += 1
+			s += 'b'
+		"#});
+
+		assert_eq!(res, Ok(Value::String("a1b".to_owned())));
+
+		let res = interpreter.run("let! t = true\nt -= 1");
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::TypeError(TypeError::BinOp {
+					op: BinExprKind::Sub,
+					lhs: Value::Boolean(true),
+					lhs_span: (14..15).into(),
+					rhs: Value::Integer(1.into()),
+					rhs_span: (19..20).into()
+				})
+			))
+		);
+	}
+
+	#[test]
+	fn division_by_zero_should_be_arithmetic_error() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run("let a = 10 / (1 - 1)");
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::ArithmeticError(
+					ArithmeticError::DivisionByZero {
+						op: BinExprKind::Div,
+						lhs: Value::Integer(10.into()),
+						lhs_span: (8..10).into(),
+						rhs_span: (14..19).into()
+					}
+				)
+			))
+		);
+	}
+
+	#[test]
+	fn integers_should_not_overflow() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run("let! x = 2147483647\nx *= 4294967296");
+
+		assert_eq!(
+			res,
+			Ok(Value::Integer(
+				"9223372032559808512".parse().unwrap()
+			))
+		);
+	}
+
+	#[test]
+	fn float_literals_should_evaluate_to_float() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run("1.5e3 - 0.25");
+
+		assert_eq!(res, Ok(Value::Float(1499.75)));
+	}
+
+	#[test]
+	fn integer_should_be_promoted_to_float() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {r#"
+			let a = 7 / 2
+			let b = 7 / 2.0
+			let c = 1 == 1.0
+			let d = 2 < 2.5
+			[a, b, c, d]
+		"#});
+
+		assert_eq!(
+			res,
+			Ok(Value::Array(
+				vec![
+					Value::Integer(3.into()),
+					Value::Float(3.5),
+					Value::Boolean(true),
+					Value::Boolean(true),
+				]
+				.into()
+			))
+		);
+	}
+
+	#[test]
+	fn float_division_by_zero_should_be_arithmetic_error() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run("1.5 / 0");
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::ArithmeticError(
+					ArithmeticError::DivisionByZero {
+						op: BinExprKind::Div,
+						lhs: Value::Float(1.5),
+						lhs_span: (0..3).into(),
+						rhs_span: (6..7).into()
+					}
+				)
+			))
+		);
+	}
+
+	#[test]
+	fn float_overflow_should_be_arithmetic_error() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run("1e308 * 10");
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::ArithmeticError(ArithmeticError::Overflow {
+					op: BinExprKind::Mul,
+					lhs: Value::Float(1e308),
+					lhs_span: (0..5).into(),
+					rhs: Value::Integer(10.into()),
+					rhs_span: (8..10).into()
+				})
+			))
+		);
+	}
+
+	#[test]
+	fn integers_past_float_precision_should_compare_exactly() {
+		let mut interpreter = Interpreter::init();
+		let big = "9".repeat(300);
+
+		let res = interpreter.run(&format!(
+			indoc! {"
+				let a = 9007199254740993
+				let b = 9007199254740992
+				let big = {}
+				[
+					a == b, a != b, a == b + 0.0, a > b + 0.0,
+					b == b + 0.0, 2 < 2.5, 3 >= 2.5, big == big + 1,
+					big > 1e299,
+				]
+			"},
+			big
+		));
+
+		assert_eq!(
+			res,
+			Ok(array(
+				[false, true, false, true, true, true, true, false, true]
+					.map(Value::Boolean)
+					.to_vec()
+			))
+		);
+	}
+
+	#[test]
+	fn integer_too_large_for_float_should_overflow() {
+		let mut interpreter = Interpreter::init();
+		let big = "9".repeat(400);
+
+		let res = interpreter.run(&format!("{big} * 0.5"));
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::ArithmeticError(ArithmeticError::Overflow {
+					op: BinExprKind::Mul,
+					lhs: Value::Integer(big.parse().unwrap()),
+					lhs_span: (0..400).into(),
+					rhs: Value::Float(0.5),
+					rhs_span: (403..406).into(),
+				})
+			))
+		);
+	}
+
+	#[test]
+	fn index_and_property_assignment_should_update_variable() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {r#"
+			let! cfg = (server: (ports: [80, 443]))
+			cfg.server.ports[1] = 8443
+			cfg.server.ports[0] += 8000
+			cfg.host = 'localhost'
+			[cfg.server.ports, cfg.host]
+		"#});
+
+		assert_eq!(
+			res,
+			Ok(Value::Array(
+				vec![
+					Value::Array(
+						vec![
+							Value::Integer(8080.into()),
+							Value::Integer(8443.into())
+						]
+						.into()
+					),
+					Value::String("localhost".to_owned())
+				]
+				.into()
+			))
+		);
+	}
+
+	#[test]
+	fn index_assignment_should_honor_immutability() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(
+			"let arr = [1, 2]
+arr[0] = 3",
+		);
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::AssignmentToImmutableVariable {
+					span: (17..23).into(),
+					declaration: Some((4..7).into()),
+				}
+			))
+		);
+
+		let res = interpreter.run("let r = (x: 0)\nr.x = 1");
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::AssignmentToImmutableVariable {
+					span: (15..18).into(),
+					declaration: Some((4..5).into()),
+				}
+			))
+		);
+
+		let res = interpreter.run("len = 1");
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::AssignmentToImmutableVariable {
+					span: (0..3).into(),
+					declaration: None,
+				}
+			))
+		);
+	}
+
+	#[test]
+	fn index_assignment_out_of_bounds_should_be_error() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(
+			"let! arr = [1]
+arr[3] = 2",
+		);
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::IndexOutOfBounds {
+					index: 3,
+					len: 1,
+					span: (19..20).into()
+				}
+			))
+		);
+	}
+
+	#[test]
+	fn assignment_to_non_place_should_be_syntax_error() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run("f().x = 1");
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::ParseError(vec![
+				ParseError(dyn_parser::ParseError::User {
+					error: Spanned::new(
+						0..3,
+						SyntaxError::InvalidAssignmentTarget
+					)
+				})
+			]))
+		);
+	}
+
+	#[test]
+	fn every_syntax_error_should_be_reported() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run("let a = 1 + )\nlet b = 2\nlet c = 3 3");
+
+		let Err(InterpreterError::ParseError(errors)) = &res else {
+			panic!("expected syntax errors, got {res:?}");
+		};
+		assert_eq!(
+			errors
+				.iter()
+				.map(ParseError::span)
+				.collect::<Vec<_>>(),
+			vec![Span::from(12..13), Span::from(34..35)]
+		);
+		assert_eq!(
+			res.unwrap_err()
+				.related()
+				.map(|related| related.count()),
+			Some(1)
+		);
+	}
+
+	#[test]
+	fn arrays_should_be_shared_between_bindings() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(
+			"let! a = [1, 2]
+let! b = a
+b[0] = 9
+a",
+		);
+
+		assert_eq!(
+			res,
+			Ok(Value::Array(
+				vec![Value::Integer(9.into()), Value::Integer(2.into())].into()
+			))
+		);
+	}
+
+	#[test]
+	fn records_should_be_shared_with_closures() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(
+			"let! r = (x: 1)
+let bump = -> r.x += 1
+bump()
+bump()
+r.x",
+		);
+
+		assert_eq!(res, Ok(Value::Integer(3.into())));
+	}
+
+	#[test]
+	fn copy_should_break_aliasing() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(
+			"let! a = [[1], 2]
+let! b = copy(a)
+b[1] = 3
+b[0][0] = 4
+a",
+		);
+
+		assert_eq!(
+			res,
+			Ok(Value::Array(
+				vec![
+					Value::Array(vec![Value::Integer(4.into())].into()),
+					Value::Integer(2.into())
+				]
+				.into()
+			))
+		);
+	}
+
+	#[test]
+	fn deep_copy_should_copy_nested_values() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(
+			"let! a = [[1], 2]
+let! b = deep_copy(a)
+b[0][0] = 4
+a",
+		);
+
+		assert_eq!(
+			res,
+			Ok(Value::Array(
+				vec![
+					Value::Array(vec![Value::Integer(1.into())].into()),
+					Value::Integer(2.into())
+				]
+				.into()
+			))
+		);
+	}
+
+	#[test]
+	fn same_should_compare_identity() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(
+			"let a = [1]
+let b = a
+[same(a, b), same(a, copy(a)), a == copy(a)]",
+		);
+
+		assert_eq!(
+			res,
+			Ok(Value::Array(
+				vec![
+					Value::Boolean(true),
+					Value::Boolean(false),
+					Value::Boolean(true)
+				]
+				.into()
+			))
+		);
+	}
+
+	#[test]
+	fn use_should_import_exported_bindings() {
+		let dir = write_modules("import", &[(
+			"math.dyn",
+			"export let add = |a, b| -> a + b\nlet hidden = 1",
+		)]);
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run_file(
+			dir.join("main.dyn"),
+			"use (add) of \"./math.dyn\"
+let math = use \"./math.dyn\"
+[add(1, 2), math.hidden]",
+		);
+
+		assert_eq!(
+			res,
+			Ok(Value::Array(
+				vec![Value::Integer(3.into()), Value::Nil].into()
+			))
+		);
+	}
+
+	#[test]
+	fn use_should_reject_missing_export() {
+		let dir = write_modules("missing-export", &[(
+			"math.dyn",
+			"let hidden = 1",
+		)]);
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run_file(
+			dir.join("main.dyn"),
+			"use (hidden) of \"./math.dyn\"",
+		);
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::ModuleError(ModuleError::MissingExport {
+					ident: Ident::new_dummy("hidden"),
+					path: dir.join("math.dyn"),
+				})
+			))
+		);
+	}
+
+	#[test]
+	fn module_should_be_evaluated_once() {
+		let dir = write_modules("cache", &[(
+			"state.dyn",
+			"export let items = []",
+		)]);
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run_file(
+			dir.join("main.dyn"),
+			"let a = use \"./state.dyn\"
+let b = use \"./state.dyn\"
+same(a.items, b.items)",
+		);
+
+		assert_eq!(res, Ok(Value::Boolean(true)));
+	}
+
+	#[test]
+	fn use_should_resolve_relative_to_importing_file() {
+		let dir = write_modules("relative", &[
+			(
+				"lib/a.dyn",
+				"use (b) of \"./b.dyn\"\nexport let a = b + 1",
+			),
+			("lib/b.dyn", "export let b = 1"),
+		]);
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run_file(
+			dir.join("main.dyn"),
+			"use (a) of \"./lib/a.dyn\"\na",
+		);
+
+		assert_eq!(res, Ok(Value::Integer(2.into())));
+	}
+
+	#[test]
+	fn import_cycle_should_be_error() {
+		let dir = write_modules("cycle", &[
+			("a.dyn", "use \"./b.dyn\""),
+			("b.dyn", "use \"./a.dyn\""),
+		]);
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run_file(dir.join("a.dyn"), "use \"./b.dyn\"");
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::ModuleError(ModuleError::Failed {
+					failure: Box::new(ModuleFailure {
+						path: dir.join("b.dyn"),
+						code: "use \"./a.dyn\"".to_owned(),
+						error: InterpreterError::RuntimeError(
+							RuntimeError::ModuleError(ModuleError::Cycle {
+								cycle: vec![
+									dir.join("a.dyn"),
+									dir.join("b.dyn"),
+									dir.join("a.dyn"),
+								],
+								span: (4..13).into(),
+							})
+						),
+					}),
+					span: (4..13).into(),
+				})
+			))
+		);
+	}
+
+	#[test]
+	fn error_in_imported_closure_should_carry_its_module() {
+		let lib = "let inner = |x| -> x / 0\nexport let boom = |x| -> inner(x)";
+		let dir = write_modules("raised", &[("lib.dyn", lib)]);
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run_file(
+			dir.join("main.dyn"),
+			"use (boom) of \"./lib.dyn\"\nboom(1)",
+		);
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::Traced {
+				error: RuntimeError::ModuleError(ModuleError::Raised {
+					failure: Box::new(ModuleFailure {
+						path: dir.join("lib.dyn"),
+						code: lib.to_owned(),
+						error: InterpreterError::Traced {
+							error: RuntimeError::ArithmeticError(
+								ArithmeticError::DivisionByZero {
+									op: BinExprKind::Div,
+									lhs: Value::Integer(1.into()),
+									lhs_span: (19..20).into(),
+									rhs_span: (23..24).into(),
+								}
+							),
+							trace: vec![TraceFrame {
+								function: Some("inner".to_owned()),
+								span: (50..58).into(),
+								repeated: 0,
+							}],
+						},
+					}),
+					span: (26..33).into(),
+				}),
+				trace: vec![TraceFrame {
+					function: Some("boom".to_owned()),
+					span: (26..33).into(),
+					repeated: 0,
+				}],
+			})
+		);
+	}
+
+	#[test]
+	fn use_of_missing_file_should_be_error() {
+		let dir = write_modules("not-found", &[]);
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run_file(
+			dir.join("main.dyn"),
+			"use \"./nope.dyn\"",
+		);
+
+		assert!(matches!(
+			res,
+			Err(InterpreterError::RuntimeError(RuntimeError::ModuleError(
+				ModuleError::NotFound { span, .. }
+			))) if span == (4..16).into()
+		));
+	}
+
+	#[test]
+	fn export_should_only_be_allowed_at_top_level() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run("{\n\texport let a = 1\n}");
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::ExportOutsideTopLevel {
+					span: (3..19).into()
+				}
+			))
+		);
+	}
+
+	fn int(i: i64) -> Value { Value::Integer(i.into()) }
+
+	fn string(s: &str) -> Value { Value::String(s.to_owned()) }
+
+	fn array(items: Vec<Value>) -> Value { Value::Array(items.into()) }
+
+	fn range(start: i64, end: i64) -> Value {
+		Value::Range(
+			value::Range::new(
+				start.into(),
+				end.into(),
+				1.into(),
+				false,
+			)
+			.unwrap(),
+		)
+	}
+
+	#[test]
+	fn stdlib_collection_functions() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {r#"
+			let a = [3, 1]
+			push(a, 2)
+			[
+				len(a)
+				pop(a)
+				len("héllo")
+				keys((b: 1, a: 2))
+				values((b: 1, a: 2))
+				range(3)
+				range(1, 3)
+			]
+		"#});
+
+		assert_eq!(
+			res,
+			Ok(array(vec![
+				int(3),
+				int(2),
+				int(5),
+				array(vec![string("a"), string("b")]),
+				array(vec![int(2), int(1)]),
+				range(0, 3),
+				range(1, 3),
+			]))
+		);
+	}
+
+	#[test]
+	fn stdlib_higher_order_functions() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {r#"
+			let xs = range(1, 5)
+			[
+				map(xs, |x| -> x * x)
+				filter(xs, |x| -> x % 2 == 0)
+				reduce(xs, |acc, x| -> acc + x, 0)
+			]
+		"#});
+
+		assert_eq!(
+			res,
+			Ok(array(vec![
+				array(vec![int(1), int(4), int(9), int(16)]),
+				array(vec![int(2), int(4)]),
+				int(10),
+			]))
+		);
+	}
+
+	#[test]
+	fn stdlib_range_should_be_lazy() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {r#"
+			let big = range(1000000000000)
+			[
+				type_of(big)
+				len(big)
+				big[2]
+				map(2..=8..3, |x| -> x * 10)
+				join(range(3), ",")
+			]
+		"#});
+
+		assert_eq!(
+			res,
+			Ok(array(vec![
+				string("range"),
+				int(1000000000000),
+				int(2),
+				array(vec![int(20), int(50), int(80)]),
+				string("0,1,2"),
+			]))
+		);
+	}
+
+	#[test]
+	fn cyclic_values_should_print() {
+		let mut interpreter = Interpreter::init();
+
+		let array = interpreter
+			.run("let! a = []\npush(a, a)\na")
+			.unwrap();
+		let record = interpreter
+			.run("let! r = (a: nil)\nr.a = r\nr")
+			.unwrap();
+
+		assert_eq!(array.to_string(), "[[...]]");
+		assert_eq!(
+			format!("{array:?}"),
+			"Array([Array([...])])"
+		);
+		assert_eq!(array.to_debug(), "[[...]]");
+		assert_eq!(record.to_string(), "(a: (...))");
+	}
+
+	#[test]
+	fn cyclic_values_should_compare_equal() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {"
+			let! a = []
+			push(a, a)
+			let! b = []
+			push(b, b)
+			[a == b, a == [a], a == [[1]]]
+		"});
+
+		assert_eq!(
+			res,
+			Ok(array(vec![
+				Value::Boolean(true),
+				Value::Boolean(true),
+				Value::Boolean(false),
+			]))
+		);
+	}
+
+	#[test]
+	fn deep_copy_should_keep_cycles() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {"
+			let! a = [1]
+			push(a, a)
+			let b = deep_copy(a)
+			push(a, 2)
+			[same(b, b[1]), same(a, b[1]), len(b)]
+		"});
+
+		assert_eq!(
+			res,
+			Ok(array(vec![
+				Value::Boolean(true),
+				Value::Boolean(false),
+				int(2),
+			]))
+		);
+	}
+
+	#[test]
+	fn stdlib_string_functions() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {r#"
+			[
+				split("a,b,c", ",")
+				join([1, "b", true], "-")
+				trim("  hi ")
+				upper("hi")
+				lower("HI")
+				contains("hello", "ell")
+			]
+		"#});
+
+		assert_eq!(
+			res,
+			Ok(array(vec![
+				array(vec![
+					string("a"),
+					string("b"),
+					string("c")
+				]),
+				string("1-b-true"),
+				string("hi"),
+				string("HI"),
+				string("hi"),
+				Value::Boolean(true),
+			]))
+		);
+	}
+
+	#[test]
+	fn stdlib_number_and_value_functions() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {r#"
+			[
+				parse_int("-42")
+				parse_int("4x")
+				min(3, 1.5, 2)
+				max(3, 1.5, 2)
+				abs(-7)
+				type_of(1.0)
+				to_string([1, 2])
+			]
+		"#});
+
+		assert_eq!(
+			res,
+			Ok(array(vec![
+				int(-42),
+				Value::Nil,
+				Value::Float(1.5),
+				int(3),
+				int(7),
+				string("float"),
+				string("[1, 2]"),
+			]))
+		);
+	}
+
+	#[test]
+	fn stdlib_should_check_argument_count() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run("len(1, 2)");
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::ArgumentCount {
+					expected: Arity::exactly(1),
+					found: 2,
+					span: (0..9).into(),
+					definition: None,
+				}
+			))
+		);
+	}
+
+	#[test]
+	fn stdlib_should_check_argument_types() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run("upper(1)");
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::TypeError(TypeError::InvalidArgument {
+					function: "upper",
+					expected: "a string",
+					value: int(1),
+					span: (6..7).into(),
+				})
+			))
+		);
+	}
+
+	#[test]
+	fn stdlib_can_be_left_out() {
+		let mut interpreter =
+			Interpreter::init_without_stdlib(HashMap::new()).unwrap();
+
+		let res = interpreter.run("len([])");
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::ReferenceError(
+					ReferenceError::UndefinedIdentifier {
+						ident: Ident::new_dummy("len")
+					}
+				)
+			))
+		);
+	}
+
+	/// `twice(f, x)` is `f(f(x))`.
+	#[derive(Clone)]
+	struct Twice;
+
+	impl BuiltinFunction for Twice {
+		fn arity(&self) -> Arity { Arity::exactly(2) }
+
+		fn call(
+			&mut self,
+			cx: &mut CallContext,
+			args: ArgumentValues,
+		) -> Result<Value, RuntimeError> {
+			let [f, x] = <[Value; 2]>::try_from(args.0).unwrap();
+			let Value::Function(f) = f else {
+				return Err(RuntimeError::Panic {
+					message: Value::String("not a function".to_owned()),
+					span: cx.argument_span(0).unwrap(),
+				});
+			};
+
+			let x = cx.call(&f, vec![x])?;
+			cx.call(&f, vec![x])
+		}
+	}
+
+	fn init_with_twice() -> Interpreter {
+		Interpreter::init_with_builtins(hashmap! {
+			Ident::new_dummy("twice") => Value::Function(
+				FunctionValue::Builtin(Box::new(Twice))
+			)
+		})
+		.unwrap()
+	}
+
+	#[test]
+	fn builtin_should_call_back_into_closures() {
+		let mut interpreter = init_with_twice();
+
+		let res = interpreter.run("twice(|x| -> x * 3, 2)");
+
+		assert_eq!(res, Ok(Value::Integer(18.into())));
+	}
+
+	#[test]
+	fn builtin_should_report_errors() {
+		let mut interpreter = init_with_twice();
+
+		let res = interpreter.run("twice(1, 2)");
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::Panic {
+					message: Value::String("not a function".to_owned()),
+					span: (6..7).into(),
+				}
+			))
+		);
+	}
+
+	#[test]
+	fn builtins_should_compare_without_panicking() {
+		let mut interpreter = init_with_twice();
+
+		let res = interpreter.run("[twice == twice, twice == len, len == len]");
+
+		assert_eq!(
+			res,
+			Ok(array(vec![
+				Value::Boolean(true),
+				Value::Boolean(false),
+				Value::Boolean(true)
+			]))
+		);
+	}
+
+	#[test]
+	fn errors_in_closures_should_propagate_through_builtins() {
+		let mut interpreter = init_with_twice();
+
+		let res = interpreter.run("twice(|x| -> panic x, 2)");
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::Traced {
+				error: RuntimeError::Panic {
+					message: Value::Integer(2.into()),
+					span: (13..20).into(),
+				},
+				trace: vec![TraceFrame {
+					function: None,
+					span: (0..24).into(),
+					repeated: 0,
+				}]
+			})
+		);
+	}
+
+	#[test]
+	fn builtin_arity_should_be_checked() {
+		let mut interpreter = init_with_twice();
+
+		let res = interpreter.run("twice(|x| -> x)");
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::ArgumentCount {
+					expected: Arity::exactly(2),
+					found: 1,
+					span: (0..15).into(),
+					definition: None,
+				}
+			))
+		);
+	}
+
+	#[test]
+	fn closure_arity_should_be_checked() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run("let f = |a, b| -> a\nf(1)");
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::ArgumentCount {
+					expected: Arity::exactly(2),
+					found: 1,
+					span: (20..24).into(),
+					definition: Some((9..13).into()),
+				}
+			))
+		);
+	}
+
+	#[test]
+	fn default_parameters_should_fill_missing_arguments() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {"
+			let f = |a, b = a * 10| -> a + b
+			[f(1), f(1, 2)]
+		"});
+
+		assert_eq!(res, Ok(array(vec![int(11), int(3)])));
+	}
+
+	#[test]
+	fn rest_parameter_should_collect_extra_arguments() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {"
+			let f = |a, ...rest| -> [a, rest]
+			[f(1), f(1, 2, 3)]
+		"});
+
+		assert_eq!(
+			res,
+			Ok(array(vec![
+				array(vec![int(1), array(vec![])]),
+				array(vec![
+					int(1),
+					array(vec![int(2), int(3)])
+				]),
+			]))
+		);
+	}
+
+	#[test]
+	fn too_many_arguments_should_be_error() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run("(|a, b = 1| -> a)(1, 2, 3)");
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::ArgumentCount {
+					expected: Arity::between(1, 2),
+					found: 3,
+					span: (0..26).into(),
+					definition: Some((2..10).into()),
+				}
+			))
+		);
+	}
+
+	#[test]
+	fn required_parameter_after_default_should_be_syntax_error() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run("|a = 1, b| -> a");
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::ParseError(vec![
+				ParseError(dyn_parser::ParseError::User {
+					error: Spanned::new(
+						8..9,
+						SyntaxError::RequiredParameterAfterDefault
+					)
+				})
+			]))
+		);
+	}
+
+	#[test]
+	fn runtime_error_should_carry_stack_trace() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {"
+			let inner = |x| -> x + true
+			let outer = |x| -> inner(x)
+			outer(1)
+		"});
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::Traced {
+				error: RuntimeError::TypeError(TypeError::BinOp {
+					op: BinExprKind::Add,
+					lhs: int(1),
+					lhs_span: (19..20).into(),
+					rhs: Value::Boolean(true),
+					rhs_span: (23..27).into(),
+				}),
+				trace: vec![
+					TraceFrame {
+						function: Some("inner".to_owned()),
+						span: (47..55).into(),
+						repeated: 0,
+					},
+					TraceFrame {
+						function: Some("outer".to_owned()),
+						span: (56..64).into(),
+						repeated: 0,
+					},
+				]
+			})
+		);
+	}
+
+	#[test]
+	fn closures_should_keep_the_name_they_were_first_bound_to() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {"
+			let f = -> panic 1
+			let g = f
+			g()
+		"});
+
+		assert_eq!(
+			res.unwrap_err(),
+			InterpreterError::Traced {
+				error: RuntimeError::Panic {
+					message: int(1),
+					span: (11..18).into(),
+				},
+				trace: vec![TraceFrame {
+					function: Some("f".to_owned()),
+					span: (29..32).into(),
+					repeated: 0,
+				}]
+			}
+		);
+	}
+
+	#[test]
+	fn unbounded_recursion_should_be_stack_overflow() {
+		let mut interpreter = Interpreter::init();
+		interpreter.set_max_call_depth(50);
+
+		let res = interpreter.run(indoc! {"
+			let f = |n| -> f(n + 1)
+			f(0)
+		"});
+
+		assert_eq!(
+			res.unwrap_err(),
+			InterpreterError::Traced {
+				error: RuntimeError::StackOverflow {
+					depth: 50,
+					span: (15..23).into(),
+				},
+				trace: vec![
+					TraceFrame {
+						function: Some("f".to_owned()),
+						span: (15..23).into(),
+						repeated: 48,
+					},
+					TraceFrame {
+						function: Some("f".to_owned()),
+						span: (24..28).into(),
+						repeated: 0,
+					},
+				]
+			}
+		);
+	}
+
+	#[test]
+	fn recursion_should_reach_exactly_the_default_limit() {
+		let limit = DEFAULT_MAX_CALL_DEPTH;
+		let code = |n| {
+			format!(
+				"let f = |n| -> if n == 0 {{ 0 }} else {{ 1 + f(n - 1) }}\n\
+				 f({n})"
+			)
+		};
+
+		assert_eq!(
+			Interpreter::init().run(&code(limit - 1)),
+			Ok(int(limit as i64 - 1))
+		);
+		assert!(matches!(
+			Interpreter::init().run(&code(limit)),
+			Err(InterpreterError::Traced {
+				error: RuntimeError::StackOverflow { depth, .. },
+				..
+			}) if depth == limit
+		));
+	}
+
+	#[test]
+	fn deep_recursion_should_not_overflow_native_stack() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {"
+			let count = |n| -> if n == 0 { 0 } else { 1 + count(n - 1) }
+			count(500)
+		"});
+
+		assert_eq!(res, Ok(int(500)));
+	}
+
+	#[test]
+	fn undefined_variable_should_fail_before_running() {
+		let mut interpreter = Interpreter::init();
+
+		interpreter
+			.run("let! count = 0")
+			.unwrap();
+		let res = interpreter.run(indoc! {"
+			count += 1
+			-> count + missing
+		"});
+
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::ReferenceError(
+					ReferenceError::UndefinedIdentifier {
+						ident: Ident::new("missing", 22..29)
+					}
+				)
+			))
+		);
+		assert_eq!(interpreter.run("count"), Ok(int(0)));
+	}
+
+	#[test]
+	fn every_undefined_variable_should_be_reported() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run("a + b * a");
+
+		let undefined = |ident| ReferenceError::UndefinedIdentifier { ident };
+		assert_eq!(
+			res,
+			Err(InterpreterError::RuntimeError(
+				RuntimeError::ReferenceError(
+					ReferenceError::UndefinedIdentifiers(vec![
+						undefined(Ident::new("a", 0..1)),
+						undefined(Ident::new("b", 4..5)),
+						undefined(Ident::new("a", 8..9)),
+					])
+				)
+			))
+		);
+		assert_eq!(
+			res.unwrap_err()
+				.related()
+				.map_or(0, |related| related.count()),
+			2
+		);
+	}
+
+	#[test]
+	fn closures_should_see_later_declarations() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {"
+			let is_even = |n| -> if n == 0 { true } else { is_odd(n - 1) }
+			let is_odd = |n| -> if n == 0 { false } else { is_even(n - 1) }
+			is_even(10)
+		"});
+
+		assert_eq!(res, Ok(Value::Boolean(true)));
+	}
+
+	#[test]
+	fn closures_should_keep_scopes_they_were_created_in() {
+		let mut interpreter = Interpreter::init();
+
+		let res = interpreter.run(indoc! {"
+			let counter = -> {
+				let! c = 0
+				-> c += 1
+			}
+			let next = counter()
+			next()
+			let! items = []
+			iter [1, 2, 3] of i {
+				push(items, -> i * 10)
+			}
+			[next(), map(items, |f| -> f())]
+		"});
+
+		assert_eq!(
+			res,
+			Ok(array(vec![
+				int(2),
+				array(vec![int(10), int(20), int(30)])
+			]))
+		);
+	}
+
+	#[test]
+	fn check_should_report_lints() {
+		let mut interpreter = Interpreter::init();
+
+		let lints = interpreter.check(indoc! {"
+			let x = 1
+			x = 2
+			let x = 3
+			let unused = x + missing
+			let f = || -> {
+				return 1
+				x
+			}
+			if true { f() }
+		"});
+
+		assert_eq!(
+			lints
+				.iter()
+				.map(|lint| (
+					lint.code().unwrap().to_string(),
+					lint.span()
+				))
+				.collect::<Vec<_>>(),
+			vec![
+				(
+					"immutable-assignment".to_owned(),
+					(10..11).into()
+				),
+				(
+					"redeclaration".to_owned(),
+					(20..21).into()
+				),
+				(
+					"unused-variable".to_owned(),
+					(30..36).into()
+				),
+				(
+					"undefined-identifier".to_owned(),
+					(43..50).into()
+				),
+				(
+					"unreachable-code".to_owned(),
+					(78..79).into()
+				),
+				(
+					"constant-condition".to_owned(),
+					(85..89).into()
+				),
+			]
+		);
+	}
+
+	#[test]
+	fn check_should_not_run_code() {
+		let mut interpreter = Interpreter::init();
+
+		interpreter
+			.run("let! count = 0")
+			.unwrap();
+		let lints = interpreter.check(indoc! {"
+			count += 1
+			let _ignored = count
+			let even = |n| -> if n == 0 { true } else { !odd(n - 1) }
+			let odd = |n| -> if n == 0 { false } else { even(n - 1) }
+			if even(4) {
+				let y = 1
+				y
+			} else {
+				let y = 2
+				y
+			}
+		"});
+
+		assert_eq!(lints, vec![]);
+		assert_eq!(interpreter.run("count"), Ok(int(0)));
+	}
+
+	#[test]
+	fn check_should_report_every_syntax_error() {
+		let mut interpreter = Interpreter::init();
+
+		let lints = interpreter.check(indoc! {"
+			let a = 1 + )
+			let b = 2
+			if b {
+				b = 3
+				b = )
+			}
+			let c = 3 3
+			if true { c }
+		"});
+
+		assert_eq!(
+			lints
+				.iter()
+				.map(|lint| (
+					lint.code().unwrap().to_string(),
+					lint.span()
+				))
+				.collect::<Vec<_>>(),
+			vec![
+				(
+					"syntax-error".to_owned(),
+					(12..13).into()
+				),
+				(
+					"immutable-assignment".to_owned(),
+					(32..33).into()
+				),
+				(
+					"syntax-error".to_owned(),
+					(43..44).into()
+				),
+				(
+					"syntax-error".to_owned(),
+					(57..58).into()
+				),
+				(
+					"constant-condition".to_owned(),
+					(62..66).into()
+				),
+			]
+		);
 	}
 }
@@ -0,0 +1,68 @@
+use std::{
+	collections::HashMap,
+	path::{Path, PathBuf},
+};
+
+use crate::value::Record;
+
+/// The code of a module along with where it was read from, kept by
+/// closures so that errors raised in them can be rendered against it.
+#[derive(Debug, PartialEq)]
+pub struct Source {
+	pub path: PathBuf,
+	pub code: String,
+}
+
+/// Bookkeeping for `use`: which modules have been evaluated and which
+/// are being evaluated right now.
+#[derive(Debug, Default)]
+pub struct Modules {
+	/// Exports of every module evaluated so far, by canonical path.
+	cache: HashMap<PathBuf, Record>,
+	/// Modules currently being evaluated, the innermost one last.
+	loading: Vec<PathBuf>,
+}
+
+impl Modules {
+	/// Resolves `path` relative to the directory of the module being
+	/// evaluated, or the working directory outside of any file.
+	pub fn resolve(&self, path: &str) -> PathBuf {
+		match self
+			.loading
+			.last()
+			.and_then(|p| p.parent())
+		{
+			Some(dir) => dir.join(path),
+			None => PathBuf::from(path),
+		}
+	}
+
+	pub fn get(&self, path: &Path) -> Option<Record> {
+		self.cache.get(path).cloned()
+	}
+
+	pub fn insert(&mut self, path: PathBuf, exports: Record) {
+		self.cache.insert(path, exports);
+	}
+
+	/// The chain of imports leading back to `path`, if it is still
+	/// being evaluated.
+	pub fn cycle(&self, path: &Path) -> Option<Vec<PathBuf>> {
+		let i = self
+			.loading
+			.iter()
+			.position(|p| p == path)?;
+
+		Some(
+			self.loading[i..]
+				.iter()
+				.cloned()
+				.chain([path.to_owned()])
+				.collect(),
+		)
+	}
+
+	pub fn enter(&mut self, path: PathBuf) { self.loading.push(path); }
+
+	pub fn exit(&mut self) { self.loading.pop(); }
+}
@@ -0,0 +1,333 @@
+use std::cmp::Ordering;
+
+use dyn_parser::ast::{BinExprKind, UnaryExprKind};
+use dyn_span::Span;
+use num_bigint::{BigInt, Sign};
+use num_traits::{FromPrimitive, One, ToPrimitive, Zero};
+
+use crate::{
+	error::{ArithmeticError, TypeError},
+	value::Range,
+	RuntimeError, Value,
+};
+
+pub fn unary(
+	op: UnaryExprKind,
+	expr: Value,
+	expr_span: Span,
+) -> Result<Value, RuntimeError> {
+	Ok(match (op, expr) {
+		(UnaryExprKind::Minus, Value::Integer(i)) => Value::Integer(-i),
+		(UnaryExprKind::Minus, Value::Float(i)) => Value::Float(-i),
+		(UnaryExprKind::Not, Value::Boolean(i)) => Value::Boolean(!i),
+		(op, expr) => Err(TypeError::UnaryOp {
+			op,
+			expr,
+			expr_span,
+		})?,
+	})
+}
+
+/// Evaluates a binary operator.
+///
+/// Arithmetic between an integer and a float promotes the integer to
+/// a float, so `1 + 0.5` is `1.5`. Comparisons are exact instead, so
+/// `1 == 1.0` holds but no integer past 2^53 equals its rounded
+/// float.
+pub fn binary(
+	op: BinExprKind,
+	lhs: Value,
+	lhs_span: Span,
+	rhs: Value,
+	rhs_span: Span,
+) -> Result<Value, RuntimeError> {
+	Ok(match (lhs, op, rhs) {
+		(
+			Value::Integer(i),
+			op @ (BinExprKind::Add
+			| BinExprKind::Sub
+			| BinExprKind::Mul
+			| BinExprKind::Div
+			| BinExprKind::Mod),
+			Value::Integer(j),
+		) => integer_arithmetic(op, i, lhs_span, j, rhs_span)?,
+		(
+			i @ (Value::Integer(_) | Value::Float(_)),
+			op @ (BinExprKind::Add
+			| BinExprKind::Sub
+			| BinExprKind::Mul
+			| BinExprKind::Div
+			| BinExprKind::Mod),
+			j @ (Value::Integer(_) | Value::Float(_)),
+		) => float_arithmetic(op, i, lhs_span, j, rhs_span)?,
+		(
+			i @ (Value::Integer(_) | Value::Float(_)),
+			BinExprKind::Add,
+			Value::String(s),
+		) => Value::String(format!("{i}{s}")),
+		(
+			Value::String(s),
+			BinExprKind::Add,
+			i @ (Value::Integer(_) | Value::Float(_)),
+		) => Value::String(format!("{s}{i}")),
+		(Value::String(s), BinExprKind::Add, Value::String(s2)) =>
+			Value::String(format!("{s}{s2}")),
+		(
+			Value::Integer(i),
+			op @ (BinExprKind::Equal
+			| BinExprKind::NotEqual
+			| BinExprKind::LessThan
+			| BinExprKind::GreaterThan
+			| BinExprKind::LessThanEqual
+			| BinExprKind::GreaterThanEqual),
+			Value::Integer(j),
+		) => Value::Boolean(compare(op, i.partial_cmp(&j))),
+		(
+			i @ (Value::Integer(_) | Value::Float(_)),
+			op @ (BinExprKind::Equal
+			| BinExprKind::NotEqual
+			| BinExprKind::LessThan
+			| BinExprKind::GreaterThan
+			| BinExprKind::LessThanEqual
+			| BinExprKind::GreaterThanEqual),
+			j @ (Value::Integer(_) | Value::Float(_)),
+		) => Value::Boolean(compare(op, compare_numbers(&i, &j))),
+		(i, BinExprKind::Equal, j) => Value::Boolean(i == j),
+		(i, BinExprKind::NotEqual, j) => Value::Boolean(i != j),
+		(i, op, j) => Err(TypeError::BinOp {
+			op,
+			lhs: i,
+			lhs_span,
+			rhs: j,
+			rhs_span,
+		})?,
+	})
+}
+
+/// Evaluates `start..end`, or `start..=end` if `inclusive`, counting
+/// by `step` if given and by one otherwise.
+pub fn range(
+	start: Value,
+	start_span: Span,
+	end: Value,
+	end_span: Span,
+	step: Option<(Value, Span)>,
+	inclusive: bool,
+) -> Result<Value, RuntimeError> {
+	let start = range_bound(start, start_span)?;
+	let end = range_bound(end, end_span)?;
+	let (step, step_span) = match step {
+		Some((step, span)) => (range_bound(step, span)?, span),
+		None => (BigInt::one(), end_span),
+	};
+
+	Range::new(start, end, step, inclusive)
+		.map(Value::Range)
+		.ok_or(RuntimeError::ZeroStep { span: step_span })
+}
+
+fn range_bound(value: Value, span: Span) -> Result<BigInt, RuntimeError> {
+	match value {
+		Value::Integer(i) => Ok(i),
+		value => Err(TypeError::InvalidRangeBound { value, span })?,
+	}
+}
+
+/// Whether a logical operator is decided by its left operand alone,
+/// in which case that operand is the result and the right one must
+/// not be evaluated.
+///
+/// `&&` stops at `false`, `||` at `true` and `??` at anything but
+/// `nil`.
+pub fn short_circuits(
+	op: &BinExprKind,
+	lhs: &Value,
+	lhs_span: Span,
+) -> Result<bool, RuntimeError> {
+	Ok(match (op, lhs) {
+		(BinExprKind::And, Value::Boolean(i)) => !i,
+		(BinExprKind::Or, Value::Boolean(i)) => *i,
+		(BinExprKind::Coalesce, i) => !matches!(i, Value::Nil),
+		(op, value) => Err(TypeError::NonBooleanOperand {
+			op: op.clone(),
+			value: value.clone(),
+			span: lhs_span,
+		})?,
+	})
+}
+
+/// Evaluates a logical operator whose left operand didn't short
+/// circuit, which leaves the right operand as the result.
+pub fn logical(
+	op: BinExprKind,
+	rhs: Value,
+	rhs_span: Span,
+) -> Result<Value, RuntimeError> {
+	Ok(match (op, rhs) {
+		(BinExprKind::And | BinExprKind::Or, Value::Boolean(i)) =>
+			Value::Boolean(i),
+		(BinExprKind::Coalesce, i) => i,
+		(op, value) => Err(TypeError::NonBooleanOperand {
+			op,
+			value,
+			span: rhs_span,
+		})?,
+	})
+}
+
+fn compare(op: BinExprKind, ord: Option<Ordering>) -> bool {
+	match op {
+		BinExprKind::Equal => ord == Some(Ordering::Equal),
+		BinExprKind::NotEqual => ord != Some(Ordering::Equal),
+		BinExprKind::LessThan => ord == Some(Ordering::Less),
+		BinExprKind::GreaterThan => ord == Some(Ordering::Greater),
+		BinExprKind::LessThanEqual => {
+			matches!(
+				ord,
+				Some(Ordering::Less | Ordering::Equal)
+			)
+		},
+		BinExprKind::GreaterThanEqual => {
+			matches!(
+				ord,
+				Some(Ordering::Greater | Ordering::Equal)
+			)
+		},
+		_ => unreachable!(
+			"{} is not a comparison operator",
+			op.as_ref()
+		),
+	}
+}
+
+/// Orders two numbers without rounding either of them, `None` if one
+/// is NaN.
+fn compare_numbers(i: &Value, j: &Value) -> Option<Ordering> {
+	match (i, j) {
+		(Value::Integer(i), Value::Integer(j)) => i.partial_cmp(j),
+		(Value::Float(i), Value::Float(j)) => i.partial_cmp(j),
+		(Value::Integer(i), Value::Float(j)) => compare_mixed(i, *j),
+		(Value::Float(i), Value::Integer(j)) =>
+			compare_mixed(j, *i).map(Ordering::reverse),
+		_ => unreachable!("only numbers are compared"),
+	}
+}
+
+/// Orders an integer and a float by comparing the integer with the
+/// float's integral part, then its fractional part with zero.
+fn compare_mixed(i: &BigInt, j: f64) -> Option<Ordering> {
+	if j.is_infinite() {
+		return Some(match j.is_sign_positive() {
+			true => Ordering::Less,
+			false => Ordering::Greater,
+		});
+	}
+
+	let whole = BigInt::from_f64(j.trunc())?;
+
+	Some(
+		i.cmp(&whole)
+			.then(0.0.partial_cmp(&j.fract())?),
+	)
+}
+
+fn to_float(v: &Value) -> f64 {
+	match v {
+		// NOTE: `to_f64` already saturates, so too large integers
+		// become infinity with the same sign
+		Value::Integer(i) => i.to_f64().unwrap_or(match i.sign() {
+			Sign::Minus => f64::NEG_INFINITY,
+			_ => f64::INFINITY,
+		}),
+		Value::Float(i) => *i,
+		_ => unreachable!(
+			"{} is not a number",
+			v.get_type().type_name()
+		),
+	}
+}
+
+fn integer_arithmetic(
+	op: BinExprKind,
+	i: BigInt,
+	lhs_span: Span,
+	j: BigInt,
+	rhs_span: Span,
+) -> Result<Value, ArithmeticError> {
+	if matches!(op, BinExprKind::Div | BinExprKind::Mod) && j.is_zero() {
+		return Err(ArithmeticError::DivisionByZero {
+			op,
+			lhs: Value::Integer(i),
+			lhs_span,
+			rhs_span,
+		});
+	}
+
+	Ok(Value::Integer(match op {
+		BinExprKind::Add => i + j,
+		BinExprKind::Sub => i - j,
+		BinExprKind::Mul => i * j,
+		BinExprKind::Div => i / j,
+		BinExprKind::Mod => i % j,
+		_ => unreachable!(
+			"{} is not an arithmetic operator",
+			op.as_ref()
+		),
+	}))
+}
+
+fn float_arithmetic(
+	op: BinExprKind,
+	lhs: Value,
+	lhs_span: Span,
+	rhs: Value,
+	rhs_span: Span,
+) -> Result<Value, ArithmeticError> {
+	let (i, j) = (to_float(&lhs), to_float(&rhs));
+	// an integer too large for a float overflows like a result would
+	let saturated =
+		|v: &Value, f: f64| matches!(v, Value::Integer(_)) && f.is_infinite();
+
+	if saturated(&lhs, i) || saturated(&rhs, j) {
+		return Err(ArithmeticError::Overflow {
+			op,
+			lhs,
+			lhs_span,
+			rhs,
+			rhs_span,
+		});
+	}
+
+	if matches!(op, BinExprKind::Div | BinExprKind::Mod) && j == 0.0 {
+		return Err(ArithmeticError::DivisionByZero {
+			op,
+			lhs,
+			lhs_span,
+			rhs_span,
+		});
+	}
+
+	let res = match op {
+		BinExprKind::Add => i + j,
+		BinExprKind::Sub => i - j,
+		BinExprKind::Mul => i * j,
+		BinExprKind::Div => i / j,
+		BinExprKind::Mod => i % j,
+		_ => unreachable!(
+			"{} is not an arithmetic operator",
+			op.as_ref()
+		),
+	};
+
+	if res.is_infinite() && i.is_finite() && j.is_finite() {
+		return Err(ArithmeticError::Overflow {
+			op,
+			lhs,
+			lhs_span,
+			rhs,
+			rhs_span,
+		});
+	}
+
+	Ok(Value::Float(res))
+}
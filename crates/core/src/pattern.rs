@@ -0,0 +1,73 @@
+use dyn_parser::ast::{BinExprKind, Ident, Pattern, PatternKind};
+use dyn_span::Span;
+
+use crate::{operator, value::Array, Value};
+
+/// Matches `value` against `pattern`, collecting what its bindings
+/// are bound to in the order they are written.
+///
+/// Literals are compared like `==` does, so `1` matches `1.0`. Arrays
+/// must have exactly as many elements as the pattern unless it has a
+/// rest, while records may have more fields than the pattern lists.
+/// Fields with a default may be missing too, and are left unbound.
+///
+/// On a mismatch, fails with the span of the part of the pattern that
+/// didn't match and the value it was matched against.
+pub fn destructure<'p>(
+	pattern: &'p Pattern,
+	value: Value,
+	bindings: &mut Vec<(&'p Ident, Value)>,
+) -> Result<(), (Span, Value)> {
+	match (&pattern.kind, value) {
+		(PatternKind::Wildcard, _) => Ok(()),
+		(PatternKind::Literal(literal), value) => match operator::binary(
+			BinExprKind::Equal,
+			Value::from_literal(literal.clone()),
+			pattern.span,
+			value.clone(),
+			pattern.span,
+		) {
+			Ok(Value::Boolean(true)) => Ok(()),
+			_ => Err((pattern.span, value)),
+		},
+		(PatternKind::Binding(ident), value) => {
+			bindings.push((ident, value));
+			Ok(())
+		},
+		(PatternKind::Array { elements, rest }, Value::Array(array)) => {
+			let mut values = array.to_vec();
+			let fits = match rest {
+				Some(_) => values.len() >= elements.len(),
+				None => values.len() == elements.len(),
+			};
+			if !fits {
+				return Err((pattern.span, Value::Array(array)));
+			}
+
+			let remaining = values.split_off(elements.len());
+			for (element, value) in elements.iter().zip(values) {
+				destructure(element, value, bindings)?;
+			}
+			if let Some(rest) = rest {
+				let remaining = Value::Array(Array::new(remaining));
+				destructure(rest, remaining, bindings)?;
+			}
+
+			Ok(())
+		},
+		(PatternKind::Record(fields), Value::Record(record)) => {
+			for field in fields {
+				match (record.get(&field.key), &field.default) {
+					(Some(value), _) =>
+						destructure(&field.pattern, value, bindings)?,
+					(None, Some(_)) => {},
+					(None, None) =>
+						return Err((field.span(), Value::Record(record))),
+				}
+			}
+
+			Ok(())
+		},
+		(_, value) => Err((pattern.span, value)),
+	}
+}
@@ -0,0 +1,145 @@
+use dyn_parser::ast::Ident;
+use dyn_span::Span;
+use num_traits::{Signed, ToPrimitive};
+
+use crate::{error::TypeError, value::Range, RuntimeError, Value};
+
+/// Converts a value evaluated from the expression at `span` into an
+/// index.
+pub fn to_index(index: Value, span: Span) -> Result<usize, RuntimeError> {
+	let index = match index {
+		Value::Integer(index) => index,
+		index => Err(TypeError::InvalidIndex { index, span })?,
+	};
+
+	if index.is_negative() {
+		return Err(RuntimeError::NegativeIndex { index, span });
+	}
+
+	// NOTE: indices that don't fit in `usize` are out of bounds anyway
+	Ok(index.to_usize().unwrap_or(usize::MAX))
+}
+
+/// Reads `value[index]`, where `value` and `index` were evaluated
+/// from the expressions at `span` and `index_span`.
+///
+/// Indexing an array or a string with a range slices it, failing if
+/// any index in the range is out of bounds.
+pub fn index(
+	value: Value,
+	span: Span,
+	index: Value,
+	index_span: Span,
+) -> Result<Value, RuntimeError> {
+	let range = match (&value, index) {
+		(Value::Array(_) | Value::String(_), Value::Range(range)) => range,
+		(_, index) => {
+			let index = to_index(index, index_span)?;
+			return Accessor::Index(index, index_span).get(value, span);
+		},
+	};
+
+	Ok(match value {
+		Value::Array(array) => {
+			let elements = array.to_vec();
+			let indices = slice(&range, elements.len(), index_span)?;
+			Value::Array(
+				indices
+					.map(|i| elements[i].clone())
+					.collect::<Vec<_>>()
+					.into(),
+			)
+		},
+		Value::String(string) => {
+			let chars = string.chars().collect::<Vec<_>>();
+			let indices = slice(&range, chars.len(), index_span)?;
+			Value::String(indices.map(|i| chars[i]).collect())
+		},
+		_ => unreachable!("only arrays and strings are sliced"),
+	})
+}
+
+/// Indices that `range` picks out of `len` elements, leaving out
+/// those past the end like a single index does.
+fn slice(
+	range: &Range,
+	len: usize,
+	span: Span,
+) -> Result<impl Iterator<Item = usize>, RuntimeError> {
+	if let (Some(first), Some(last)) = (range.first(), range.last()) {
+		let low = first.min(last);
+
+		if low.is_negative() {
+			return Err(RuntimeError::NegativeIndex { index: low, span });
+		}
+	}
+
+	Ok(range
+		.truncate(&len.into())
+		.iter()
+		.map(|i| {
+			i.to_usize()
+				.expect("indices are less than the length")
+		}))
+}
+
+/// A single step into a value, with its index already evaluated.
+pub enum Accessor {
+	Prop(Ident),
+	Index(usize, Span),
+}
+
+impl Accessor {
+	/// Reads the accessed field of `value`, which was evaluated from
+	/// the expression at `span`.
+	pub fn get(&self, value: Value, span: Span) -> Result<Value, RuntimeError> {
+		Ok(match (self, value) {
+			(Accessor::Prop(prop), Value::Record(record)) =>
+				record.get(prop).unwrap_or(Value::Nil),
+			(Accessor::Index(index, _), Value::Array(array)) =>
+				array.get(*index).unwrap_or(Value::Nil),
+			(Accessor::Index(index, _), Value::Range(range)) => range
+				.get(*index)
+				.map_or(Value::Nil, Value::Integer),
+			(Accessor::Prop(prop), value) => Err(TypeError::PropertyAccess {
+				prop: prop.clone(),
+				value,
+				span,
+			})?,
+			(Accessor::Index(..), value) =>
+				Err(TypeError::NotIndexable { value, span })?,
+		})
+	}
+
+	/// Replaces the accessed field of `target`, which was evaluated
+	/// from the expression at `span`.
+	pub fn set(
+		&self,
+		target: Value,
+		span: Span,
+		value: Value,
+	) -> Result<(), RuntimeError> {
+		match (self, target) {
+			(Accessor::Prop(prop), Value::Record(record)) => {
+				record.insert(prop.clone(), value);
+			},
+			(Accessor::Index(index, index_span), Value::Array(array)) =>
+				if !array.set(*index, value) {
+					Err(RuntimeError::IndexOutOfBounds {
+						index: *index,
+						len: array.len(),
+						span: *index_span,
+					})?
+				},
+			(Accessor::Prop(prop), value) => Err(TypeError::PropertyAccess {
+				prop: prop.clone(),
+				value,
+				span,
+			})?,
+			(Accessor::Index(..), value) =>
+				Err(TypeError::NotIndexable { value, span })?,
+		}
+
+		Ok(())
+	}
+}
@@ -0,0 +1,333 @@
+//! Static scope resolution.
+//!
+//! Before a script runs, every variable that lives in a local scope
+//! is resolved to how many scopes up it was declared and its slot
+//! there, so the tree walker can find it without comparing names.
+//! Top-level variables stay dynamic, since the REPL and builtins add
+//! to them at runtime.
+
+use std::collections::{HashMap, HashSet};
+
+use dyn_parser::{
+	ast::{
+		Code, Expr, ExprKind, Function, Ident, MatchArm, Pattern, Place,
+		PlaceKind, Use,
+	},
+	walk_expr, Visit,
+};
+use dyn_span::Span;
+
+/// Where a local variable lives, relative to the scope it is used in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Binding {
+	/// Number of scopes between the use and the declaration.
+	pub depth: usize,
+	pub slot: usize,
+}
+
+/// Bindings of the local variables of a script, keyed by the span of
+/// each identifier that declares or uses them.
+#[derive(Debug, Default)]
+pub struct Resolution(HashMap<Span, Binding>);
+
+impl Resolution {
+	pub fn get(&self, ident: &Ident) -> Option<Binding> {
+		self.0.get(&ident.span).copied()
+	}
+}
+
+struct Scope {
+	/// Slot of every variable declared anywhere in the scope, and
+	/// whether its declaration has been visited yet.
+	slots: HashMap<String, (usize, bool)>,
+	/// Whether this is the outermost scope of a closure.
+	function: bool,
+}
+
+/// Resolves the variables of a script, collecting those that can't be
+/// declared anywhere they are used.
+///
+/// Within a function, a use refers to the nearest declaration visited
+/// before it. Closures run later, so they also see declarations that
+/// come after them in enclosing scopes.
+pub struct Resolver<F> {
+	scopes: Vec<Scope>,
+	/// Top-level variables declared anywhere in the script, and those
+	/// visited so far.
+	globals: HashSet<String>,
+	declared_globals: HashSet<String>,
+	/// Whether a top-level variable was declared before the script.
+	defined: F,
+	resolution: Resolution,
+	undefined: Vec<Ident>,
+}
+
+impl<F> Resolver<F>
+where F: Fn(&str) -> bool
+{
+	/// Resolves `code`, where `defined` tells which top-level
+	/// variables already exist.
+	pub fn resolve(code: &Code, defined: F) -> (Resolution, Vec<Ident>) {
+		let mut resolver = Self {
+			scopes: vec![],
+			globals: declarations(&code.stmts)
+				.into_iter()
+				.map(Ident::into_symbol)
+				.collect(),
+			declared_globals: HashSet::new(),
+			defined,
+			resolution: Resolution::default(),
+			undefined: vec![],
+		};
+
+		resolver.visit_code(code);
+
+		(resolver.resolution, resolver.undefined)
+	}
+
+	fn push_scope(&mut self, idents: Vec<Ident>, function: bool) {
+		let mut slots = HashMap::new();
+
+		for ident in idents {
+			let slot = slots.len();
+			slots
+				.entry(ident.into_symbol())
+				.or_insert((slot, false));
+		}
+
+		self.scopes
+			.push(Scope { slots, function });
+	}
+
+	fn declare(&mut self, ident: &Ident) {
+		let Some(scope) = self.scopes.last_mut() else {
+			self.declared_globals
+				.insert(ident.symbol.clone());
+			return;
+		};
+
+		let (slot, declared) = scope
+			.slots
+			.get_mut(&ident.symbol)
+			.expect("declarations are collected before the scope");
+		*declared = true;
+
+		self.resolution
+			.0
+			.insert(ident.span, Binding {
+				depth: 0,
+				slot: *slot,
+			});
+	}
+
+	fn resolve_ident(&mut self, ident: &Ident) {
+		let mut same_function = true;
+
+		for (depth, scope) in self.scopes.iter().rev().enumerate() {
+			if let Some((slot, declared)) = scope.slots.get(&ident.symbol) {
+				if *declared || !same_function {
+					self.resolution
+						.0
+						.insert(ident.span, Binding {
+							depth,
+							slot: *slot,
+						});
+					return;
+				}
+			}
+
+			if scope.function {
+				same_function = false;
+			}
+		}
+
+		let globals = match same_function {
+			true => &self.declared_globals,
+			false => &self.globals,
+		};
+
+		if !globals.contains(&ident.symbol) && !(self.defined)(&ident.symbol) {
+			self.undefined.push(ident.clone());
+		}
+	}
+
+	/// Visits `code` in a scope of its own.
+	fn visit_scope(&mut self, code: &Code) {
+		self.push_scope(declarations(&code.stmts), false);
+		self.visit_code(code);
+		self.scopes.pop();
+	}
+
+	/// Declares the bindings of `pattern`, then visits its defaults,
+	/// which run once the fields that are there have been bound and
+	/// each declare the binding they are for.
+	fn bind_pattern(&mut self, pattern: &Pattern) {
+		let defaults = pattern.defaults();
+		for ident in pattern.bindings() {
+			if defaults
+				.iter()
+				.all(|(defaulted, _)| *defaulted != ident)
+			{
+				self.declare(ident);
+			}
+		}
+		for (ident, default) in defaults {
+			self.visit_expr(default);
+			self.declare(ident);
+		}
+	}
+}
+
+impl<F> Visit for Resolver<F>
+where F: Fn(&str) -> bool
+{
+	fn visit_function(&mut self, i: &Function) {
+		let positional = i.parameters.positional.iter();
+		let mut idents = positional
+			.clone()
+			.flat_map(|parameter| pattern_declarations(&parameter.pattern))
+			.chain(i.parameters.rest.clone())
+			.collect::<Vec<_>>();
+		for parameter in positional.clone() {
+			if let Some(default) = &parameter.default {
+				idents.extend(declarations([default]));
+			}
+		}
+		idents.extend(declarations([&*i.body]));
+
+		self.push_scope(idents, true);
+
+		for parameter in positional {
+			if let Some(default) = &parameter.default {
+				self.visit_expr(default);
+			}
+			self.bind_pattern(&parameter.pattern);
+		}
+		if let Some(rest) = &i.parameters.rest {
+			self.declare(rest);
+		}
+		self.visit_expr(&i.body);
+
+		self.scopes.pop();
+	}
+
+	fn visit_use(&mut self, i: &Use) {
+		for ident in &i.imports {
+			self.declare(ident);
+		}
+	}
+
+	fn visit_match_arm(&mut self, i: &MatchArm) {
+		let mut idents = pattern_declarations(&i.pattern);
+		idents.extend(declarations(
+			i.guard.iter().chain([&i.body]),
+		));
+
+		self.push_scope(idents, false);
+		self.bind_pattern(&i.pattern);
+		if let Some(guard) = &i.guard {
+			self.visit_expr(guard);
+		}
+		self.visit_expr(&i.body);
+		self.scopes.pop();
+	}
+
+	fn visit_place(&mut self, i: &Place) {
+		match &i.kind {
+			PlaceKind::Ident(i) => self.resolve_ident(i),
+			PlaceKind::Prop(i, _) => self.visit_place(i),
+			PlaceKind::Index(i, j) => {
+				self.visit_place(i);
+				self.visit_expr(j);
+			},
+		}
+	}
+
+	fn visit_expr(&mut self, i: &Expr) {
+		match &i.kind {
+			ExprKind::Ident(i) => self.resolve_ident(i),
+			ExprKind::Prop(i, _) => self.visit_expr(i),
+			ExprKind::Declare(ident, value)
+			| ExprKind::DeclareMut(ident, value) => {
+				self.visit_expr(value);
+				self.declare(ident);
+			},
+			ExprKind::Destructure { pattern, value, .. } => {
+				self.visit_expr(value);
+				self.bind_pattern(pattern);
+			},
+			ExprKind::Block(code) => self.visit_scope(code),
+			ExprKind::For {
+				collection,
+				item,
+				body,
+			} => {
+				self.visit_expr(collection);
+
+				let mut idents = pattern_declarations(item);
+				idents.extend(declarations(&body.stmts));
+				self.push_scope(idents, false);
+				self.bind_pattern(item);
+				self.visit_code(body);
+				self.scopes.pop();
+			},
+			_ => walk_expr(self, i),
+		}
+	}
+}
+
+/// Collects the variables that `exprs` declare in the scope they are
+/// evaluated in, leaving out those of nested blocks, loop bodies and
+/// closures.
+pub(crate) fn declarations<'e, I>(exprs: I) -> Vec<Ident>
+where I: IntoIterator<Item = &'e Expr> {
+	let mut collector = Declarations(vec![]);
+
+	for expr in exprs {
+		collector.visit_expr(expr);
+	}
+
+	collector.0
+}
+
+/// Collects the variables that binding `pattern` declares, including
+/// those declared by its defaults.
+pub(crate) fn pattern_declarations(pattern: &Pattern) -> Vec<Ident> {
+	let mut idents = pattern
+		.bindings()
+		.into_iter()
+		.cloned()
+		.collect::<Vec<_>>();
+	idents.extend(declarations(
+		pattern
+			.defaults()
+			.into_iter()
+			.map(|(_, default)| default),
+	));
+	idents
+}
+
+struct Declarations(Vec<Ident>);
+
+impl Visit for Declarations {
+	fn visit_use(&mut self, i: &Use) { self.0.extend(i.imports.clone()); }
+
+	fn visit_expr(&mut self, i: &Expr) {
+		match &i.kind {
+			ExprKind::Declare(ident, value)
+			| ExprKind::DeclareMut(ident, value) => {
+				self.visit_expr(value);
+				self.0.push(ident.clone());
+			},
+			ExprKind::Destructure { pattern, value, .. } => {
+				self.visit_expr(value);
+				self.0
+					.extend(pattern_declarations(pattern));
+			},
+			ExprKind::For { collection, .. } => self.visit_expr(collection),
+			ExprKind::Match { scrutinee, .. } => self.visit_expr(scrutinee),
+			ExprKind::Function(_) | ExprKind::Block(_) => {},
+			_ => walk_expr(self, i),
+		}
+	}
+}
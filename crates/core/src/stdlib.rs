@@ -0,0 +1,256 @@
+//! Builtin functions declared in every interpreter, unless it is
+//! created with
+//! [`Interpreter::init_without_stdlib`](crate::Interpreter::init_without_stdlib).
+
+use std::collections::HashMap;
+
+use dyn_parser::ast::Ident;
+use dyn_span::Span;
+use num_bigint::BigInt;
+
+use crate::{
+	error::TypeError,
+	value::{Array, Items, Record},
+	Arity, CallContext, FunctionValue, RuntimeError, Value,
+};
+
+mod collection;
+mod number;
+mod string;
+mod value;
+
+type NativeFn = fn(&mut CallContext, Arguments) -> Result<Value, RuntimeError>;
+
+/// A function of the standard library.
+///
+/// These work like [`BuiltinFunction`](crate::BuiltinFunction)s, but
+/// are plain function pointers so that two of them can be compared.
+#[derive(Clone, Copy)]
+pub struct Native {
+	pub(crate) name: &'static str,
+	pub(crate) arity: Arity,
+	pub(crate) call: NativeFn,
+}
+
+const NATIVES: &[Native] = &[
+	native(
+		"len",
+		Arity::exactly(1),
+		collection::len,
+	),
+	native(
+		"push",
+		Arity::exactly(2),
+		collection::push,
+	),
+	native(
+		"pop",
+		Arity::exactly(1),
+		collection::pop,
+	),
+	native(
+		"keys",
+		Arity::exactly(1),
+		collection::keys,
+	),
+	native(
+		"values",
+		Arity::exactly(1),
+		collection::values,
+	),
+	native(
+		"range",
+		Arity::between(1, 2),
+		collection::range,
+	),
+	native(
+		"map",
+		Arity::exactly(2),
+		collection::map,
+	),
+	native(
+		"filter",
+		Arity::exactly(2),
+		collection::filter,
+	),
+	native(
+		"reduce",
+		Arity::exactly(3),
+		collection::reduce,
+	),
+	native(
+		"split",
+		Arity::exactly(2),
+		string::split,
+	),
+	native("join", Arity::exactly(2), string::join),
+	native("trim", Arity::exactly(1), string::trim),
+	native(
+		"upper",
+		Arity::exactly(1),
+		string::upper,
+	),
+	native(
+		"lower",
+		Arity::exactly(1),
+		string::lower,
+	),
+	native(
+		"contains",
+		Arity::exactly(2),
+		string::contains,
+	),
+	native(
+		"parse_int",
+		Arity::exactly(1),
+		number::parse_int,
+	),
+	native("min", Arity::at_least(1), number::min),
+	native("max", Arity::at_least(1), number::max),
+	native("abs", Arity::exactly(1), number::abs),
+	native(
+		"type_of",
+		Arity::exactly(1),
+		value::type_of,
+	),
+	native(
+		"to_string",
+		Arity::exactly(1),
+		value::to_string,
+	),
+	native("copy", Arity::exactly(1), value::copy),
+	native(
+		"deep_copy",
+		Arity::exactly(1),
+		value::deep_copy,
+	),
+	native("same", Arity::exactly(2), value::same),
+];
+
+const fn native(name: &'static str, arity: Arity, call: NativeFn) -> Native {
+	Native { name, arity, call }
+}
+
+/// The standard library, keyed by the names it is declared under.
+pub fn builtins() -> HashMap<Ident, Value> {
+	NATIVES
+		.iter()
+		.map(|f| {
+			(
+				Ident::new_dummy(f.name),
+				Value::Function(FunctionValue::Native(*f)),
+			)
+		})
+		.collect()
+}
+
+/// Arguments of a [`Native`] call, already checked against its arity.
+pub struct Arguments {
+	function: &'static str,
+	values: Vec<(Value, Span)>,
+}
+
+impl Arguments {
+	pub fn new(
+		function: &'static str,
+		values: Vec<Value>,
+		cx: &CallContext,
+	) -> Self {
+		let values = values
+			.into_iter()
+			.enumerate()
+			.map(|(i, v)| {
+				(
+					v,
+					cx.argument_span(i).unwrap_or(cx.span()),
+				)
+			})
+			.collect();
+
+		Self { function, values }
+	}
+
+	fn len(&self) -> usize { self.values.len() }
+
+	fn value(&self, i: usize) -> Value { self.values[i].0.clone() }
+
+	fn span_of(&self, i: usize) -> Span { self.values[i].1 }
+
+	/// Extracts the `i`th argument with `f`, or reports that it
+	/// should have been `expected` instead.
+	fn expect<T, F>(
+		&self,
+		i: usize,
+		expected: &'static str,
+		f: F,
+	) -> Result<T, RuntimeError>
+	where
+		F: FnOnce(&Value) -> Option<T>,
+	{
+		let (value, span) = &self.values[i];
+
+		f(value).ok_or_else(|| self.invalid(expected, value.clone(), *span))
+	}
+
+	fn invalid(
+		&self,
+		expected: &'static str,
+		value: Value,
+		span: Span,
+	) -> RuntimeError {
+		TypeError::InvalidArgument {
+			function: self.function,
+			expected,
+			value,
+			span,
+		}
+		.into()
+	}
+
+	fn integer(&self, i: usize) -> Result<BigInt, RuntimeError> {
+		self.expect(i, "an integer", |v| match v {
+			Value::Integer(v) => Some(v.clone()),
+			_ => None,
+		})
+	}
+
+	fn number(&self, i: usize) -> Result<Value, RuntimeError> {
+		self.expect(i, "a number", |v| match v {
+			Value::Integer(_) | Value::Float(_) => Some(v.clone()),
+			_ => None,
+		})
+	}
+
+	fn string(&self, i: usize) -> Result<String, RuntimeError> {
+		self.expect(i, "a string", |v| match v {
+			Value::String(v) => Some(v.clone()),
+			_ => None,
+		})
+	}
+
+	fn array(&self, i: usize) -> Result<Array, RuntimeError> {
+		self.expect(i, "an array", |v| match v {
+			Value::Array(v) => Some(v.clone()),
+			_ => None,
+		})
+	}
+
+	/// Items of an array or a range, to go through once.
+	fn items(&self, i: usize) -> Result<Items, RuntimeError> {
+		self.expect(i, "an array or a range", Value::items)
+	}
+
+	fn record(&self, i: usize) -> Result<Record, RuntimeError> {
+		self.expect(i, "a record", |v| match v {
+			Value::Record(v) => Some(v.clone()),
+			_ => None,
+		})
+	}
+
+	fn function(&self, i: usize) -> Result<FunctionValue, RuntimeError> {
+		self.expect(i, "a function", |v| match v {
+			Value::Function(v) => Some(v.clone()),
+			_ => None,
+		})
+	}
+}
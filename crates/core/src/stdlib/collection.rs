@@ -0,0 +1,152 @@
+use num_bigint::BigInt;
+use num_traits::{One, Zero};
+
+use super::Arguments;
+use crate::{value::Range, CallContext, RuntimeError, Value};
+
+pub fn len(
+	_: &mut CallContext,
+	args: Arguments,
+) -> Result<Value, RuntimeError> {
+	let len = args.expect(
+		0,
+		"an array, a record, a string or a range",
+		|v| match v {
+			Value::Array(v) => Some(v.len().into()),
+			Value::Record(v) => Some(v.len().into()),
+			Value::String(v) => Some(v.chars().count().into()),
+			Value::Range(v) => Some(v.len()),
+			_ => None,
+		},
+	)?;
+
+	Ok(Value::Integer(len))
+}
+
+pub fn push(
+	_: &mut CallContext,
+	args: Arguments,
+) -> Result<Value, RuntimeError> {
+	args.array(0)?.push(args.value(1));
+
+	Ok(Value::Nil)
+}
+
+pub fn pop(
+	_: &mut CallContext,
+	args: Arguments,
+) -> Result<Value, RuntimeError> {
+	Ok(args
+		.array(0)?
+		.pop()
+		.unwrap_or(Value::Nil))
+}
+
+/// Field names of a record, sorted so the result is deterministic.
+pub fn keys(
+	_: &mut CallContext,
+	args: Arguments,
+) -> Result<Value, RuntimeError> {
+	let mut keys = args
+		.record(0)?
+		.to_map()
+		.into_keys()
+		.map(|k| k.into_symbol())
+		.collect::<Vec<_>>();
+	keys.sort();
+
+	Ok(Value::Array(
+		keys.into_iter()
+			.map(Value::String)
+			.collect::<Vec<_>>()
+			.into(),
+	))
+}
+
+/// Field values of a record, in the same order as [`keys`].
+pub fn values(
+	_: &mut CallContext,
+	args: Arguments,
+) -> Result<Value, RuntimeError> {
+	let mut fields = args
+		.record(0)?
+		.to_map()
+		.into_iter()
+		.collect::<Vec<_>>();
+	fields.sort_by(|(i, _), (j, _)| i.symbol().cmp(j.symbol()));
+
+	Ok(Value::Array(
+		fields
+			.into_iter()
+			.map(|(_, v)| v)
+			.collect::<Vec<_>>()
+			.into(),
+	))
+}
+
+/// `range(end)` or `range(start, end)`, excluding `end`, as a lazy
+/// range like `start..end`.
+pub fn range(
+	_: &mut CallContext,
+	args: Arguments,
+) -> Result<Value, RuntimeError> {
+	let (start, end) = match args.len() {
+		1 => (BigInt::zero(), args.integer(0)?),
+		_ => (args.integer(0)?, args.integer(1)?),
+	};
+
+	Ok(Value::Range(
+		Range::new(start, end, BigInt::one(), false)
+			.expect("the step isn't zero"),
+	))
+}
+
+pub fn map(
+	cx: &mut CallContext,
+	args: Arguments,
+) -> Result<Value, RuntimeError> {
+	let (items, f) = (args.items(0)?, args.function(1)?);
+
+	let items = items
+		.map(|v| cx.call(&f, vec![v]))
+		.collect::<Result<Vec<_>, _>>()?;
+
+	Ok(Value::Array(items.into()))
+}
+
+pub fn filter(
+	cx: &mut CallContext,
+	args: Arguments,
+) -> Result<Value, RuntimeError> {
+	let (items, f) = (args.items(0)?, args.function(1)?);
+
+	let mut kept = vec![];
+	for v in items {
+		let keep = cx.call(&f, vec![v.clone()])?;
+
+		match keep {
+			Value::Boolean(true) => kept.push(v),
+			Value::Boolean(false) => {},
+			keep => Err(args.invalid(
+				"a function returning `bool`",
+				keep,
+				args.span_of(1),
+			))?,
+		}
+	}
+
+	Ok(Value::Array(kept.into()))
+}
+
+/// `reduce(items, f, init)` folds an array or a range from the left,
+/// calling `f(accumulator, item)`.
+pub fn reduce(
+	cx: &mut CallContext,
+	args: Arguments,
+) -> Result<Value, RuntimeError> {
+	let (mut items, f) = (args.items(0)?, args.function(1)?);
+
+	items.try_fold(args.value(2), |acc, v| {
+		cx.call(&f, vec![acc, v])
+	})
+}
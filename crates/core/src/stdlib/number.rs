@@ -0,0 +1,64 @@
+use dyn_parser::ast::BinExprKind;
+use num_traits::Signed;
+
+use super::Arguments;
+use crate::{operator, CallContext, RuntimeError, Value};
+
+/// Parses a decimal integer, evaluating to `nil` if it is malformed.
+pub fn parse_int(
+	_: &mut CallContext,
+	args: Arguments,
+) -> Result<Value, RuntimeError> {
+	Ok(args
+		.string(0)?
+		.parse()
+		.map_or(Value::Nil, Value::Integer))
+}
+
+pub fn min(
+	_: &mut CallContext,
+	args: Arguments,
+) -> Result<Value, RuntimeError> {
+	extremum(args, BinExprKind::LessThan)
+}
+
+pub fn max(
+	_: &mut CallContext,
+	args: Arguments,
+) -> Result<Value, RuntimeError> {
+	extremum(args, BinExprKind::GreaterThan)
+}
+
+/// The first argument that is not beaten by any other under `op`.
+fn extremum(args: Arguments, op: BinExprKind) -> Result<Value, RuntimeError> {
+	let mut best = args.number(0)?;
+
+	for i in 1..args.len() {
+		let v = args.number(i)?;
+		let span = args.span_of(i);
+
+		if operator::binary(
+			op.clone(),
+			v.clone(),
+			span,
+			best.clone(),
+			span,
+		)? == Value::Boolean(true)
+		{
+			best = v;
+		}
+	}
+
+	Ok(best)
+}
+
+pub fn abs(
+	_: &mut CallContext,
+	args: Arguments,
+) -> Result<Value, RuntimeError> {
+	Ok(match args.number(0)? {
+		Value::Integer(i) => Value::Integer(i.abs()),
+		Value::Float(i) => Value::Float(i.abs()),
+		_ => unreachable!("`number` only accepts numbers"),
+	})
+}
@@ -0,0 +1,73 @@
+use super::Arguments;
+use crate::{CallContext, RuntimeError, Value};
+
+/// Splits a string on every occurrence of a separator, or into
+/// characters if the separator is empty.
+pub fn split(
+	_: &mut CallContext,
+	args: Arguments,
+) -> Result<Value, RuntimeError> {
+	let (s, sep) = (args.string(0)?, args.string(1)?);
+
+	let parts = if sep.is_empty() {
+		s.chars()
+			.map(|c| Value::String(c.to_string()))
+			.collect::<Vec<_>>()
+	} else {
+		s.split(&sep)
+			.map(|s| Value::String(s.to_owned()))
+			.collect::<Vec<_>>()
+	};
+
+	Ok(Value::Array(parts.into()))
+}
+
+pub fn join(
+	_: &mut CallContext,
+	args: Arguments,
+) -> Result<Value, RuntimeError> {
+	let (items, sep) = (args.items(0)?, args.string(1)?);
+
+	Ok(Value::String(
+		items
+			.map(|v| v.to_string())
+			.collect::<Vec<_>>()
+			.join(&sep),
+	))
+}
+
+pub fn trim(
+	_: &mut CallContext,
+	args: Arguments,
+) -> Result<Value, RuntimeError> {
+	Ok(Value::String(
+		args.string(0)?.trim().to_owned(),
+	))
+}
+
+pub fn upper(
+	_: &mut CallContext,
+	args: Arguments,
+) -> Result<Value, RuntimeError> {
+	Ok(Value::String(
+		args.string(0)?.to_uppercase(),
+	))
+}
+
+pub fn lower(
+	_: &mut CallContext,
+	args: Arguments,
+) -> Result<Value, RuntimeError> {
+	Ok(Value::String(
+		args.string(0)?.to_lowercase(),
+	))
+}
+
+pub fn contains(
+	_: &mut CallContext,
+	args: Arguments,
+) -> Result<Value, RuntimeError> {
+	let (s, pattern) = (args.string(0)?, args.string(1)?);
+
+	Ok(Value::Boolean(s.contains(&pattern)))
+}
@@ -0,0 +1,44 @@
+use super::Arguments;
+use crate::{CallContext, RuntimeError, Value};
+
+pub fn type_of(
+	_: &mut CallContext,
+	args: Arguments,
+) -> Result<Value, RuntimeError> {
+	let ty = args.value(0).get_type().type_name();
+
+	Ok(Value::String(ty.to_owned()))
+}
+
+pub fn to_string(
+	_: &mut CallContext,
+	args: Arguments,
+) -> Result<Value, RuntimeError> {
+	Ok(Value::String(args.value(0).to_string()))
+}
+
+/// Copies an array or record, sharing its elements.
+pub fn copy(
+	_: &mut CallContext,
+	args: Arguments,
+) -> Result<Value, RuntimeError> {
+	Ok(args.value(0).shallow_copy())
+}
+
+/// Copies an array or record and everything in it.
+pub fn deep_copy(
+	_: &mut CallContext,
+	args: Arguments,
+) -> Result<Value, RuntimeError> {
+	Ok(args.value(0).deep_copy())
+}
+
+/// Whether both arguments are the same array or record.
+pub fn same(
+	_: &mut CallContext,
+	args: Arguments,
+) -> Result<Value, RuntimeError> {
+	Ok(Value::Boolean(
+		args.value(0).is_same(&args.value(1)),
+	))
+}
@@ -1,44 +1,501 @@
 use std::{
-	collections::HashMap,
+	cell::RefCell,
+	collections::{HashMap, HashSet},
 	fmt::{self, Debug, Display, Formatter},
 	sync::{Arc, RwLock},
+	vec,
 };
 
 use dyn_clone::{clone_trait_object, DynClone};
-use dyn_parser::ast::{Boolean, Function, Ident, Integer, Literal, StringT};
+use dyn_parser::ast::{
+	Boolean, Float, Function, Ident, Integer, Literal, StringT,
+};
+use dyn_span::Span;
+use num_bigint::BigInt;
+use num_traits::{One, Signed, Zero};
 use strum::EnumDiscriminants;
 
-use crate::environment::Frame;
+use crate::{
+	environment::Frame, module::Source, stdlib::Native, vm::Closure,
+	Interpreter, RuntimeError, Unwind,
+};
+
+/// An array or record being printed or compared, by address.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Visit {
+	Display(usize),
+	Debug(usize),
+	Eq(usize, usize),
+}
+
+thread_local! {
+	static VISITING: RefCell<HashSet<Visit>> = RefCell::new(HashSet::new());
+}
+
+/// Runs `f`, unless the same `visit` is already running further up
+/// the stack, so that arrays and records containing themselves don't
+/// recurse forever.
+fn visit<T>(visit: Visit, f: impl FnOnce() -> T) -> Option<T> {
+	if !VISITING.with_borrow_mut(|v| v.insert(visit)) {
+		return None;
+	}
+
+	let res = f();
+	VISITING.with_borrow_mut(|v| v.remove(&visit));
+	Some(res)
+}
+
+/// Shared, mutable list of values.
+///
+/// Cloning an `Array` only clones the reference, so every clone
+/// observes mutations made through any other one.
+#[derive(Clone, Default)]
+pub struct Array(Arc<RwLock<Vec<Value>>>);
+
+impl Array {
+	pub fn new(elements: Vec<Value>) -> Self {
+		Self(Arc::new(RwLock::new(elements)))
+	}
+
+	pub fn len(&self) -> usize { self.0.read().unwrap().len() }
+
+	pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+	pub fn get(&self, index: usize) -> Option<Value> {
+		self.0
+			.read()
+			.unwrap()
+			.get(index)
+			.cloned()
+	}
+
+	/// Replaces the element at `index`, returning `false` if it is
+	/// out of bounds.
+	pub fn set(&self, index: usize, value: Value) -> bool {
+		match self.0.write().unwrap().get_mut(index) {
+			Some(slot) => {
+				*slot = value;
+				true
+			},
+			None => false,
+		}
+	}
+
+	pub fn push(&self, value: Value) { self.0.write().unwrap().push(value) }
+
+	pub fn pop(&self) -> Option<Value> { self.0.write().unwrap().pop() }
+
+	/// Snapshot of the current elements.
+	pub fn to_vec(&self) -> Vec<Value> { self.0.read().unwrap().clone() }
+
+	/// Whether both arrays are the same object.
+	pub fn ptr_eq(&self, other: &Self) -> bool {
+		Arc::ptr_eq(&self.0, &other.0)
+	}
+
+	fn addr(&self) -> usize { Arc::as_ptr(&self.0) as usize }
+}
+
+impl From<Vec<Value>> for Array {
+	fn from(value: Vec<Value>) -> Self { Self::new(value) }
+}
+
+impl Debug for Array {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		visit(Visit::Debug(self.addr()), || {
+			f.debug_list()
+				.entries(self.0.read().unwrap().iter())
+				.finish()
+		})
+		.unwrap_or_else(|| write!(f, "[...]"))
+	}
+}
+
+impl PartialEq for Array {
+	/// Arrays already being compared further up are taken to be
+	/// equal, so that two arrays containing themselves are.
+	fn eq(&self, other: &Self) -> bool {
+		self.ptr_eq(other)
+			|| visit(
+				Visit::Eq(self.addr(), other.addr()),
+				|| *self.0.read().unwrap() == *other.0.read().unwrap(),
+			)
+			.unwrap_or(true)
+	}
+}
+
+/// Shared, mutable map from keys to values.
+///
+/// Like [`Array`], clones refer to the same fields.
+#[derive(Clone, Default)]
+pub struct Record(Arc<RwLock<HashMap<Ident, Value>>>);
+
+impl Record {
+	pub fn new(fields: HashMap<Ident, Value>) -> Self {
+		Self(Arc::new(RwLock::new(fields)))
+	}
+
+	pub fn len(&self) -> usize { self.0.read().unwrap().len() }
+
+	pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+	pub fn get(&self, key: &Ident) -> Option<Value> {
+		self.0.read().unwrap().get(key).cloned()
+	}
+
+	pub fn insert(&self, key: Ident, value: Value) -> Option<Value> {
+		self.0
+			.write()
+			.unwrap()
+			.insert(key, value)
+	}
+
+	/// Snapshot of the current fields.
+	pub fn to_map(&self) -> HashMap<Ident, Value> {
+		self.0.read().unwrap().clone()
+	}
+
+	/// Whether both records are the same object.
+	pub fn ptr_eq(&self, other: &Self) -> bool {
+		Arc::ptr_eq(&self.0, &other.0)
+	}
+
+	fn addr(&self) -> usize { Arc::as_ptr(&self.0) as usize }
+}
+
+impl From<HashMap<Ident, Value>> for Record {
+	fn from(value: HashMap<Ident, Value>) -> Self { Self::new(value) }
+}
+
+impl Debug for Record {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		visit(Visit::Debug(self.addr()), || {
+			f.debug_struct("Record")
+				.field("fields", &*self.0.read().unwrap())
+				.finish()
+		})
+		.unwrap_or_else(|| write!(f, "(...)"))
+	}
+}
+
+impl PartialEq for Record {
+	/// Like arrays, records already being compared further up are
+	/// taken to be equal.
+	fn eq(&self, other: &Self) -> bool {
+		self.ptr_eq(other)
+			|| visit(
+				Visit::Eq(self.addr(), other.addr()),
+				|| *self.0.read().unwrap() == *other.0.read().unwrap(),
+			)
+			.unwrap_or(true)
+	}
+}
+
+/// Integers from `start` towards `end`, counting by `step`.
+///
+/// Elements are only computed when they are needed, so a range costs
+/// the same whatever its length.
+#[derive(Debug, Clone)]
+pub struct Range {
+	start: BigInt,
+	end: BigInt,
+	/// Never zero.
+	step: BigInt,
+	/// Whether `end` is part of the range if the steps land on it.
+	inclusive: bool,
+}
+
+impl Range {
+	/// Fails if `step` is zero.
+	pub fn new(
+		start: BigInt,
+		end: BigInt,
+		step: BigInt,
+		inclusive: bool,
+	) -> Option<Self> {
+		(!step.is_zero()).then_some(Self {
+			start,
+			end,
+			step,
+			inclusive,
+		})
+	}
+
+	/// First integer past the end of the range, in its direction.
+	fn bound(&self) -> BigInt {
+		match self.inclusive {
+			true => &self.end + self.step.signum(),
+			false => self.end.clone(),
+		}
+	}
+
+	pub fn len(&self) -> BigInt {
+		let distance = self.bound() - &self.start;
+
+		if distance.sign() != self.step.sign() {
+			return BigInt::zero();
+		}
+
+		(distance + &self.step - self.step.signum()) / &self.step
+	}
+
+	pub fn is_empty(&self) -> bool { self.len().is_zero() }
+
+	pub fn get(&self, index: usize) -> Option<BigInt> {
+		let index = BigInt::from(index);
+
+		(index < self.len()).then(|| &self.start + index * &self.step)
+	}
+
+	pub fn first(&self) -> Option<BigInt> {
+		(!self.is_empty()).then(|| self.start.clone())
+	}
+
+	pub fn last(&self) -> Option<BigInt> {
+		let len = self.len();
+
+		(!len.is_zero()).then(|| &self.start + (len - 1) * &self.step)
+	}
+
+	/// The range without its elements that are `limit` or more.
+	pub fn truncate(&self, limit: &BigInt) -> Range {
+		if self.step.is_positive() {
+			if self.bound() <= *limit {
+				return self.clone();
+			}
+
+			return Range {
+				end: limit.clone(),
+				inclusive: false,
+				..self.clone()
+			};
+		}
+
+		if self.start < *limit {
+			return self.clone();
+		}
+
+		// NOTE: steps to take from `start` to land below `limit`
+		let steps = (&self.start - limit) / -&self.step + 1;
+
+		Range {
+			start: &self.start + steps * &self.step,
+			..self.clone()
+		}
+	}
+
+	pub fn iter(&self) -> RangeIter {
+		RangeIter {
+			next: self.start.clone(),
+			bound: self.bound(),
+			step: self.step.clone(),
+		}
+	}
+}
+
+impl PartialEq for Range {
+	/// Ranges are equal if they have the same elements, however they
+	/// were written.
+	fn eq(&self, other: &Self) -> bool {
+		let len = self.len();
+
+		len == other.len()
+			&& (len.is_zero() || self.start == other.start)
+			&& (len <= BigInt::one() || self.step == other.step)
+	}
+}
+
+impl Display for Range {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		let op = if self.inclusive { "..=" } else { ".." };
+		write!(f, "{}{op}{}", self.start, self.end)?;
+
+		if !self.step.is_one() {
+			write!(f, "..{}", self.step)?;
+		}
+
+		Ok(())
+	}
+}
+
+/// Iterator over the elements of a [`Range`].
+pub struct RangeIter {
+	next: BigInt,
+	bound: BigInt,
+	step: BigInt,
+}
+
+impl Iterator for RangeIter {
+	type Item = BigInt;
+
+	fn next(&mut self) -> Option<BigInt> {
+		let more = match self.step.is_positive() {
+			true => self.next < self.bound,
+			false => self.next > self.bound,
+		};
 
-#[derive(Debug, Clone, PartialEq)]
-pub struct Record {
-	pub fields: HashMap<Ident, Value>,
+		more.then(|| {
+			let next = &self.next + &self.step;
+			std::mem::replace(&mut self.next, next)
+		})
+	}
+}
+
+/// Items that `iter` goes through.
+pub enum Items {
+	/// Elements of an array as they were when the loop started.
+	Array(vec::IntoIter<Value>),
+	Range(RangeIter),
+}
+
+impl Iterator for Items {
+	type Item = Value;
+
+	fn next(&mut self) -> Option<Value> {
+		match self {
+			Items::Array(i) => i.next(),
+			Items::Range(i) => i.next().map(Value::Integer),
+		}
+	}
 }
 
 pub struct ArgumentValues(pub Vec<Value>);
 
+/// Number of arguments a function accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Arity {
+	pub min: usize,
+	/// `None` if any number of arguments past `min` is accepted.
+	pub max: Option<usize>,
+}
+
+impl Arity {
+	pub const fn exactly(n: usize) -> Self {
+		Self {
+			min: n,
+			max: Some(n),
+		}
+	}
+
+	pub const fn between(min: usize, max: usize) -> Self {
+		Self {
+			min,
+			max: Some(max),
+		}
+	}
+
+	pub const fn at_least(min: usize) -> Self { Self { min, max: None } }
+
+	pub fn accepts(&self, n: usize) -> bool {
+		self.min <= n && self.max.is_none_or(|max| n <= max)
+	}
+}
+
+impl Display for Arity {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		let plural = |n: usize| if n == 1 { "argument" } else { "arguments" };
+
+		match self.max {
+			Some(max) if max == self.min => write!(f, "{max} {}", plural(max)),
+			Some(max) => write!(
+				f,
+				"{} to {max} {}",
+				self.min,
+				plural(max)
+			),
+			None => write!(
+				f,
+				"at least {} {}",
+				self.min,
+				plural(self.min)
+			),
+		}
+	}
+}
+
+/// What a builtin function can see of the call it is handling.
+pub struct CallContext<'a> {
+	pub(crate) interpreter: &'a mut Interpreter,
+	pub(crate) span: Span,
+	pub(crate) argument_spans: Vec<Span>,
+}
+
+impl CallContext<'_> {
+	/// Span of the whole call expression.
+	pub fn span(&self) -> Span { self.span }
+
+	/// Span of the `i`th argument expression.
+	pub fn argument_span(&self, i: usize) -> Option<Span> {
+		self.argument_spans.get(i).copied()
+	}
+
+	/// Calls a dyn function, such as a closure passed as an argument.
+	pub fn call(
+		&mut self,
+		function: &FunctionValue,
+		args: Vec<Value>,
+	) -> Result<Value, RuntimeError> {
+		let args = args
+			.into_iter()
+			.map(|v| (v, self.span))
+			.collect();
+
+		self.interpreter
+			.call(function.clone(), args, self.span)
+			.map_err(Unwind::into_error)
+	}
+}
+
 pub trait BuiltinFunction: DynClone {
-	fn call(&mut self, args: ArgumentValues) -> Value;
+	/// Number of arguments accepted, checked before [`call`] is
+	/// invoked.
+	///
+	/// [`call`]: BuiltinFunction::call
+	fn arity(&self) -> Arity;
+
+	fn call(
+		&mut self,
+		cx: &mut CallContext,
+		args: ArgumentValues,
+	) -> Result<Value, RuntimeError>;
+
+	/// Name used to compare builtins, since every lookup hands out a
+	/// fresh clone. Defaults to the name of the implementing type.
+	fn name(&self) -> &'static str { std::any::type_name::<Self>() }
 }
 
 clone_trait_object!(BuiltinFunction);
 
 pub enum FunctionValue {
 	Builtin(Box<dyn BuiltinFunction + Send + Sync>),
+	Native(Native),
 	Closure {
 		body: Function,
-		capture: Arc<RwLock<Frame>>,
+		capture: Frame,
+		/// Name of the variable the closure was first bound to, used
+		/// in stack traces.
+		name: Option<String>,
+		/// Module the closure was defined in, if it was read from a
+		/// file.
+		source: Option<Arc<Source>>,
 	},
+	/// Closure created by the bytecode backend.
+	Compiled(Arc<Closure>),
 }
 
 impl Debug for FunctionValue {
 	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
 		match self {
 			Self::Builtin(_) => write!(f, "[BUILTIN FUNCTION]"),
-			Self::Closure { body, capture: _ } => f
+			Self::Native(i) => write!(f, "[BUILTIN FUNCTION {}]", i.name),
+			Self::Closure { body, .. } => f
 				.debug_tuple("Lambda")
 				.field(body)
 				.finish(),
+			Self::Compiled(closure) => f
+				.debug_tuple("Compiled")
+				.field(&closure.proto)
+				.finish(),
 		}
 	}
 }
@@ -47,10 +504,19 @@ impl Clone for FunctionValue {
 	fn clone(&self) -> Self {
 		match self {
 			Self::Builtin(arg0) => Self::Builtin(arg0.clone()),
-			Self::Closure { body, capture } => Self::Closure {
+			Self::Native(arg0) => Self::Native(*arg0),
+			Self::Closure {
+				body,
+				capture,
+				name,
+				source,
+			} => Self::Closure {
 				body: body.clone(),
-				capture: Arc::clone(capture),
+				capture: capture.clone(),
+				name: name.clone(),
+				source: source.clone(),
 			},
+			Self::Compiled(closure) => Self::Compiled(Arc::clone(closure)),
 		}
 	}
 }
@@ -58,11 +524,14 @@ impl Clone for FunctionValue {
 impl PartialEq for FunctionValue {
 	fn eq(&self, other: &Self) -> bool {
 		match (self, other) {
-			(Self::Builtin(_), Self::Builtin(_)) => unimplemented!(),
+			(Self::Builtin(i), Self::Builtin(j)) => i.name() == j.name(),
+			(Self::Native(i), Self::Native(j)) => i.name == j.name,
 			(
 				Self::Closure { body: b1, .. },
 				Self::Closure { body: b2, .. },
 			) => b1 == b2,
+			(Self::Compiled(i), Self::Compiled(j)) =>
+				Arc::ptr_eq(&i.proto, &j.proto),
 			_ => false,
 		}
 	}
@@ -73,20 +542,22 @@ impl PartialEq for FunctionValue {
 pub enum Value {
 	Nil,
 	Boolean(bool),
-	Integer(i32),
+	Integer(BigInt),
+	Float(f64),
 	String(String),
-	Array(Vec<Value>),
+	Array(Array),
 	Record(Record),
+	Range(Range),
 	Function(FunctionValue),
 }
 
 impl Value {
-	#[allow(unused)]
-	fn from_literal(ex: Literal) -> Self {
+	pub(crate) fn from_literal(ex: Literal) -> Self {
 		match ex {
 			Literal::Nil(_) => Self::Nil,
 			Literal::Boolean(Boolean { value, .. }) => Self::Boolean(value),
 			Literal::Integer(Integer { value, .. }) => Self::Integer(value),
+			Literal::Float(Float { value, .. }) => Self::Float(value),
 			Literal::String(StringT { value, .. }) => Self::String(value),
 		}
 	}
@@ -94,61 +565,173 @@ impl Value {
 	pub fn to_debug(&self) -> String {
 		match self {
 			Value::Nil => "nil".to_owned(),
-			Value::Boolean(v) => {
+			Value::Boolean(v) =>
 				if *v {
 					"true".to_owned()
 				} else {
 					"false".to_owned()
-				}
-			},
+				},
 			Value::Integer(v) => format!("{v}"),
+			Value::Float(v) => format!("{v:?}"),
 			Value::String(v) => format!(r#""{}""#, v),
-			Value::Array(v) => format!(
-				"[{}]",
-				v.iter()
-					.map(|e| e.to_debug())
-					.collect::<Vec<_>>()
-					.join(", ")
-			),
-			Value::Record(v) => format!(
-				"({})",
-				v.fields
-					.iter()
-					.map(|(k, v)| format!("{k:?}: {v:?}"))
-					.collect::<Vec<_>>()
-					.join(", ")
-			),
+			Value::Array(v) => visit(Visit::Debug(v.addr()), || {
+				format!(
+					"[{}]",
+					v.to_vec()
+						.iter()
+						.map(|e| e.to_debug())
+						.collect::<Vec<_>>()
+						.join(", ")
+				)
+			})
+			.unwrap_or_else(|| "[...]".to_owned()),
+			Value::Record(v) => visit(Visit::Debug(v.addr()), || {
+				format!(
+					"({})",
+					v.to_map()
+						.iter()
+						.map(|(k, v)| format!("{k:?}: {v:?}"))
+						.collect::<Vec<_>>()
+						.join(", ")
+				)
+			})
+			.unwrap_or_else(|| "(...)".to_owned()),
+			Value::Range(v) => v.to_string(),
 			Value::Function(_) => "[FUNCTION]".to_owned(),
 		}
 	}
 
-	pub fn get_type(&self) -> ValueType {
-		self.into()
+	pub fn get_type(&self) -> ValueType { self.into() }
+
+	/// Items of an array or a range, or `None` if the value can't be
+	/// iterated over.
+	pub fn items(&self) -> Option<Items> {
+		match self {
+			Value::Array(v) => Some(Items::Array(v.to_vec().into_iter())),
+			Value::Range(v) => Some(Items::Range(v.iter())),
+			_ => None,
+		}
+	}
+
+	/// Copies the outermost array or record, sharing its elements.
+	pub fn shallow_copy(&self) -> Self {
+		match self {
+			Value::Array(v) => Value::Array(v.to_vec().into()),
+			Value::Record(v) => Value::Record(v.to_map().into()),
+			v => v.clone(),
+		}
+	}
+
+	/// Recursively copies arrays and records so that the result
+	/// shares nothing with `self`. An array or record reached twice,
+	/// as in a cycle, is copied once and the copy is reused.
+	pub fn deep_copy(&self) -> Self { self.deep_copy_with(&mut HashMap::new()) }
+
+	/// Like [`Value::deep_copy`], with the copies made so far keyed
+	/// by the address of what they copy.
+	fn deep_copy_with(&self, copies: &mut HashMap<usize, Value>) -> Self {
+		match self {
+			Value::Array(v) => {
+				if let Some(copy) = copies.get(&v.addr()) {
+					return copy.clone();
+				}
+
+				let copy = Array::default();
+				copies.insert(v.addr(), Value::Array(copy.clone()));
+				for item in v.to_vec() {
+					copy.push(item.deep_copy_with(copies));
+				}
+
+				Value::Array(copy)
+			},
+			Value::Record(v) => {
+				if let Some(copy) = copies.get(&v.addr()) {
+					return copy.clone();
+				}
+
+				let copy = Record::default();
+				copies.insert(v.addr(), Value::Record(copy.clone()));
+				for (key, value) in v.to_map() {
+					copy.insert(key, value.deep_copy_with(copies));
+				}
+
+				Value::Record(copy)
+			},
+			v => v.clone(),
+		}
+	}
+
+	/// Names an anonymous closure after `ident`, leaving any other
+	/// value as it is.
+	pub fn named(self, ident: &Ident) -> Self {
+		match self {
+			Value::Function(FunctionValue::Closure {
+				body,
+				capture,
+				name: None,
+				source,
+			}) => Value::Function(FunctionValue::Closure {
+				body,
+				capture,
+				name: Some(ident.symbol().to_owned()),
+				source,
+			}),
+			Value::Function(FunctionValue::Compiled(closure))
+				if closure.name.is_none() =>
+			{
+				let closure = closure.named(ident.symbol().to_owned());
+				Value::Function(FunctionValue::Compiled(Arc::new(
+					closure,
+				)))
+			},
+			v => v,
+		}
+	}
+
+	/// Identity comparison: arrays and records are only the same if
+	/// they are the same object, other values are compared by value.
+	pub fn is_same(&self, other: &Self) -> bool {
+		match (self, other) {
+			(Value::Array(i), Value::Array(j)) => i.ptr_eq(j),
+			(Value::Record(i), Value::Record(j)) => i.ptr_eq(j),
+			(i, j) => i == j,
+		}
 	}
 }
 
 impl Display for Value {
 	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-		write!(
-			f,
-			"{}",
-			match self {
-				Value::Nil => "nil".to_owned(),
-				Value::Boolean(i) => i.to_string(),
-				Value::Integer(i) => i.to_string(),
-				Value::String(i) => i.to_string(),
-				Value::Array(i) => format!("{i:?}"),
-				Value::Record(i) => format!(
+		write!(f, "{}", match self {
+			Value::Nil => "nil".to_owned(),
+			Value::Boolean(i) => i.to_string(),
+			Value::Integer(i) => i.to_string(),
+			Value::Float(i) => format!("{i:?}"),
+			Value::String(i) => i.to_string(),
+			Value::Array(i) => visit(Visit::Display(i.addr()), || {
+				format!(
+					"[{}]",
+					i.to_vec()
+						.iter()
+						.map(|v| v.to_string())
+						.collect::<Vec<_>>()
+						.join(", ")
+				)
+			})
+			.unwrap_or_else(|| "[...]".to_owned()),
+			Value::Record(i) => visit(Visit::Display(i.addr()), || {
+				format!(
 					"({})",
-					i.fields
+					i.to_map()
 						.iter()
 						.map(|(k, v)| format!("{}: {v}", k.symbol()))
 						.collect::<Vec<_>>()
 						.join(", ")
-				),
-				Value::Function(_) => "FUNCTION".to_owned(),
-			}
-		)
+				)
+			})
+			.unwrap_or_else(|| "(...)".to_owned()),
+			Value::Range(i) => i.to_string(),
+			Value::Function(_) => "FUNCTION".to_owned(),
+		})
 	}
 }
 
@@ -157,10 +740,12 @@ impl ValueType {
 		match self {
 			ValueType::Nil => "nil",
 			ValueType::Boolean => "bool",
-			ValueType::Integer => "number",
+			ValueType::Integer => "integer",
+			ValueType::Float => "float",
 			ValueType::String => "string",
 			ValueType::Array => "array",
 			ValueType::Record => "record",
+			ValueType::Range => "range",
 			ValueType::Function => "function",
 		}
 	}
@@ -0,0 +1,917 @@
+//! The bytecode backend.
+//!
+//! Scripts are compiled to [`Proto`]s with variables resolved to
+//! slots, then run on a stack machine. Unlike the tree walker, calls
+//! between closures don't recurse on the native stack and no syntax
+//! tree is cloned while running.
+
+use std::{
+	collections::HashMap,
+	sync::{Arc, RwLock},
+	vec,
+};
+
+use dyn_parser::ast::{Code, Ident};
+use dyn_span::Span;
+
+use self::{
+	bytecode::{Capture, Failure, Op, Pattern, Proto, Step, Var},
+	compiler::Compiler,
+};
+use crate::{
+	error::{ModuleError, TypeError},
+	module::Source,
+	operator,
+	pattern::destructure,
+	place::{self, to_index, Accessor},
+	value::Items,
+	FunctionValue, Interpreter, ReferenceError, RuntimeError, SymbolInfo,
+	Unwind, Value, STACK_RED_ZONE, STACK_SEGMENT_SIZE,
+};
+
+mod bytecode;
+mod compiler;
+
+/// Top-level variables of a script, indexed by the compiler.
+#[derive(Debug, Default)]
+pub struct Globals {
+	indices: HashMap<String, u32>,
+	slots: Vec<Option<SymbolInfo>>,
+}
+
+impl Globals {
+	/// Globals with `builtins` declared in them.
+	pub fn shared(builtins: &HashMap<Ident, Value>) -> Arc<RwLock<Self>> {
+		let mut globals = Self::default();
+
+		for (ident, value) in builtins {
+			let i = globals.index(&ident.symbol) as usize;
+			globals.slots[i] = Some(SymbolInfo {
+				mutable: false,
+				value: value.clone(),
+				declaration: None,
+			});
+		}
+
+		Arc::new(RwLock::new(globals))
+	}
+
+	/// Index of the global called `name`, which may not be declared
+	/// yet.
+	pub fn index(&mut self, name: &str) -> u32 {
+		if let Some(i) = self.indices.get(name) {
+			return *i;
+		}
+
+		self.slots.push(None);
+		let i = self.slots.len() as u32 - 1;
+		self.indices.insert(name.to_owned(), i);
+		i
+	}
+
+	pub fn contains(&self, name: &str) -> bool {
+		self.indices
+			.get(name)
+			.is_some_and(|i| self.slots[*i as usize].is_some())
+	}
+
+	pub fn load(&self, ident: &Ident) -> Result<Value, RuntimeError> {
+		self.indices
+			.get(&ident.symbol)
+			.and_then(|i| self.slots[*i as usize].as_ref())
+			.map(|symbol| symbol.value.clone())
+			.ok_or_else(|| undefined(ident))
+	}
+}
+
+/// A variable captured by a closure, shared with the frame it was
+/// declared in.
+type Cell = Arc<RwLock<Option<SymbolInfo>>>;
+
+/// A compiled function together with the variables it captured.
+#[derive(Debug, Clone)]
+pub struct Closure {
+	pub proto: Arc<Proto>,
+	upvalues: Vec<Cell>,
+	globals: Arc<RwLock<Globals>>,
+	/// Name of the variable the closure was first bound to, used in
+	/// stack traces.
+	pub name: Option<String>,
+	/// Module the closure was defined in, if it was read from a file.
+	source: Option<Arc<Source>>,
+}
+
+impl Closure {
+	pub fn named(&self, name: String) -> Self {
+		Self {
+			name: Some(name),
+			..self.clone()
+		}
+	}
+}
+
+/// Storage of a local variable. A variable is moved into a [`Cell`]
+/// when a closure captures it.
+enum Slot {
+	Local(Option<SymbolInfo>),
+	Captured(Cell),
+}
+
+struct Frame {
+	closure: Arc<Closure>,
+	ip: usize,
+	/// Where the frame's locals, operands and iterations start.
+	locals: usize,
+	stack: usize,
+	iterations: usize,
+	/// Span of the call, `None` for the top level of a script.
+	call: Option<Span>,
+}
+
+/// State of an `iter` expression.
+struct Iteration {
+	items: Items,
+	/// Height of the operand stack when the iteration started.
+	stack: usize,
+}
+
+/// Execution state of the bytecode backend.
+pub struct Vm {
+	pub globals: Arc<RwLock<Globals>>,
+	stack: Vec<Value>,
+	locals: Vec<Slot>,
+	frames: Vec<Frame>,
+	iterations: Vec<Iteration>,
+}
+
+impl Vm {
+	pub fn new(globals: Arc<RwLock<Globals>>) -> Self {
+		Self {
+			globals,
+			stack: vec![],
+			locals: vec![],
+			frames: vec![],
+			iterations: vec![],
+		}
+	}
+
+	fn pop(&mut self) -> Value {
+		self.stack
+			.pop()
+			.expect("operand stack underflow")
+	}
+
+	fn pop_n(&mut self, n: usize) -> Vec<Value> {
+		self.stack
+			.split_off(self.stack.len() - n)
+	}
+
+	/// Calls `f` with the binding of `var` as seen from `closure`,
+	/// whose locals start at `base`.
+	fn binding<T>(
+		&mut self,
+		var: Var,
+		closure: &Closure,
+		base: usize,
+		f: impl FnOnce(&mut Option<SymbolInfo>) -> T,
+	) -> T {
+		match var {
+			Var::Local(slot) => match &mut self.locals[base + slot as usize] {
+				Slot::Local(symbol) => f(symbol),
+				Slot::Captured(cell) => f(&mut cell.write().unwrap()),
+			},
+			Var::Upvalue(i) => f(&mut closure.upvalues[i as usize]
+				.write()
+				.unwrap()),
+			Var::Global(i) =>
+				f(&mut closure.globals.write().unwrap().slots[i as usize]),
+		}
+	}
+
+	/// Declares what the bindings of `pattern` matched, making sure
+	/// the ones left unbound for a default aren't declared already.
+	fn bind(
+		&mut self,
+		pattern: &Pattern,
+		bindings: Vec<(&Ident, Value)>,
+		closure: &Closure,
+		base: usize,
+	) -> Result<(), RuntimeError> {
+		let mut bindings = bindings.into_iter().peekable();
+
+		for (ident, var) in &pattern.vars {
+			let value = bindings
+				.next_if(|(bound, _)| *bound == ident)
+				.map(|(_, value)| value);
+
+			self.binding(*var, closure, base, |slot| {
+				match (slot, value) {
+					(Some(_), _) => Err(RuntimeError::AlreadyDeclared),
+					(slot, Some(value)) => {
+						*slot = Some(SymbolInfo {
+							mutable: pattern.mutable,
+							value,
+							declaration: Some(ident.span),
+						});
+						Ok(())
+					},
+					(None, None) => Ok(()),
+				}
+			})?;
+		}
+
+		Ok(())
+	}
+
+	/// Moves the local at `slot` into a cell, if it isn't already, so
+	/// that it can be shared with a closure.
+	fn capture(&mut self, slot: usize) -> Cell {
+		let slot = &mut self.locals[slot];
+
+		if let Slot::Local(symbol) = slot {
+			*slot = Slot::Captured(Arc::new(RwLock::new(symbol.take())));
+		}
+
+		match slot {
+			Slot::Captured(cell) => cell.clone(),
+			Slot::Local(_) => unreachable!(),
+		}
+	}
+}
+
+fn undefined(ident: &Ident) -> RuntimeError {
+	ReferenceError::UndefinedIdentifier {
+		ident: ident.clone(),
+	}
+	.into()
+}
+
+impl Interpreter {
+	/// Compiles and runs the top level of a script on the bytecode
+	/// backend.
+	pub(crate) fn run_bytecode(
+		&mut self,
+		code: &Code,
+	) -> Result<Value, RuntimeError> {
+		let globals = self.vm.globals.clone();
+		let proto = Compiler::compile(code, &mut globals.write().unwrap());
+		let closure = Closure {
+			proto: Arc::new(proto),
+			upvalues: vec![],
+			globals,
+			name: None,
+			source: self.source.clone(),
+		};
+
+		self.push_frame(Arc::new(closure), vec![], None)?;
+		self.execute()
+	}
+
+	/// Calls a compiled closure from outside of the bytecode backend,
+	/// such as from a builtin function.
+	pub(crate) fn call_compiled(
+		&mut self,
+		closure: Arc<Closure>,
+		arguments: Vec<Value>,
+		span: Span,
+	) -> Result<Value, RuntimeError> {
+		self.push_frame(closure, arguments, Some(span))?;
+
+		stacker::maybe_grow(
+			STACK_RED_ZONE,
+			STACK_SEGMENT_SIZE,
+			|| self.execute(),
+		)
+	}
+
+	/// Enters `closure` with `arguments` bound to its parameters. The
+	/// number of arguments must already be checked.
+	fn push_frame(
+		&mut self,
+		closure: Arc<Closure>,
+		arguments: Vec<Value>,
+		call: Option<Span>,
+	) -> Result<(), RuntimeError> {
+		if let Some(span) = call {
+			if self.depth >= self.max_call_depth {
+				Err(RuntimeError::StackOverflow {
+					depth: self.max_call_depth,
+					span,
+				})?
+			}
+
+			self.depth += 1;
+		}
+
+		let vm = &mut self.vm;
+		let locals = vm.locals.len();
+		let proto = &closure.proto;
+		vm.locals
+			.resize_with(locals + proto.slots as usize, || {
+				Slot::Local(None)
+			});
+
+		let mut arguments = arguments.into_iter();
+		for ((slot, span), value) in proto
+			.positional
+			.iter()
+			.zip(&mut arguments)
+		{
+			vm.locals[locals + *slot as usize] =
+				Slot::Local(Some(SymbolInfo {
+					mutable: false,
+					value,
+					declaration: Some(*span),
+				}));
+		}
+
+		if let Some((slot, span)) = proto.rest {
+			vm.locals[locals + slot as usize] = Slot::Local(Some(SymbolInfo {
+				mutable: false,
+				value: Value::Array(arguments.collect::<Vec<_>>().into()),
+				declaration: Some(span),
+			}));
+		}
+
+		vm.frames.push(Frame {
+			closure,
+			ip: 0,
+			locals,
+			stack: vm.stack.len(),
+			iterations: vm.iterations.len(),
+			call,
+		});
+
+		Ok(())
+	}
+
+	/// Runs the innermost frame until it returns.
+	///
+	/// On error, every frame entered since is dropped and the closure
+	/// calls among them are added to the stack trace, see
+	/// [`Interpreter::leave_module`].
+	fn execute(&mut self) -> Result<Value, RuntimeError> {
+		let entry = self.vm.frames.len() - 1;
+
+		let res = self.dispatch(entry);
+
+		res.map_err(|mut error| {
+			let frames = self.vm.frames.split_off(entry);
+			let outer = self
+				.vm
+				.frames
+				.last()
+				.and_then(|frame| frame.closure.source.clone());
+
+			self.vm.stack.truncate(frames[0].stack);
+			self.vm
+				.locals
+				.truncate(frames[0].locals);
+			self.vm
+				.iterations
+				.truncate(frames[0].iterations);
+
+			for (i, frame) in frames.iter().enumerate().rev() {
+				if let Some(span) = frame.call {
+					let caller = match i {
+						0 => outer.as_ref(),
+						_ => frames[i - 1].closure.source.as_ref(),
+					};
+
+					self.depth -= 1;
+					error = self.leave_module(
+						frame.closure.source.as_ref(),
+						caller,
+						error,
+						span,
+					);
+					self.push_trace(frame.closure.name.clone(), span);
+				}
+			}
+
+			error
+		})
+	}
+
+	fn dispatch(&mut self, entry: usize) -> Result<Value, RuntimeError> {
+		let frame = self.vm.frames.last().unwrap();
+		let mut closure = frame.closure.clone();
+		let mut base = frame.locals;
+
+		loop {
+			let frame = self.vm.frames.last_mut().unwrap();
+			let proto = &*closure.proto;
+			let op = proto.code[frame.ip].clone();
+			frame.ip += 1;
+
+			match op {
+				Op::Nil => self.vm.stack.push(Value::Nil),
+				Op::Constant(i) => {
+					self.vm
+						.stack
+						.push(proto.constants[i as usize].clone());
+				},
+				Op::Pop => {
+					self.vm.pop();
+				},
+				Op::Load { var, ident } => {
+					let value = self
+						.vm
+						.binding(var, &closure, base, |symbol| {
+							symbol
+								.as_ref()
+								.map(|symbol| symbol.value.clone())
+						})
+						.ok_or_else(|| {
+							undefined(&proto.idents[ident as usize])
+						})?;
+					self.vm.stack.push(value);
+				},
+				Op::Declare {
+					var,
+					ident,
+					mutable,
+				} => {
+					let value = self
+						.vm
+						.pop()
+						.named(&proto.idents[ident as usize]);
+					let symbol = SymbolInfo {
+						mutable,
+						value: value.clone(),
+						declaration: Some(proto.idents[ident as usize].span),
+					};
+
+					self.vm.binding(
+						var,
+						&closure,
+						base,
+						|slot| match slot {
+							Some(_) => Err(RuntimeError::AlreadyDeclared),
+							None => {
+								*slot = Some(symbol);
+								Ok(())
+							},
+						},
+					)?;
+					self.vm.stack.push(value);
+				},
+				Op::Assign { var, ident } => {
+					let value = self.vm.stack.last().unwrap().clone();
+
+					self.vm.binding(
+						var,
+						&closure,
+						base,
+						|slot| match slot {
+							None =>
+								Err(undefined(&proto.idents[ident as usize])),
+							Some(SymbolInfo {
+								mutable: false,
+								declaration,
+								..
+							}) => Err(
+								RuntimeError::AssignmentToImmutableVariable {
+									span: proto.idents[ident as usize].span,
+									declaration: *declaration,
+								},
+							),
+							Some(symbol) => {
+								symbol.value = value;
+								Ok(())
+							},
+						},
+					)?;
+				},
+				Op::Clear { start, len } => {
+					let start = base + start as usize;
+					self.vm.locals[start..start + len as usize]
+						.iter_mut()
+						.for_each(|slot| *slot = Slot::Local(None));
+				},
+				Op::Array(len) => {
+					let elements = self.vm.pop_n(len as usize);
+					self.vm
+						.stack
+						.push(Value::Array(elements.into()));
+				},
+				Op::Record { keys, len } => {
+					let values = self.vm.pop_n(len as usize);
+					let keys = &proto.idents[keys as usize..][..len as usize];
+					let fields = keys
+						.iter()
+						.cloned()
+						.zip(values)
+						.collect::<HashMap<_, _>>();
+					self.vm
+						.stack
+						.push(Value::Record(fields.into()));
+				},
+				Op::Template(i) => {
+					let fragments = &proto.templates[i as usize];
+					let values = self.vm.pop_n(fragments.len() - 1);
+
+					let mut buf = fragments[0].clone();
+					for (value, fragment) in values.iter().zip(&fragments[1..])
+					{
+						buf.push_str(&value.to_string());
+						buf.push_str(fragment);
+					}
+
+					self.vm.stack.push(Value::String(buf));
+				},
+				Op::Closure(i) => {
+					let proto = proto.protos[i as usize].clone();
+					let upvalues = proto
+						.captures
+						.iter()
+						.map(|capture| match capture {
+							Capture::Local(slot) =>
+								self.vm.capture(base + *slot as usize),
+							Capture::Upvalue(i) =>
+								closure.upvalues[*i as usize].clone(),
+						})
+						.collect();
+
+					self.vm.stack.push(Value::Function(
+						FunctionValue::Compiled(Arc::new(Closure {
+							proto,
+							upvalues,
+							globals: closure.globals.clone(),
+							name: None,
+							source: closure.source.clone(),
+						})),
+					));
+				},
+				Op::Unary { op, span } => {
+					let value = self.vm.pop();
+					let span = proto.spans[span as usize];
+					self.vm
+						.stack
+						.push(operator::unary(op, value, span)?);
+				},
+				Op::Binary { op, spans } => {
+					let rhs = self.vm.pop();
+					let lhs = self.vm.pop();
+					let spans = &proto.spans[spans as usize..];
+					self.vm.stack.push(operator::binary(
+						op, lhs, spans[0], rhs, spans[1],
+					)?);
+				},
+				Op::Range {
+					spans,
+					step,
+					inclusive,
+				} => {
+					let spans = &proto.spans[spans as usize..];
+					let step = step.then(|| (self.vm.pop(), spans[2]));
+					let end = self.vm.pop();
+					let start = self.vm.pop();
+					self.vm.stack.push(operator::range(
+						start, spans[0], end, spans[1], step, inclusive,
+					)?);
+				},
+				Op::ShortCircuit { op, target, span } => {
+					let lhs = self.vm.stack.last().unwrap();
+					let span = proto.spans[span as usize];
+
+					if operator::short_circuits(&op, lhs, span)? {
+						frame.ip = target as usize;
+					} else {
+						self.vm.pop();
+					}
+				},
+				Op::Logical { op, span } => {
+					let rhs = self.vm.pop();
+					let span = proto.spans[span as usize];
+					self.vm
+						.stack
+						.push(operator::logical(op, rhs, span)?);
+				},
+				Op::Prop { ident, span } => {
+					let value = self.vm.pop();
+					let prop = proto.idents[ident as usize].clone();
+					let span = proto.spans[span as usize];
+					self.vm
+						.stack
+						.push(Accessor::Prop(prop).get(value, span)?);
+				},
+				Op::Index { spans } => {
+					let index = self.vm.pop();
+					let value = self.vm.pop();
+					let spans = &proto.spans[spans as usize..];
+					self.vm.stack.push(place::index(
+						value, spans[0], index, spans[1],
+					)?);
+				},
+				Op::CheckIndex { span } => {
+					let index = self.vm.stack.last().unwrap().clone();
+					to_index(index, proto.spans[span as usize])?;
+				},
+				Op::LoadPlace(i) => {
+					let place = &proto.places[i as usize];
+					let indices = place.indices();
+					let indices =
+						self.vm.stack[self.vm.stack.len() - indices..].to_vec();
+
+					let root = self
+						.vm
+						.binding(place.root, &closure, base, |symbol| {
+							symbol
+								.as_ref()
+								.map(|symbol| symbol.value.clone())
+						})
+						.ok_or_else(|| {
+							undefined(&proto.idents[place.ident as usize])
+						})?;
+
+					let value = accessors(&place.steps, indices)
+						.try_fold(root, |value, (accessor, span)| {
+							accessor.get(value, span)
+						})?;
+					self.vm.stack.push(value);
+				},
+				Op::StorePlace(i) => {
+					let place = &proto.places[i as usize];
+					let value = self.vm.pop();
+					let indices = self.vm.pop_n(place.indices());
+
+					let root = self
+						.vm
+						.binding(place.root, &closure, base, |symbol| {
+							symbol.as_ref().map(|symbol| {
+								match symbol.mutable {
+									true => Ok(symbol.value.clone()),
+									false => Err(
+										RuntimeError::AssignmentToImmutableVariable {
+											span: place.span,
+											declaration: symbol.declaration,
+										},
+									),
+								}
+							})
+						})
+						.ok_or_else(|| {
+							undefined(&proto.idents[place.ident as usize])
+						})??;
+
+					let mut accessors = accessors(&place.steps, indices);
+					let (accessor, span) = accessors.next_back().unwrap();
+					let target = accessors
+						.try_fold(root, |value, (accessor, span)| {
+							accessor.get(value, span)
+						})?;
+					accessor.set(target, span, value.clone())?;
+
+					self.vm.stack.push(value);
+				},
+				Op::Call { argc, spans } => {
+					let callee = self.vm.pop();
+					let arguments = self.vm.pop_n(argc as usize);
+					let spans = &proto.spans[spans as usize..];
+					let span = spans[0];
+
+					match callee {
+						Value::Function(FunctionValue::Compiled(callee)) => {
+							let arity = callee.proto.arity;
+
+							if !arity.accepts(arguments.len()) {
+								Err(RuntimeError::ArgumentCount {
+									expected: arity,
+									found: arguments.len(),
+									span,
+									definition: Some(callee.proto.parameters),
+								})?
+							}
+
+							self.push_frame(callee, arguments, Some(span))?;
+
+							let frame = self.vm.frames.last().unwrap();
+							closure = frame.closure.clone();
+							base = frame.locals;
+						},
+						Value::Function(callee) => {
+							let arguments = arguments
+								.into_iter()
+								.zip(spans[2..].iter().copied())
+								.collect();
+							let value = self
+								.call(callee, arguments, span)
+								.map_err(Unwind::into_error)?;
+							self.vm.stack.push(value);
+						},
+						value => Err(TypeError::NotCallable {
+							value,
+							span: spans[1],
+						})?,
+					}
+				},
+				Op::Jump(target) => frame.ip = target as usize,
+				Op::JumpUnless { target, span } => match self.vm.pop() {
+					Value::Boolean(true) => {},
+					Value::Boolean(false) =>
+						self.vm.frames.last_mut().unwrap().ip = target as usize,
+					value => Err(TypeError::NonBooleanCondition {
+						value,
+						span: proto.spans[span as usize],
+					})?,
+				},
+				Op::IterStart { span } => {
+					let collection = self.vm.pop();
+					let Some(items) = collection.items() else {
+						Err(TypeError::NotIterable {
+							value: collection,
+							span: proto.spans[span as usize],
+						})?
+					};
+
+					self.vm.iterations.push(Iteration {
+						items,
+						stack: self.vm.stack.len(),
+					});
+				},
+				Op::IterNext { exit } => {
+					let iteration = self.vm.iterations.last_mut().unwrap();
+
+					match iteration.items.next() {
+						Some(item) => self.vm.stack.push(item),
+						None => {
+							self.vm.iterations.pop();
+							self.vm.stack.push(Value::Nil);
+							self.vm.frames.last_mut().unwrap().ip =
+								exit as usize;
+						},
+					}
+				},
+				Op::Break(target) => {
+					let value = self.vm.pop();
+					let iteration = self.vm.iterations.pop().unwrap();
+					self.vm.stack.truncate(iteration.stack);
+					self.vm.stack.push(value);
+					self.vm.frames.last_mut().unwrap().ip = target as usize;
+				},
+				Op::Continue(target) => {
+					let iteration = self.vm.iterations.last().unwrap();
+					self.vm.stack.truncate(iteration.stack);
+					self.vm.frames.last_mut().unwrap().ip = target as usize;
+				},
+				Op::Default { var, skip } => {
+					let given =
+						self.vm
+							.binding(var, &closure, base, |symbol| {
+								symbol.is_some()
+							});
+
+					if given {
+						self.vm.frames.last_mut().unwrap().ip = skip as usize;
+					}
+				},
+				Op::Return => {
+					let value = self.vm.pop();
+					let frame = self.vm.frames.pop().unwrap();
+
+					self.vm.stack.truncate(frame.stack);
+					self.vm.locals.truncate(frame.locals);
+					self.vm
+						.iterations
+						.truncate(frame.iterations);
+
+					if frame.call.is_some() {
+						self.depth -= 1;
+					}
+
+					if self.vm.frames.len() == entry {
+						return Ok(value);
+					}
+
+					self.vm.stack.push(value);
+
+					let frame = self.vm.frames.last().unwrap();
+					closure = frame.closure.clone();
+					base = frame.locals;
+				},
+				Op::Match { pattern, fail } => {
+					let pattern = &proto.patterns[pattern as usize];
+					let value = self.vm.stack.last().unwrap().clone();
+					let mut bindings = vec![];
+
+					if destructure(&pattern.pattern, value, &mut bindings)
+						.is_err()
+					{
+						frame.ip = fail as usize;
+						continue;
+					}
+
+					self.vm
+						.bind(pattern, bindings, &closure, base)?;
+				},
+				Op::Destructure(pattern) => {
+					let pattern = &proto.patterns[pattern as usize];
+					let value = self.vm.stack.last().unwrap().clone();
+					let mut bindings = vec![];
+
+					destructure(&pattern.pattern, value, &mut bindings)
+						.map_err(|(span, value)| {
+							RuntimeError::PatternMismatch { value, span }
+						})?;
+
+					self.vm
+						.bind(pattern, bindings, &closure, base)?;
+				},
+				Op::NoMatch { span } => Err(RuntimeError::NoMatch {
+					value: self.vm.pop(),
+					span: proto.spans[span as usize],
+				})?,
+				Op::Argument(slot) => {
+					let value = self.vm.binding(
+						Var::Local(slot),
+						&closure,
+						base,
+						|symbol| symbol.as_ref().unwrap().value.clone(),
+					);
+					self.vm.stack.push(value);
+				},
+				Op::Panic { span } => Err(RuntimeError::Panic {
+					message: self.vm.pop(),
+					span: proto.spans[span as usize],
+				})?,
+				Op::Assert { span } => match self.vm.pop() {
+					Value::Boolean(true) => self.vm.stack.push(Value::Nil),
+					value => Err(RuntimeError::AssertionFailed {
+						value,
+						span: proto.spans[span as usize],
+					})?,
+				},
+				Op::Fail { failure, span } => {
+					let span = proto.spans[span as usize];
+
+					Err(match failure {
+						Failure::ReturnOutsideFunction =>
+							RuntimeError::ReturnOutsideFunction { span },
+						Failure::BreakOutsideLoop =>
+							RuntimeError::BreakOutsideLoop { span },
+						Failure::ContinueOutsideLoop =>
+							RuntimeError::ContinueOutsideLoop { span },
+						Failure::ExportOutsideTopLevel =>
+							RuntimeError::ExportOutsideTopLevel { span },
+						Failure::AlreadyDeclared =>
+							RuntimeError::AlreadyDeclared,
+					})?
+				},
+				Op::Use(i) => {
+					let u = &proto.uses[i as usize];
+					let (path, exports) = self.load_module(&u.path)?;
+
+					let imports = u
+						.imports
+						.iter()
+						.map(|ident| match exports.get(ident) {
+							Some(value) => Ok(value),
+							None => Err(ModuleError::MissingExport {
+								ident: ident.clone(),
+								path: path.clone(),
+							}),
+						})
+						.collect::<Result<Vec<_>, _>>()?;
+
+					self.vm
+						.stack
+						.push(Value::Record(exports));
+					self.vm
+						.stack
+						.extend(imports.into_iter().rev());
+				},
+				Op::Export(ident) => self
+					.exports
+					.push(proto.idents[ident as usize].clone()),
+			}
+		}
+	}
+}
+
+/// Pairs the steps of a place with the indices they take from the
+/// stack.
+fn accessors(
+	steps: &[(Step, Span)],
+	indices: Vec<Value>,
+) -> impl DoubleEndedIterator<Item = (Accessor, Span)> + '_ {
+	let mut indices = indices.into_iter();
+
+	steps
+		.iter()
+		.map(move |(step, span)| {
+			let accessor = match step {
+				Step::Prop(prop) => Accessor::Prop(prop.clone()),
+				Step::Index(index_span) => {
+					let index = indices.next().unwrap();
+					let index = to_index(index, *index_span)
+						.expect("indices are checked when evaluated");
+					Accessor::Index(index, *index_span)
+				},
+			};
+
+			(accessor, *span)
+		})
+		.collect::<Vec<_>>()
+		.into_iter()
+}
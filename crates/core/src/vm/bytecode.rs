@@ -0,0 +1,284 @@
+use std::{
+	fmt::{self, Debug, Formatter},
+	sync::Arc,
+};
+
+use dyn_parser::ast::{self, BinExprKind, Ident, UnaryExprKind, Use};
+use dyn_span::Span;
+
+use crate::{Arity, Value};
+
+/// Where a variable lives, resolved at compile time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Var {
+	/// Slot in the current call frame.
+	Local(u32),
+	/// Variable captured by the current closure.
+	Upvalue(u32),
+	/// Top-level binding, looked up in the closure's globals.
+	Global(u32),
+}
+
+/// How a closure captures a variable when it is created.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Capture {
+	/// Slot in the frame creating the closure.
+	Local(u32),
+	/// Variable that the creating closure itself captured.
+	Upvalue(u32),
+}
+
+/// Control flow that is only an error where it appears.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Failure {
+	ReturnOutsideFunction,
+	BreakOutsideLoop,
+	ContinueOutsideLoop,
+	ExportOutsideTopLevel,
+	AlreadyDeclared,
+}
+
+/// A single instruction.
+///
+/// Operands index into the tables of the [`Proto`] being executed, so
+/// that every instruction stays small.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op {
+	Nil,
+	Constant(u32),
+	Pop,
+	Load {
+		var: Var,
+		ident: u32,
+	},
+	/// Declares the value on top of the stack, leaving it there.
+	Declare {
+		var: Var,
+		ident: u32,
+		mutable: bool,
+	},
+	/// Assigns the value on top of the stack, leaving it there.
+	Assign {
+		var: Var,
+		ident: u32,
+	},
+	/// Forgets the variables of a scope that is entered again.
+	Clear {
+		start: u32,
+		len: u32,
+	},
+	Array(u32),
+	Record {
+		keys: u32,
+		len: u32,
+	},
+	Template(u32),
+	Closure(u32),
+	Unary {
+		op: UnaryExprKind,
+		span: u32,
+	},
+	/// Applies a binary operator to operands evaluated from `spans`
+	/// and the span right after it.
+	Binary {
+		op: BinExprKind,
+		spans: u32,
+	},
+	/// Makes a range of bounds evaluated from `spans` and the span
+	/// right after it, followed by the step if there is one.
+	Range {
+		spans: u32,
+		step: bool,
+		inclusive: bool,
+	},
+	/// Jumps to `target` if the value on top of the stack decides the
+	/// result of a logical operator, keeping it, and pops it
+	/// otherwise.
+	ShortCircuit {
+		op: BinExprKind,
+		target: u32,
+		span: u32,
+	},
+	/// Checks the right operand of a logical operator, which is left
+	/// on the stack as its result.
+	Logical {
+		op: BinExprKind,
+		span: u32,
+	},
+	Prop {
+		ident: u32,
+		span: u32,
+	},
+	/// Indexes a value evaluated from `spans` with an index evaluated
+	/// from the span right after it.
+	Index {
+		spans: u32,
+	},
+	/// Checks that the value on top of the stack can be used as an
+	/// index into a place.
+	CheckIndex {
+		span: u32,
+	},
+	/// Reads the current value of a place, leaving its indices on the
+	/// stack.
+	LoadPlace(u32),
+	/// Assigns the value on top of the stack to a place, consuming
+	/// its indices.
+	StorePlace(u32),
+	/// Calls the callee on top of the stack with `argc` arguments
+	/// below it. `spans` points at the spans of the call, the callee
+	/// and each argument, in that order.
+	Call {
+		argc: u32,
+		spans: u32,
+	},
+	Jump(u32),
+	/// Pops a condition and jumps to `target` if it is `false`.
+	JumpUnless {
+		target: u32,
+		span: u32,
+	},
+	/// Pops an array and starts iterating over a snapshot of it.
+	IterStart {
+		span: u32,
+	},
+	/// Pushes the next item, or ends the innermost iteration, pushes
+	/// `nil` and jumps to `exit`.
+	IterNext {
+		exit: u32,
+	},
+	/// Ends the innermost iteration with the value on top of the
+	/// stack.
+	Break(u32),
+	/// Skips to the next item of the innermost iteration.
+	Continue(u32),
+	/// Declares the bindings of a pattern if the value on top of the
+	/// stack matches it, keeping the value, and jumps to `fail`
+	/// otherwise.
+	Match {
+		pattern: u32,
+		fail: u32,
+	},
+	/// Declares the bindings of a pattern in the value on top of the
+	/// stack, keeping the value, and fails if it doesn't match.
+	Destructure(u32),
+	/// Pops a value that no arm of a `match` matched.
+	NoMatch {
+		span: u32,
+	},
+	/// Pushes the argument given for a parameter with a pattern,
+	/// which has a slot of its own.
+	Argument(u32),
+	/// Skips evaluating a default if the parameter or field it is
+	/// for has been declared already.
+	Default {
+		var: Var,
+		skip: u32,
+	},
+	Return,
+	Panic {
+		span: u32,
+	},
+	Assert {
+		span: u32,
+	},
+	Fail {
+		failure: Failure,
+		span: u32,
+	},
+	Use(u32),
+	Export(u32),
+}
+
+/// One step into a value when assigning to a place.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+	Prop(Ident),
+	/// Index taken from the stack, evaluated from the span given.
+	Index(Span),
+}
+
+/// An assignment target more complex than a single variable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Place {
+	pub root: Var,
+	pub ident: u32,
+	pub span: Span,
+	/// Steps from the root variable to the assigned field, each with
+	/// the span of the expression it is applied to.
+	pub steps: Vec<(Step, Span)>,
+}
+
+impl Place {
+	pub fn indices(&self) -> usize {
+		self.steps
+			.iter()
+			.filter(|(step, _)| matches!(step, Step::Index(_)))
+			.count()
+	}
+}
+
+/// A pattern along with where each of its bindings lives, in the
+/// order they are bound.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+	pub pattern: ast::Pattern,
+	pub vars: Vec<(Ident, Var)>,
+	pub mutable: bool,
+}
+
+/// A compiled function, or the top level of a script.
+#[derive(PartialEq)]
+pub struct Proto {
+	pub code: Vec<Op>,
+	pub constants: Vec<Value>,
+	pub idents: Vec<Ident>,
+	pub spans: Vec<Span>,
+	pub templates: Vec<Vec<String>>,
+	pub places: Vec<Place>,
+	pub patterns: Vec<Pattern>,
+	pub uses: Vec<Use>,
+	pub protos: Vec<Arc<Proto>>,
+	pub captures: Vec<Capture>,
+	/// Number of local slots a call frame needs.
+	pub slots: u32,
+	pub arity: Arity,
+	/// Span of the parameter list, for arity errors.
+	pub parameters: Span,
+	/// Slots of the positional parameters, in order, with the spans
+	/// that declare them.
+	pub positional: Vec<(u32, Span)>,
+	pub rest: Option<(u32, Span)>,
+}
+
+impl Proto {
+	pub fn new(arity: Arity, parameters: Span) -> Self {
+		Self {
+			code: vec![],
+			constants: vec![],
+			idents: vec![],
+			spans: vec![],
+			templates: vec![],
+			places: vec![],
+			patterns: vec![],
+			uses: vec![],
+			protos: vec![],
+			captures: vec![],
+			slots: 0,
+			arity,
+			parameters,
+			positional: vec![],
+			rest: None,
+		}
+	}
+}
+
+impl Debug for Proto {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Proto")
+			.field("arity", &self.arity)
+			.field("slots", &self.slots)
+			.field("code", &self.code)
+			.finish_non_exhaustive()
+	}
+}
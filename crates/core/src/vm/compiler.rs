@@ -0,0 +1,998 @@
+use std::{collections::HashMap, sync::Arc};
+
+use dyn_parser::ast::{
+	self, BinExprKind, Code, Else, Expr, ExprKind, Function, Ident, Literal,
+	MatchArm, Parameters, PatternKind, PlaceKind,
+};
+use dyn_span::{HasSpan, Span};
+
+use super::{
+	bytecode::{Capture, Failure, Op, Pattern, Place, Proto, Step, Var},
+	Globals,
+};
+use crate::{closure_arity, Arity, Value};
+
+/// Variables of one scope.
+enum Scope {
+	/// The top level of a script, whose variables are globals.
+	Global,
+	Local {
+		/// Slot of every variable declared anywhere in the scope, and
+		/// whether its declaration has been compiled yet.
+		slots: HashMap<String, (u32, bool)>,
+		start: u32,
+	},
+}
+
+struct Loop {
+	head: u32,
+	/// `Break`s to patch once the end of the loop is known.
+	breaks: Vec<usize>,
+}
+
+/// A function being compiled.
+struct Frame {
+	proto: Proto,
+	scopes: Vec<Scope>,
+	loops: Vec<Loop>,
+	next_slot: u32,
+}
+
+impl Frame {
+	fn new(proto: Proto, scopes: Vec<Scope>) -> Self {
+		Self {
+			proto,
+			scopes,
+			loops: vec![],
+			next_slot: 0,
+		}
+	}
+
+	fn capture(&mut self, capture: Capture) -> u32 {
+		let captures = &mut self.proto.captures;
+
+		match captures
+			.iter()
+			.position(|c| *c == capture)
+		{
+			Some(i) => i as u32,
+			None => {
+				captures.push(capture);
+				captures.len() as u32 - 1
+			},
+		}
+	}
+}
+
+/// Compiles syntax trees into [`Proto`]s.
+///
+/// Variables are resolved while compiling. A use refers to the
+/// nearest declaration compiled before it in the same function, so
+/// that `let x = x + 1` can shadow an outer `x`. Closures run later,
+/// so they also see declarations that come after them in enclosing
+/// functions, which is what makes recursive closures work.
+pub struct Compiler<'a> {
+	globals: &'a mut Globals,
+	frames: Vec<Frame>,
+}
+
+impl<'a> Compiler<'a> {
+	/// Compiles the top level of a script, whose top-level variables
+	/// live in `globals`.
+	pub fn compile(code: &Code, globals: &'a mut Globals) -> Proto {
+		let proto = Proto::new(Arity::exactly(0), Span::DUMMY_SPAN);
+		let mut compiler = Self {
+			globals,
+			frames: vec![Frame::new(proto, vec![Scope::Global])],
+		};
+
+		compiler.code(code);
+		compiler.emit(Op::Return);
+
+		compiler.frames.pop().unwrap().proto
+	}
+
+	fn frame(&mut self) -> &mut Frame { self.frames.last_mut().unwrap() }
+
+	fn proto(&mut self) -> &mut Proto { &mut self.frame().proto }
+
+	fn here(&mut self) -> u32 { self.proto().code.len() as u32 }
+
+	fn emit(&mut self, op: Op) -> usize {
+		let code = &mut self.proto().code;
+		code.push(op);
+		code.len() - 1
+	}
+
+	/// Points the jump at `at` to the next instruction.
+	fn patch(&mut self, at: usize) {
+		let here = self.here();
+
+		match &mut self.proto().code[at] {
+			Op::Jump(target)
+			| Op::JumpUnless { target, .. }
+			| Op::ShortCircuit { target, .. }
+			| Op::IterNext { exit: target }
+			| Op::Break(target)
+			| Op::Match { fail: target, .. }
+			| Op::Default { skip: target, .. } => *target = here,
+			op => unreachable!("{op:?} does not jump"),
+		}
+	}
+
+	fn constant(&mut self, value: Value) -> u32 {
+		let constants = &mut self.proto().constants;
+		constants.push(value);
+		constants.len() as u32 - 1
+	}
+
+	fn ident(&mut self, ident: &Ident) -> u32 {
+		let idents = &mut self.proto().idents;
+		idents.push(ident.clone());
+		idents.len() as u32 - 1
+	}
+
+	fn span(&mut self, span: Span) -> u32 {
+		let spans = &mut self.proto().spans;
+		spans.push(span);
+		spans.len() as u32 - 1
+	}
+
+	/// Enters a scope declaring `idents`, returning the range of
+	/// slots reserved for them.
+	fn push_scope<'e, I>(&mut self, idents: I) -> (u32, u32)
+	where I: IntoIterator<Item = &'e Ident> {
+		let frame = self.frame();
+		let start = frame.next_slot;
+		let mut slots = HashMap::new();
+
+		for ident in idents {
+			slots
+				.entry(ident.symbol.clone())
+				.or_insert_with(|| {
+					frame.next_slot += 1;
+					(frame.next_slot - 1, false)
+				});
+		}
+
+		frame.proto.slots = frame.proto.slots.max(frame.next_slot);
+		frame
+			.scopes
+			.push(Scope::Local { slots, start });
+
+		(start, frame.next_slot - start)
+	}
+
+	fn pop_scope(&mut self) {
+		let frame = self.frame();
+
+		if let Some(Scope::Local { start, .. }) = frame.scopes.pop() {
+			frame.next_slot = start;
+		}
+	}
+
+	/// Forgets the variables of a scope that may have been entered
+	/// before, such as the body of a loop.
+	fn clear(&mut self, (start, len): (u32, u32)) {
+		if len > 0 {
+			self.emit(Op::Clear { start, len });
+		}
+	}
+
+	/// Marks `ident` as declared in the innermost scope, returning
+	/// where it lives.
+	fn declared(&mut self, ident: &Ident) -> Var { self.innermost(ident, true) }
+
+	/// Finds where `ident` lives in the innermost scope, marking it
+	/// as declared if `declare` is set.
+	fn innermost(&mut self, ident: &Ident, declare: bool) -> Var {
+		let frame = self.frames.last_mut().unwrap();
+
+		match frame.scopes.last_mut().unwrap() {
+			Scope::Global => Var::Global(self.globals.index(&ident.symbol)),
+			Scope::Local { slots, .. } => {
+				let (slot, declared) = slots
+					.get_mut(&ident.symbol)
+					.expect("declarations are collected before the scope");
+				*declared |= declare;
+				Var::Local(*slot)
+			},
+		}
+	}
+
+	/// Declares the value on top of the stack as `ident`.
+	fn declare(&mut self, ident: &Ident, mutable: bool) {
+		let var = self.declared(ident);
+		let ident = self.ident(ident);
+		self.emit(Op::Declare {
+			var,
+			ident,
+			mutable,
+		});
+	}
+
+	/// Adds `pattern` to the current proto along with where its
+	/// bindings live, returning its index.
+	///
+	/// Bindings with a default are only marked as declared once
+	/// their default is compiled, so that it sees the variables they
+	/// shadow.
+	fn pattern(&mut self, pattern: &ast::Pattern, mutable: bool) -> u32 {
+		let defaulted = pattern
+			.defaults()
+			.into_iter()
+			.map(|(ident, _)| ident)
+			.collect::<Vec<_>>();
+		let vars = pattern
+			.bindings()
+			.into_iter()
+			.map(|ident| {
+				let declare = !defaulted.contains(&ident);
+				(
+					ident.clone(),
+					self.innermost(ident, declare),
+				)
+			})
+			.collect();
+
+		let patterns = &mut self.proto().patterns;
+		patterns.push(Pattern {
+			pattern: pattern.clone(),
+			vars,
+			mutable,
+		});
+		patterns.len() as u32 - 1
+	}
+
+	/// Compiles the defaults of a pattern that matched, each skipped
+	/// if its field was there.
+	fn defaults(&mut self, pattern: &ast::Pattern, mutable: bool) {
+		for (ident, default) in pattern.defaults() {
+			let var = self.innermost(ident, false);
+			let skip = self.emit(Op::Default { var, skip: 0 });
+			self.expr(default);
+			self.declare(ident, mutable);
+			self.emit(Op::Pop);
+			self.patch(skip);
+		}
+	}
+
+	/// Declares the bindings of `pattern` in the value on top of the
+	/// stack, leaving it there.
+	fn destructure(&mut self, pattern: &ast::Pattern, mutable: bool) {
+		if let PatternKind::Binding(ident) = &pattern.kind {
+			self.declare(ident, mutable);
+			return;
+		}
+
+		let index = self.pattern(pattern, mutable);
+		self.emit(Op::Destructure(index));
+		self.defaults(pattern, mutable);
+	}
+
+	fn resolve(&mut self, ident: &Ident) -> Var {
+		let name = &ident.symbol;
+		let f = self.frames.len() - 1;
+
+		for scope in self.frames[f].scopes.iter().rev() {
+			match scope {
+				Scope::Global => return Var::Global(self.globals.index(name)),
+				Scope::Local { slots, .. } => {
+					if let Some((slot, true)) = slots.get(name) {
+						return Var::Local(*slot);
+					}
+				},
+			}
+		}
+
+		match self.capture(f, name) {
+			Some(i) => Var::Upvalue(i),
+			None => Var::Global(self.globals.index(name)),
+		}
+	}
+
+	/// Captures `name` from the functions enclosing the `f`th one,
+	/// returning its index among that function's upvalues.
+	fn capture(&mut self, f: usize, name: &str) -> Option<u32> {
+		let parent = f.checked_sub(1)?;
+		let mut slot = None;
+
+		for scope in self.frames[parent].scopes.iter().rev() {
+			match scope {
+				Scope::Global => return None,
+				Scope::Local { slots, .. } => {
+					if let Some((i, _)) = slots.get(name) {
+						slot = Some(*i);
+						break;
+					}
+				},
+			}
+		}
+
+		let capture = match slot {
+			Some(slot) => Capture::Local(slot),
+			None => Capture::Upvalue(self.capture(parent, name)?),
+		};
+
+		Some(self.frames[f].capture(capture))
+	}
+
+	fn code(&mut self, code: &Code) {
+		let Some((last, stmts)) = code.stmts.split_last() else {
+			self.emit(Op::Nil);
+			return;
+		};
+
+		for stmt in stmts {
+			self.expr(stmt);
+			self.emit(Op::Pop);
+		}
+
+		self.expr(last);
+	}
+
+	fn block(&mut self, code: &Code) {
+		let mut idents = vec![];
+		all_declarations(&code.stmts, &mut idents);
+
+		let slots = self.push_scope(idents);
+		self.clear(slots);
+		self.code(code);
+		self.pop_scope();
+	}
+
+	fn expr(&mut self, expr: &Expr) {
+		match &expr.kind {
+			ExprKind::Literal(Literal::Nil(_)) => {
+				self.emit(Op::Nil);
+			},
+			ExprKind::Literal(i) => {
+				let constant = self.constant(Value::from_literal(i.clone()));
+				self.emit(Op::Constant(constant));
+			},
+			ExprKind::Ident(ident) => {
+				let var = self.resolve(ident);
+				let ident = self.ident(ident);
+				self.emit(Op::Load { var, ident });
+			},
+			ExprKind::TemplateString(i) => {
+				i.values
+					.iter()
+					.for_each(|value| self.expr(value));
+
+				let templates = &mut self.proto().templates;
+				templates.push(i.fragments.clone());
+				let template = templates.len() as u32 - 1;
+				self.emit(Op::Template(template));
+			},
+			ExprKind::Array(i) => {
+				i.elements
+					.iter()
+					.for_each(|element| self.expr(element));
+				self.emit(Op::Array(i.elements.len() as u32));
+			},
+			ExprKind::Record(i) => {
+				i.fields
+					.iter()
+					.for_each(|(_, value)| self.expr(value));
+
+				let keys = self.proto().idents.len() as u32;
+				for (key, _) in &i.fields {
+					self.ident(key);
+				}
+				self.emit(Op::Record {
+					keys,
+					len: i.fields.len() as u32,
+				});
+			},
+			ExprKind::Function(f) => {
+				let proto = self.function(f);
+				self.emit(Op::Closure(proto));
+			},
+			ExprKind::Call(callee, arguments) => {
+				arguments
+					.0
+					.iter()
+					.for_each(|argument| self.expr(argument));
+				self.expr(callee);
+
+				let spans = self.span(expr.span);
+				self.span(callee.span());
+				for argument in &arguments.0 {
+					self.span(argument.span());
+				}
+				self.emit(Op::Call {
+					argc: arguments.0.len() as u32,
+					spans,
+				});
+			},
+			ExprKind::Prop(i, prop) => {
+				self.expr(i);
+
+				let span = self.span(i.span());
+				let ident = self.ident(prop);
+				self.emit(Op::Prop { ident, span });
+			},
+			ExprKind::Index(i, j) => {
+				self.expr(i);
+				self.expr(j);
+
+				let spans = self.span(i.span());
+				self.span(j.span());
+				self.emit(Op::Index { spans });
+			},
+			ExprKind::UnaryExpr(i) => {
+				self.expr(&i.expr);
+
+				let span = self.span(i.span());
+				self.emit(Op::Unary { op: i.op, span });
+			},
+			ExprKind::BinExpr(i) if i.op.is_logical() => {
+				self.expr(&i.lhs);
+				let span = self.span(i.lhs.span());
+				let end = self.emit(Op::ShortCircuit {
+					op: i.op.clone(),
+					target: 0,
+					span,
+				});
+
+				self.expr(&i.rhs);
+				let span = self.span(i.rhs.span());
+				self.emit(Op::Logical {
+					op: i.op.clone(),
+					span,
+				});
+				self.patch(end);
+			},
+			ExprKind::BinExpr(i) => {
+				self.expr(&i.lhs);
+				self.expr(&i.rhs);
+				self.binary(i.op.clone(), i.lhs.span(), i.rhs.span());
+			},
+			ExprKind::Range {
+				start,
+				end,
+				step,
+				inclusive,
+			} => {
+				self.expr(start);
+				self.expr(end);
+				if let Some(step) = step {
+					self.expr(step);
+				}
+
+				let spans = self.span(start.span());
+				self.span(end.span());
+				if let Some(step) = step {
+					self.span(step.span());
+				}
+				self.emit(Op::Range {
+					spans,
+					step: step.is_some(),
+					inclusive: *inclusive,
+				});
+			},
+			ExprKind::Assign(place, value) => self.assign(place, None, value),
+			ExprKind::AddAssign(place, value) =>
+				self.assign(place, Some(BinExprKind::Add), value),
+			ExprKind::SubAssign(place, value) =>
+				self.assign(place, Some(BinExprKind::Sub), value),
+			ExprKind::MulAssign(place, value) =>
+				self.assign(place, Some(BinExprKind::Mul), value),
+			ExprKind::DivAssign(place, value) =>
+				self.assign(place, Some(BinExprKind::Div), value),
+			ExprKind::ModAssign(place, value) =>
+				self.assign(place, Some(BinExprKind::Mod), value),
+			ExprKind::Block(code) => self.block(code),
+			ExprKind::Declare(ident, value) => {
+				self.expr(value);
+				self.declare(ident, false);
+			},
+			ExprKind::DeclareMut(ident, value) => {
+				self.expr(value);
+				self.declare(ident, true);
+			},
+			ExprKind::Destructure {
+				pattern,
+				value,
+				mutable,
+			} => {
+				self.expr(value);
+				self.destructure(pattern, *mutable);
+			},
+			ExprKind::If { condition, yes, no } =>
+				self.conditional(condition, yes, no.as_ref()),
+			ExprKind::For {
+				collection,
+				item,
+				body,
+			} => {
+				self.expr(collection);
+				let span = self.span(collection.span());
+				self.emit(Op::IterStart { span });
+
+				let head = self.here();
+				let next = self.emit(Op::IterNext { exit: 0 });
+
+				let mut idents = vec![];
+				pattern_declarations(item, &mut idents);
+				all_declarations(&body.stmts, &mut idents);
+				let slots = self.push_scope(idents);
+				self.clear(slots);
+				self.destructure(item, true);
+				self.emit(Op::Pop);
+
+				self.frame().loops.push(Loop {
+					head,
+					breaks: vec![],
+				});
+				self.code(body);
+				self.emit(Op::Pop);
+				self.emit(Op::Jump(head));
+				let lp = self.frame().loops.pop().unwrap();
+				self.pop_scope();
+
+				self.patch(next);
+				for at in lp.breaks {
+					self.patch(at);
+				}
+			},
+			ExprKind::Match { scrutinee, arms } => {
+				self.expr(scrutinee);
+
+				let ends = arms
+					.iter()
+					.map(|arm| self.match_arm(arm))
+					.collect::<Vec<_>>();
+
+				let span = self.span(scrutinee.span());
+				self.emit(Op::NoMatch { span });
+				for at in ends {
+					self.patch(at);
+				}
+			},
+			ExprKind::Panic(message) => {
+				self.expr(message);
+				let span = self.span(expr.span);
+				self.emit(Op::Panic { span });
+			},
+			ExprKind::Assert(condition) => {
+				self.expr(condition);
+				let span = self.span(condition.span());
+				self.emit(Op::Assert { span });
+			},
+			ExprKind::Return(value) => {
+				self.expr(value);
+
+				if self.frames.len() > 1 {
+					self.emit(Op::Return);
+				} else {
+					self.fail(
+						Failure::ReturnOutsideFunction,
+						expr.span,
+					);
+				}
+			},
+			ExprKind::Break(value) => {
+				self.expr(value);
+
+				if self.frame().loops.is_empty() {
+					self.fail(Failure::BreakOutsideLoop, expr.span);
+				} else {
+					let at = self.emit(Op::Break(0));
+					self.frame()
+						.loops
+						.last_mut()
+						.unwrap()
+						.breaks
+						.push(at);
+				}
+			},
+			ExprKind::Continue(value) => {
+				self.expr(value);
+
+				match self.frame().loops.last() {
+					Some(lp) => {
+						let head = lp.head;
+						self.emit(Op::Continue(head));
+					},
+					None => self.fail(Failure::ContinueOutsideLoop, expr.span),
+				}
+			},
+			ExprKind::Use(u) => {
+				let uses = &mut self.proto().uses;
+				uses.push(u.clone());
+				let index = uses.len() as u32 - 1;
+				self.emit(Op::Use(index));
+
+				for ident in &u.imports {
+					self.declare(ident, false);
+					self.emit(Op::Pop);
+				}
+			},
+			ExprKind::Export(declaration) => {
+				let top_level = self.frames.len() == 1
+					&& matches!(
+						self.frame().scopes.last(),
+						Some(Scope::Global)
+					);
+
+				if !top_level {
+					self.fail(
+						Failure::ExportOutsideTopLevel,
+						expr.span,
+					);
+					return;
+				}
+
+				let ident = match &declaration.kind {
+					ExprKind::Declare(ident, _)
+					| ExprKind::DeclareMut(ident, _) => ident,
+					_ => unreachable!("only declarations can be exported"),
+				};
+
+				self.expr(declaration);
+				let ident = self.ident(ident);
+				self.emit(Op::Export(ident));
+			},
+		}
+	}
+
+	fn fail(&mut self, failure: Failure, span: Span) {
+		let span = self.span(span);
+		self.emit(Op::Fail { failure, span });
+	}
+
+	fn binary(&mut self, op: BinExprKind, lhs: Span, rhs: Span) {
+		let spans = self.span(lhs);
+		self.span(rhs);
+		self.emit(Op::Binary { op, spans });
+	}
+
+	fn conditional(&mut self, condition: &Expr, yes: &Code, no: Option<&Else>) {
+		self.expr(condition);
+		let span = self.span(condition.span());
+		let otherwise = self.emit(Op::JumpUnless { target: 0, span });
+
+		self.code(yes);
+		let end = self.emit(Op::Jump(0));
+
+		self.patch(otherwise);
+		match no {
+			Some(Else::Block(no)) => self.code(no),
+			Some(Else::If(no)) => self.expr(no),
+			None => {
+				self.emit(Op::Nil);
+			},
+		}
+
+		self.patch(end);
+	}
+
+	/// Compiles an arm of a `match` whose scrutinee is on top of the
+	/// stack, returning the jump to its end.
+	fn match_arm(&mut self, arm: &MatchArm) -> usize {
+		let mut idents = vec![];
+		pattern_declarations(&arm.pattern, &mut idents);
+		if let Some(guard) = &arm.guard {
+			declarations(guard, &mut idents);
+		}
+		declarations(&arm.body, &mut idents);
+
+		let slots = self.push_scope(idents);
+		self.clear(slots);
+
+		let pattern = self.pattern(&arm.pattern, false);
+		let fail = self.emit(Op::Match { pattern, fail: 0 });
+		self.defaults(&arm.pattern, false);
+
+		let unless = arm.guard.as_ref().map(|guard| {
+			self.expr(guard);
+			let span = self.span(guard.span());
+			self.emit(Op::JumpUnless { target: 0, span })
+		});
+
+		self.emit(Op::Pop);
+		self.expr(&arm.body);
+		let end = self.emit(Op::Jump(0));
+		self.pop_scope();
+
+		self.patch(fail);
+		if let Some(unless) = unless {
+			self.patch(unless);
+		}
+
+		end
+	}
+
+	/// Compiles the assignment of `value` to `place`, combined with
+	/// its current value through `op` for compound assignments.
+	fn assign(
+		&mut self,
+		place: &dyn_parser::ast::Place,
+		op: Option<BinExprKind>,
+		value: &Expr,
+	) {
+		let mut steps = vec![];
+		let root = self.place(place, &mut steps);
+		let var = self.resolve(root);
+		let ident = self.ident(root);
+
+		if steps.is_empty() {
+			if let Some(op) = op {
+				self.emit(Op::Load { var, ident });
+				self.expr(value);
+				self.binary(op, place.span(), value.span());
+			} else {
+				self.expr(value);
+			}
+
+			self.emit(Op::Assign { var, ident });
+			return;
+		}
+
+		let places = &mut self.proto().places;
+		places.push(Place {
+			root: var,
+			ident,
+			span: place.span(),
+			steps,
+		});
+		let index = places.len() as u32 - 1;
+
+		if let Some(op) = op {
+			self.emit(Op::LoadPlace(index));
+			self.expr(value);
+			self.binary(op, place.span(), value.span());
+		} else {
+			self.expr(value);
+		}
+
+		self.emit(Op::StorePlace(index));
+	}
+
+	/// Compiles the indices in `place` from the root outwards,
+	/// returning the variable it belongs to.
+	fn place<'p>(
+		&mut self,
+		place: &'p dyn_parser::ast::Place,
+		steps: &mut Vec<(Step, Span)>,
+	) -> &'p Ident {
+		match &place.kind {
+			PlaceKind::Ident(ident) => ident,
+			PlaceKind::Prop(i, prop) => {
+				let root = self.place(i, steps);
+				steps.push((Step::Prop(prop.clone()), i.span()));
+				root
+			},
+			PlaceKind::Index(i, j) => {
+				let root = self.place(i, steps);
+				self.expr(j);
+				let span = self.span(j.span());
+				self.emit(Op::CheckIndex { span });
+				steps.push((Step::Index(j.span()), i.span()));
+				root
+			},
+		}
+	}
+
+	/// Compiles `function` into a nested [`Proto`] of the current
+	/// one, returning its index.
+	fn function(&mut self, function: &Function) -> u32 {
+		let Function { parameters, body } = function;
+		let Parameters {
+			positional, rest, ..
+		} = parameters;
+
+		let mut idents = positional
+			.iter()
+			.flat_map(|parameter| parameter.pattern.bindings())
+			.chain(rest)
+			.collect::<Vec<_>>();
+		let duplicate =
+			(1..idents.len()).any(|i| idents[..i].contains(&idents[i]));
+		for parameter in positional {
+			for (_, default) in parameter.pattern.defaults() {
+				declarations(default, &mut idents);
+			}
+			if let Some(default) = &parameter.default {
+				declarations(default, &mut idents);
+			}
+		}
+		declarations(body, &mut idents);
+
+		let proto = Proto::new(
+			closure_arity(parameters),
+			parameters.span,
+		);
+		self.frames
+			.push(Frame::new(proto, vec![]));
+		self.push_scope(idents);
+
+		if duplicate {
+			// NOTE: the tree walker only notices when declaring the
+			// second parameter, which is on every call
+			let span = self.span(parameters.span);
+			self.emit(Op::Fail {
+				failure: Failure::AlreadyDeclared,
+				span,
+			});
+		}
+
+		for parameter in positional {
+			let ident = match &parameter.pattern.kind {
+				PatternKind::Binding(ident) => Some(ident),
+				_ => None,
+			};
+
+			let slot = match ident {
+				Some(ident) if parameter.default.is_none() => {
+					let Var::Local(slot) = self.declared(ident) else {
+						unreachable!("parameters are local")
+					};
+					slot
+				},
+				Some(ident) => {
+					let Var::Local(slot) = self.innermost(ident, false) else {
+						unreachable!("parameters are local")
+					};
+					slot
+				},
+				// the argument gets a slot of its own to destructure
+				None => {
+					let frame = self.frame();
+					frame.next_slot += 1;
+					frame.proto.slots = frame.proto.slots.max(frame.next_slot);
+					frame.next_slot - 1
+				},
+			};
+			self.proto()
+				.positional
+				.push((slot, parameter.pattern.span));
+
+			match (ident, &parameter.default) {
+				(Some(_), None) => {},
+				(Some(ident), Some(default)) => {
+					let var = Var::Local(slot);
+					let skip = self.emit(Op::Default { var, skip: 0 });
+					self.expr(default);
+					self.declare(ident, false);
+					self.emit(Op::Pop);
+					self.patch(skip);
+				},
+				(None, None) => {
+					self.emit(Op::Argument(slot));
+					self.destructure(&parameter.pattern, false);
+					self.emit(Op::Pop);
+				},
+				(None, Some(default)) => {
+					let var = Var::Local(slot);
+					let skip = self.emit(Op::Default { var, skip: 0 });
+					self.expr(default);
+					let given = self.emit(Op::Jump(0));
+					self.patch(skip);
+					self.emit(Op::Argument(slot));
+					self.patch(given);
+					self.destructure(&parameter.pattern, false);
+					self.emit(Op::Pop);
+				},
+			}
+		}
+
+		if let Some(rest) = rest {
+			let Var::Local(slot) = self.declared(rest) else {
+				unreachable!("parameters are local")
+			};
+			self.proto().rest = Some((slot, rest.span));
+		}
+
+		self.expr(body);
+		self.emit(Op::Return);
+
+		let proto = self.frames.pop().unwrap().proto;
+		let protos = &mut self.proto().protos;
+		protos.push(Arc::new(proto));
+		protos.len() as u32 - 1
+	}
+}
+
+/// Collects the variables that `expr` declares in the scope it is
+/// evaluated in, leaving out those of nested blocks, loop bodies and
+/// closures.
+fn declarations<'e>(expr: &'e Expr, idents: &mut Vec<&'e Ident>) {
+	match &expr.kind {
+		ExprKind::Declare(ident, value)
+		| ExprKind::DeclareMut(ident, value) => {
+			declarations(value, idents);
+			idents.push(ident);
+		},
+		ExprKind::Destructure { pattern, value, .. } => {
+			declarations(value, idents);
+			pattern_declarations(pattern, idents);
+		},
+		ExprKind::Use(u) => idents.extend(&u.imports),
+		ExprKind::If { condition, yes, no } => {
+			declarations(condition, idents);
+			all_declarations(&yes.stmts, idents);
+			match no {
+				Some(Else::Block(no)) => all_declarations(&no.stmts, idents),
+				Some(Else::If(no)) => declarations(no, idents),
+				None => {},
+			}
+		},
+		ExprKind::For { collection, .. } => declarations(collection, idents),
+		ExprKind::Match { scrutinee, .. } => declarations(scrutinee, idents),
+		ExprKind::Literal(_)
+		| ExprKind::Ident(_)
+		| ExprKind::Function(_)
+		| ExprKind::Block(_) => {},
+		ExprKind::TemplateString(i) => all_declarations(&i.values, idents),
+		ExprKind::Array(i) => all_declarations(&i.elements, idents),
+		ExprKind::Record(i) =>
+			all_declarations(i.fields.iter().map(|(_, v)| v), idents),
+		ExprKind::Call(callee, arguments) => {
+			all_declarations(&arguments.0, idents);
+			declarations(callee, idents);
+		},
+		ExprKind::Prop(i, _) => declarations(i, idents),
+		ExprKind::Index(i, j) => all_declarations([&**i, &**j], idents),
+		ExprKind::UnaryExpr(i) => declarations(&i.expr, idents),
+		ExprKind::BinExpr(i) => all_declarations([&*i.lhs, &*i.rhs], idents),
+		ExprKind::Range {
+			start, end, step, ..
+		} => all_declarations(
+			[&**start, &**end]
+				.into_iter()
+				.chain(step.as_deref()),
+			idents,
+		),
+		ExprKind::Assign(place, value)
+		| ExprKind::AddAssign(place, value)
+		| ExprKind::SubAssign(place, value)
+		| ExprKind::MulAssign(place, value)
+		| ExprKind::DivAssign(place, value)
+		| ExprKind::ModAssign(place, value) => {
+			place_declarations(place, idents);
+			declarations(value, idents);
+		},
+		ExprKind::Panic(i)
+		| ExprKind::Assert(i)
+		| ExprKind::Return(i)
+		| ExprKind::Break(i)
+		| ExprKind::Continue(i)
+		| ExprKind::Export(i) => declarations(i, idents),
+	}
+}
+
+/// Collects the variables that binding `pattern` declares, including
+/// those declared in its defaults.
+fn pattern_declarations<'e>(
+	pattern: &'e ast::Pattern,
+	idents: &mut Vec<&'e Ident>,
+) {
+	idents.extend(pattern.bindings());
+	for (_, default) in pattern.defaults() {
+		declarations(default, idents);
+	}
+}
+
+fn all_declarations<'e, I>(exprs: I, idents: &mut Vec<&'e Ident>)
+where I: IntoIterator<Item = &'e Expr> {
+	exprs
+		.into_iter()
+		.for_each(|expr| declarations(expr, idents));
+}
+
+fn place_declarations<'e>(
+	place: &'e dyn_parser::ast::Place,
+	idents: &mut Vec<&'e Ident>,
+) {
+	match &place.kind {
+		PlaceKind::Ident(_) => {},
+		PlaceKind::Prop(i, _) => place_declarations(i, idents),
+		PlaceKind::Index(i, j) => {
+			place_declarations(i, idents);
+			declarations(j, idents);
+		},
+	}
+}
@@ -0,0 +1,862 @@
+//! Formatter behind `dyn fmt`.
+//!
+//! Scripts are parsed and printed back in a canonical layout.
+//! Comments aren't part of the syntax tree, so they are taken from
+//! the concrete one and put back between the statements and elements
+//! they were written between. Whether a block or a list spans
+//! multiple lines is kept as written.
+
+use dyn_lexer::TokenKind;
+use dyn_parser::{
+	ast::{
+		BinExprKind, Code, Else, Expr, ExprKind, FieldPattern, Function,
+		MatchArm, Pattern, PatternKind, Place, PlaceKind, TemplateString, Use,
+	},
+	cst, parse_code, ParseError,
+};
+use dyn_span::{HasSpan, Span};
+
+/// Formats `source`, failing if it doesn't parse.
+pub fn format(source: &str) -> Result<String, ParseError> {
+	let code = parse_code(source)?;
+
+	let mut comments = vec![];
+	let mut braces = vec![];
+	for token in cst::from_ast(source, &code).tokens() {
+		match token.kind {
+			TokenKind::LineComment | TokenKind::BlockComment =>
+				comments.push(token.span),
+			TokenKind::RightBrace => braces.push(token.span.start()),
+			_ => {},
+		}
+	}
+
+	let mut printer = Printer {
+		source,
+		comments,
+		next_comment: 0,
+		braces,
+		indent: 0,
+		out: String::new(),
+		template_end: 0,
+	};
+	printer.lines(
+		&code.stmts,
+		source.len(),
+		false,
+		Expr::span,
+		|p, stmt| p.expr(stmt, STATEMENT),
+	);
+
+	Ok(printer.out)
+}
+
+/// Precedence of expressions that can only be nested in parentheses
+/// or where a whole expression is expected, like `let` and closures.
+const STATEMENT: u8 = 10;
+
+/// Precedence of literals, variables, brackets and postfix
+/// operations.
+const ATOM: u8 = 1;
+
+const UNARY: u8 = 2;
+
+fn precedence(expr: &Expr) -> u8 {
+	match &expr.kind {
+		ExprKind::Literal(_)
+		| ExprKind::Ident(_)
+		| ExprKind::TemplateString(_)
+		| ExprKind::Array(_)
+		| ExprKind::Record(_)
+		| ExprKind::Block(_)
+		| ExprKind::If { .. }
+		| ExprKind::Match { .. }
+		| ExprKind::Call(..)
+		| ExprKind::Prop(..)
+		| ExprKind::Index(..) => ATOM,
+		ExprKind::UnaryExpr(_) => UNARY,
+		ExprKind::BinExpr(i) => match i.op {
+			BinExprKind::Mul | BinExprKind::Div | BinExprKind::Mod => 3,
+			BinExprKind::Add | BinExprKind::Sub => 4,
+			BinExprKind::Equal
+			| BinExprKind::NotEqual
+			| BinExprKind::LessThan
+			| BinExprKind::GreaterThan
+			| BinExprKind::LessThanEqual
+			| BinExprKind::GreaterThanEqual => 6,
+			BinExprKind::And => 7,
+			BinExprKind::Or => 8,
+			BinExprKind::Coalesce => 9,
+		},
+		ExprKind::Range { .. } => 5,
+		_ => STATEMENT,
+	}
+}
+
+/// Escapes a fragment of a template string. Plain strings are copied
+/// from the source instead.
+fn escape(fragment: &str) -> String {
+	let mut escaped = String::with_capacity(fragment.len());
+
+	for c in fragment.chars() {
+		match c {
+			'\\' => escaped.push_str(r"\\"),
+			'"' => escaped.push_str(r#"\""#),
+			'{' => escaped.push_str(r"\{"),
+			'}' => escaped.push_str(r"\}"),
+			'\n' => escaped.push_str(r"\n"),
+			'\r' => escaped.push_str(r"\r"),
+			'\t' => escaped.push_str(r"\t"),
+			c => escaped.push(c),
+		}
+	}
+
+	escaped
+}
+
+struct Printer<'s> {
+	source: &'s str,
+	/// Spans of all comments, in order.
+	comments: Vec<Span>,
+	/// First comment that hasn't been printed yet.
+	next_comment: usize,
+	/// Starts of all `}`s, which the syntax tree doesn't keep.
+	braces: Vec<usize>,
+	indent: usize,
+	out: String,
+	/// Where the last template string printed ends, since a newline
+	/// after one doesn't separate elements.
+	template_end: usize,
+}
+
+impl<'s> Printer<'s> {
+	/// Appends `text`, indenting it if it starts a line.
+	fn write(&mut self, text: &str) {
+		if self.out.ends_with('\n') {
+			self.out
+				.extend(std::iter::repeat_n('\t', self.indent));
+		}
+
+		self.out.push_str(text);
+	}
+
+	fn comment(&self, span: Span) -> &'s str {
+		self.source[span.range()].trim_end()
+	}
+
+	fn next_comment_before(&self, end: usize) -> Option<Span> {
+		self.comments
+			.get(self.next_comment)
+			.filter(|span| span.start() < end)
+			.copied()
+	}
+
+	/// Keeps a single empty line if there was at least one between
+	/// `from` and `to`.
+	fn blank_line(&mut self, from: usize, to: usize) {
+		if self.source[from..to]
+			.matches('\n')
+			.count() >= 2
+		{
+			self.out.push('\n');
+		}
+	}
+
+	/// Prints the comments before `end` on lines of their own.
+	fn comments_before(&mut self, end: usize, last: &mut Option<usize>) {
+		while let Some(span) = self.next_comment_before(end) {
+			self.next_comment += 1;
+
+			if let Some(last) = *last {
+				self.blank_line(last, span.start());
+			}
+			self.write(self.comment(span));
+			self.out.push('\n');
+
+			*last = Some(span.end());
+		}
+	}
+
+	/// Prints the comments on the same line after something that ends
+	/// at `end`, along with those inside it that haven't been
+	/// printed yet. Returns where the last of them ends.
+	fn trailing_comments(&mut self, end: usize) -> Option<usize> {
+		let mut last = None;
+		let mut line_comment = false;
+
+		while let Some(span) = self
+			.comments
+			.get(self.next_comment)
+			.copied()
+		{
+			if span.start() >= end
+				&& self.source[end..span.start()].contains('\n')
+			{
+				break;
+			}
+			self.next_comment += 1;
+
+			// a line comment swallows anything after it
+			if line_comment {
+				self.out.push('\n');
+			} else {
+				self.write(" ");
+			}
+
+			let comment = self.comment(span);
+			self.write(comment);
+			line_comment = comment.starts_with("//");
+
+			last = Some(span.end());
+		}
+
+		last
+	}
+
+	/// Prints `items` one per line, keeping the comments and empty
+	/// lines between them, up to `end`. Elements of lists are
+	/// separated by line breaks too, unless they end with a template
+	/// string.
+	fn lines<T>(
+		&mut self,
+		items: &[T],
+		end: usize,
+		elements: bool,
+		span: impl Fn(&T) -> Span,
+		mut print: impl FnMut(&mut Self, &T),
+	) {
+		let mut last = None;
+
+		for item in items {
+			let span = span(item);
+
+			self.comments_before(span.start(), &mut last);
+			if let Some(last) = last {
+				self.blank_line(last, span.start());
+			}
+
+			print(self, item);
+			if elements && self.out.len() == self.template_end {
+				self.write(",");
+			}
+			last = self
+				.trailing_comments(span.end())
+				.or(Some(span.end()));
+			self.out.push('\n');
+		}
+
+		self.comments_before(end, &mut last);
+	}
+
+	/// Prints `items` between `open` and `close`, where `span` covers
+	/// both brackets. They stay on one line unless they weren't.
+	fn list<T>(
+		&mut self,
+		(open, close): (&str, &str),
+		items: &[T],
+		span: Span,
+		item_span: impl Fn(&T) -> Span,
+		mut print: impl FnMut(&mut Self, &T),
+	) {
+		let end = span.end() - close.len();
+
+		self.write(open);
+
+		if !self.source[span.start()..end].contains('\n') {
+			for (i, item) in items.iter().enumerate() {
+				if i > 0 {
+					self.write(", ");
+				}
+				print(self, item);
+			}
+		} else {
+			self.out.push('\n');
+			self.indent += 1;
+			self.lines(items, end, true, item_span, print);
+			self.indent -= 1;
+		}
+
+		self.write(close);
+	}
+
+	fn block(&mut self, code: &Code) {
+		let i = self
+			.braces
+			.partition_point(|&brace| brace < code.span.end());
+		let close = self.braces[i];
+		let start = code.span.start().min(close);
+
+		if code.stmts.len() <= 1
+			&& !self.source[start..close].contains('\n')
+			&& self
+				.next_comment_before(close)
+				.is_none()
+		{
+			match code.stmts.first() {
+				Some(stmt) => {
+					self.write("{ ");
+					self.expr(stmt, STATEMENT);
+					self.write(" }");
+				},
+				None => self.write("{}"),
+			}
+			return;
+		}
+
+		self.write("{");
+		self.out.push('\n');
+		self.indent += 1;
+		self.lines(
+			&code.stmts,
+			close,
+			false,
+			Expr::span,
+			|p, stmt| p.expr(stmt, STATEMENT),
+		);
+		self.indent -= 1;
+		self.write("}");
+	}
+
+	/// Prints `expr`, wrapping it in parentheses if it binds looser
+	/// than `max` allows.
+	fn expr(&mut self, expr: &Expr, max: u8) {
+		let precedence = precedence(expr);
+		if precedence > max {
+			self.write("(");
+			self.expr(expr, STATEMENT);
+			self.write(")");
+			return;
+		}
+
+		match &expr.kind {
+			ExprKind::Literal(_) => {
+				let source = self.source;
+				self.write(&source[expr.span.range()]);
+			},
+			ExprKind::Ident(i) => self.write(i.symbol()),
+			ExprKind::TemplateString(i) => self.template_string(i),
+			ExprKind::Array(i) => self.list(
+				("[", "]"),
+				&i.elements,
+				expr.span,
+				Expr::span,
+				|p, e| p.expr(e, STATEMENT),
+			),
+			ExprKind::Record(i) => self.list(
+				("(", ")"),
+				&i.fields,
+				expr.span,
+				|(i, e)| i.span + e.span,
+				|p, (i, e)| {
+					p.write(i.symbol());
+					p.write(": ");
+					p.expr(e, STATEMENT);
+				},
+			),
+			ExprKind::Function(i) => self.function(i),
+			ExprKind::Call(i, j) => {
+				self.expr(i, ATOM);
+				self.list(
+					("(", ")"),
+					&j.0,
+					Span::new(i.span.end(), expr.span.end()),
+					Expr::span,
+					|p, e| p.expr(e, STATEMENT),
+				);
+			},
+			ExprKind::Prop(i, j) => {
+				self.expr(i, ATOM);
+				self.write(".");
+				self.write(j.symbol());
+			},
+			ExprKind::Index(i, j) => {
+				self.expr(i, ATOM);
+				self.write("[");
+				self.expr(j, STATEMENT);
+				self.write("]");
+			},
+			ExprKind::UnaryExpr(i) => {
+				self.write(i.op.as_ref());
+				self.expr(&i.expr, UNARY);
+			},
+			ExprKind::BinExpr(i) => {
+				self.expr(&i.lhs, precedence);
+				self.write(" ");
+				self.write(i.op.as_ref());
+				self.write(" ");
+				self.expr(&i.rhs, precedence - 1);
+			},
+			ExprKind::Range {
+				start,
+				end,
+				step,
+				inclusive,
+			} => {
+				self.expr(start, precedence - 1);
+				self.write(if *inclusive { "..=" } else { ".." });
+				self.expr(end, precedence - 1);
+				if let Some(step) = step {
+					self.write("..");
+					self.expr(step, precedence - 1);
+				}
+			},
+			ExprKind::Assign(i, j) => self.assign(i, "=", j),
+			ExprKind::AddAssign(i, j) => self.assign(i, "+=", j),
+			ExprKind::SubAssign(i, j) => self.assign(i, "-=", j),
+			ExprKind::MulAssign(i, j) => self.assign(i, "*=", j),
+			ExprKind::DivAssign(i, j) => self.assign(i, "/=", j),
+			ExprKind::ModAssign(i, j) => self.assign(i, "%=", j),
+			ExprKind::Block(i) => self.block(i),
+			ExprKind::Declare(i, j) | ExprKind::DeclareMut(i, j) => {
+				self.write(match expr.kind {
+					ExprKind::Declare(..) => "let ",
+					_ => "let! ",
+				});
+				self.write(i.symbol());
+				self.write(" = ");
+				self.expr(j, STATEMENT);
+			},
+			ExprKind::Destructure {
+				pattern,
+				value,
+				mutable,
+			} => {
+				self.write(if *mutable { "let! " } else { "let " });
+				self.pattern(pattern);
+				self.write(" = ");
+				self.expr(value, STATEMENT);
+			},
+			ExprKind::If { condition, yes, no } => {
+				self.write("if ");
+				self.expr(condition, STATEMENT);
+				self.write(" ");
+				self.block(yes);
+
+				match no {
+					Some(Else::Block(no)) => {
+						self.write(" else ");
+						self.block(no);
+					},
+					Some(Else::If(no)) => {
+						self.write(" else ");
+						self.expr(no, STATEMENT);
+					},
+					None => {},
+				}
+			},
+			ExprKind::For {
+				collection,
+				item,
+				body,
+			} => {
+				self.write("iter ");
+				self.expr(collection, STATEMENT);
+				self.write(" of ");
+				self.pattern(item);
+				self.write(" ");
+				self.block(body);
+			},
+			ExprKind::Match { scrutinee, arms } => {
+				self.write("match ");
+				self.expr(scrutinee, STATEMENT);
+				self.write(" {");
+				self.out.push('\n');
+				self.indent += 1;
+				self.lines(
+					arms,
+					expr.span.end() - 1,
+					true,
+					|arm| arm.span,
+					Self::match_arm,
+				);
+				self.indent -= 1;
+				self.write("}");
+			},
+			ExprKind::Panic(i) => self.keyword("panic", i),
+			ExprKind::Assert(i) => self.keyword("assert", i),
+			ExprKind::Return(i) => self.keyword("return", i),
+			ExprKind::Break(i) => self.keyword("break", i),
+			ExprKind::Continue(i) => self.keyword("continue", i),
+			ExprKind::Use(i) => self.use_(i),
+			ExprKind::Export(i) => {
+				self.write("export ");
+				self.expr(i, STATEMENT);
+			},
+		}
+	}
+
+	fn template_string(&mut self, i: &TemplateString) {
+		self.write("\"");
+
+		for (fragment, value) in i.fragments.iter().zip(&i.values) {
+			self.write(&escape(fragment));
+			self.write("#{");
+			self.expr(value, STATEMENT);
+			self.write("}");
+		}
+
+		if let Some(trailing) = i.fragments.last() {
+			self.write(&escape(trailing));
+		}
+		self.write("\"");
+		self.template_end = self.out.len();
+	}
+
+	fn function(&mut self, i: &Function) {
+		let parameters = &i.parameters;
+
+		if !parameters.positional.is_empty() || parameters.rest.is_some() {
+			self.write("|");
+
+			for (j, parameter) in parameters.positional.iter().enumerate() {
+				if j > 0 {
+					self.write(", ");
+				}
+				self.pattern(&parameter.pattern);
+				if let Some(default) = &parameter.default {
+					self.write(" = ");
+					self.expr(default, STATEMENT - 1);
+				}
+			}
+
+			if let Some(rest) = &parameters.rest {
+				if !parameters.positional.is_empty() {
+					self.write(", ");
+				}
+				self.write("...");
+				self.write(rest.symbol());
+			}
+
+			self.write("| ");
+		}
+
+		self.write("-> ");
+		self.expr(&i.body, STATEMENT);
+	}
+
+	fn match_arm(&mut self, i: &MatchArm) {
+		self.pattern(&i.pattern);
+		if let Some(guard) = &i.guard {
+			self.write(" if ");
+			self.expr(guard, STATEMENT - 1);
+		}
+		self.write(" -> ");
+		self.expr(&i.body, STATEMENT);
+	}
+
+	fn pattern(&mut self, i: &Pattern) {
+		match &i.kind {
+			PatternKind::Wildcard => self.write("_"),
+			PatternKind::Literal(_) => {
+				let source = self.source;
+				self.write(&source[i.span.range()]);
+			},
+			PatternKind::Binding(i) => self.write(i.symbol()),
+			PatternKind::Array { elements, rest } => {
+				let items = elements
+					.iter()
+					.map(|element| (false, element))
+					.chain(rest.iter().map(|rest| (true, &**rest)))
+					.collect::<Vec<_>>();
+
+				self.list(
+					("[", "]"),
+					&items,
+					i.span,
+					|(_, element)| element.span,
+					|p, (rest, element)| {
+						if *rest {
+							p.write("...");
+						}
+						p.pattern(element);
+					},
+				)
+			},
+			PatternKind::Record(fields) => self.list(
+				("(", ")"),
+				fields,
+				i.span,
+				FieldPattern::span,
+				|p, field| {
+					p.write(field.key.symbol());
+					// `(x: x)` is written `(x)`
+					if !matches!(
+						&field.pattern.kind,
+						PatternKind::Binding(i) if *i == field.key
+					) {
+						p.write(": ");
+						p.pattern(&field.pattern);
+					}
+					if let Some(default) = &field.default {
+						p.write(" = ");
+						p.expr(default, STATEMENT - 1);
+					}
+				},
+			),
+		}
+	}
+
+	fn place(&mut self, i: &Place) {
+		match &i.kind {
+			PlaceKind::Ident(i) => self.write(i.symbol()),
+			PlaceKind::Prop(i, j) => {
+				self.place(i);
+				self.write(".");
+				self.write(j.symbol());
+			},
+			PlaceKind::Index(i, j) => {
+				self.place(i);
+				self.write("[");
+				self.expr(j, STATEMENT);
+				self.write("]");
+			},
+		}
+	}
+
+	fn assign(&mut self, place: &Place, op: &str, value: &Expr) {
+		self.place(place);
+		self.write(" ");
+		self.write(op);
+		self.write(" ");
+		self.expr(value, STATEMENT);
+	}
+
+	/// Prints `keyword` followed by its operand, which is left out if
+	/// it was, as in a bare `return`.
+	fn keyword(&mut self, keyword: &str, operand: &Expr) {
+		self.write(keyword);
+
+		if operand.span.start() != operand.span.end() {
+			self.write(" ");
+			self.expr(operand, STATEMENT);
+		}
+	}
+
+	fn use_(&mut self, i: &Use) {
+		self.write("use ");
+
+		if !i.imports.is_empty() {
+			self.write("(");
+			for (j, import) in i.imports.iter().enumerate() {
+				if j > 0 {
+					self.write(", ");
+				}
+				self.write(import.symbol());
+			}
+			self.write(") of ");
+		}
+
+		let source = self.source;
+		self.write(&source[i.path.span.range()]);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+
+	use indoc::indoc;
+	use pretty_assertions::assert_eq;
+
+	use super::*;
+
+	#[test]
+	fn formatting_examples_should_be_idempotent() {
+		let examples = concat!(
+			env!("CARGO_MANIFEST_DIR"),
+			"/../../examples"
+		);
+
+		for entry in fs::read_dir(examples).unwrap() {
+			let path = entry.unwrap().path();
+			let source = fs::read_to_string(&path).unwrap();
+
+			// some examples show off syntax errors
+			let Ok(formatted) = format(&source) else {
+				continue;
+			};
+
+			assert_eq!(
+				format(&formatted).as_ref(),
+				Ok(&formatted),
+				"{} isn't formatted the same twice",
+				path.display()
+			);
+		}
+	}
+
+	#[test]
+	fn should_normalize_spacing() {
+		let source = indoc! {"
+			let!   x=1+2*3
+			let f=| a,b = 1 ,...rest|->  a+b
+
+
+			print( f(x ,2),[1,2] ,( a:1, b:'two'))
+			if a {1}else  if b {2}   else{3}
+			let y=2*if a {1}else{3}
+		"};
+
+		assert_eq!(
+			format(source),
+			Ok(indoc! {"
+				let! x = 1 + 2 * 3
+				let f = |a, b = 1, ...rest| -> a + b
+
+				print(f(x, 2), [1, 2], (a: 1, b: 'two'))
+				if a { 1 } else if b { 2 } else { 3 }
+				let y = 2 * if a { 1 } else { 3 }
+			"}
+			.to_owned())
+		);
+	}
+
+	#[test]
+	fn should_keep_necessary_parentheses() {
+		let source = indoc! {"
+			(1 + 2) * -(3 - 4) - (5 - 6)
+			((1 + 2) + 3)
+			(|x| -> x)(1)
+			!(a && b) || c
+			(a ?? b) || c ?? d
+		"};
+
+		assert_eq!(
+			format(source),
+			Ok(indoc! {"
+				(1 + 2) * -(3 - 4) - (5 - 6)
+				1 + 2 + 3
+				(|x| -> x)(1)
+				!(a && b) || c
+				(a ?? b) || c ?? d
+			"}
+			.to_owned())
+		);
+	}
+
+	#[test]
+	fn should_preserve_comments() {
+		let source = indoc! {"
+			// leading
+			let a = 1 // trailing
+			/* block */
+
+
+			if a == 1 {
+			    // inside
+			  print(a)
+			    /* before closing */
+			} else { a }
+			iter [
+			  1, // first
+			  2
+			] of i {
+			return
+			}
+		"};
+
+		assert_eq!(
+			format(source),
+			Ok(indoc! {"
+				// leading
+				let a = 1 // trailing
+				/* block */
+
+				if a == 1 {
+					// inside
+					print(a)
+					/* before closing */
+				} else { a }
+				iter [
+					1 // first
+					2
+				] of i {
+					return
+				}
+			"}
+			.to_owned())
+		);
+	}
+
+	#[test]
+	fn should_format_match_arms() {
+		let source = indoc! {"
+			match x { 0->nil, [a,...rest] if a>0-> rest
+			  // records
+			  (a:_,b) -> b , _->-1 }
+		"};
+
+		assert_eq!(
+			format(source),
+			Ok(indoc! {"
+				match x {
+					0 -> nil
+					[a, ...rest] if a > 0 -> rest
+					// records
+					(a: _, b) -> b
+					_ -> -1
+				}
+			"}
+			.to_owned())
+		);
+	}
+
+	#[test]
+	fn should_format_destructuring() {
+		let source = indoc! {"
+			let![a,...b]=c
+			let (x,y:[z],w=1)=p
+			iter xs of (k:key,v) { f(|[a,b]=[1,2]|->a) }
+		"};
+
+		assert_eq!(
+			format(source),
+			Ok(indoc! {"
+				let! [a, ...b] = c
+				let (x, y: [z], w = 1) = p
+				iter xs of (k: key, v) { f(|[a, b] = [1, 2]| -> a) }
+			"}
+			.to_owned())
+		);
+	}
+
+	#[test]
+	fn should_format_ranges() {
+		let source = indoc! {"
+			iter 0 .. n+1 of i { xs[i..=(i..j).len] }
+			let r=(1..10..2)==a
+		"};
+
+		assert_eq!(
+			format(source),
+			Ok(indoc! {"
+				iter 0..n + 1 of i { xs[i..=(i..j).len] }
+				let r = 1..10..2 == a
+			"}
+			.to_owned())
+		);
+	}
+
+	#[test]
+	fn should_format_template_strings() {
+		let source = indoc! {r##"
+			print("a #{ x }b#{y+1}")
+			print(
+				"#{x}",
+				"#{y}"
+			)
+		"##};
+
+		assert_eq!(
+			format(source),
+			Ok(indoc! {r##"
+				print("a #{x}b#{y + 1}")
+				print(
+					"#{x}",
+					"#{y}",
+				)
+			"##}
+			.to_owned())
+		);
+	}
+}
@@ -1,3 +1,4 @@
+use dyn_span::Span;
 use serde::Serialize;
 use thiserror::Error;
 
@@ -9,4 +10,13 @@ pub enum LexError {
 	#[default]
 	#[error("InvalidToken")]
 	InvalidToken,
+
+	#[error("invalid digit `{digit}` in a base {radix} literal")]
+	InvalidDigit { digit: char, radix: u32, span: Span },
+
+	#[error("missing digits after the integer base prefix")]
+	MissingDigits { span: Span },
+
+	#[error("float literal is out of range")]
+	FloatOutOfRange { span: Span },
 }
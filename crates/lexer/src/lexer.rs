@@ -1,12 +1,13 @@
 use winnow::{
 	combinator::{alt, eof},
-	stream::Offset,
+	stream::Location,
 	Located, PResult, Parser,
 };
 
 use crate::{token::TokenKind, LexError, SpannedToken, Token};
 
 mod comment;
+mod float;
 mod identifier;
 mod integer;
 mod punctuation;
@@ -14,6 +15,7 @@ mod string;
 mod whitespace;
 
 use comment::{block_comment, line_comment};
+use float::float;
 use identifier::identifier;
 use integer::integer;
 use punctuation::punctuation;
@@ -27,18 +29,23 @@ use self::string::{
 
 type Stream<'a> = Located<&'a str>;
 
-fn token(i: &mut Stream<'_>) -> PResult<Token> {
+fn token(i: &mut Stream<'_>) -> PResult<Result<Token, LexError>> {
 	alt((
-		whitespace,
-		line_comment,
-		block_comment,
-		identifier,
-		template_string_leading_fragment,
-		template_string_central_fragment,
-		template_string_trailing_fragment,
-		punctuation,
+		alt((
+			whitespace,
+			line_comment,
+			block_comment,
+			identifier,
+			template_string_leading_fragment,
+			template_string_central_fragment,
+			template_string_trailing_fragment,
+			punctuation,
+		))
+		.map(Ok),
+		// NOTE: number literals report malformed digits on their own
+		float,
 		integer,
-		string,
+		string.map(Ok),
 	))
 	.parse_next(i)
 }
@@ -88,7 +95,7 @@ impl<'a> Iterator for SpannedLexer<'a> {
 				.with_span()
 				.parse_next(&mut self.code)
 			{
-				Ok((Some(tok), span)) => {
+				Ok((Some(Ok(tok)), span)) => {
 					if self.config.ignore_whitespace && tok == Token::Whitespace
 					{
 						continue;
@@ -97,23 +104,26 @@ impl<'a> Iterator for SpannedLexer<'a> {
 					if self.config.ignore_comments
 						&& matches!(
 							tok,
-							Token::LineComment | Token::BlockComment
+							Token::LineComment(_) | Token::BlockComment(_)
 						) {
 						continue;
 					}
 
 					if self.config.asi
-						&& tok == Token::NewLine && !matches!(
-						self.last,
-						Some(
-							TokenKind::Nil
-								| TokenKind::True | TokenKind::False
-								| TokenKind::Integer | TokenKind::String
-								| TokenKind::Identifier
-								| TokenKind::RightParenthesis
-								| TokenKind::RightBrace | TokenKind::RightBracket
-						)
-					) {
+						&& tok == Token::NewLine
+						&& !matches!(
+							self.last,
+							Some(
+								TokenKind::Nil
+									| TokenKind::True | TokenKind::False
+									| TokenKind::Integer | TokenKind::Float
+									| TokenKind::String | TokenKind::Identifier
+									| TokenKind::RightParenthesis
+									| TokenKind::RightBrace
+									| TokenKind::RightBracket | TokenKind::Return
+									| TokenKind::Break | TokenKind::Continue
+							)
+						) {
 						continue;
 					}
 
@@ -121,14 +131,21 @@ impl<'a> Iterator for SpannedLexer<'a> {
 
 					Some(SpannedToken::new(tok, span.into()))
 				},
+				Ok((Some(Err(err)), span)) =>
+					Some(SpannedToken::new_err(err, span.into())),
 				Ok((None, _)) => None,
 				Err(_) => {
-					// let err = err.into_inner();
-					// println!("{:?}", err);
-					let offset = self.code.offset_from(&start);
+					// skip a character so that lexing can go on
+					self.code.reset(start);
+					let offset = self.code.location();
+					let len = self
+						.code
+						.next_token()
+						.map_or(1, char::len_utf8);
+
 					Some(SpannedToken::new_err(
 						LexError::InvalidToken,
-						(offset..offset + 1).into(),
+						(offset..offset + len).into(),
 					))
 				},
 			}
@@ -190,9 +207,61 @@ mod tests {
 		assert_eq!(
 			lexer.next(),
 			Some(SpannedToken {
-				token: Ok(Token::LineComment),
+				token: Ok(Token::LineComment(
+					" comment".to_owned()
+				)),
 				span: (0..10).into()
 			})
 		);
 	}
+
+	#[test]
+	fn should_lex_float() {
+		let code = "1.5e3";
+		let mut lexer = SpannedLexer::new(code, LexerConfig::default());
+
+		assert_eq!(
+			lexer.next(),
+			Some(SpannedToken {
+				token: Ok(Token::Float(1500.0)),
+				span: (0..5).into()
+			})
+		);
+	}
+
+	#[test]
+	fn integer_followed_by_dot_dot_should_not_be_float() {
+		let code = "1..2";
+		let lexer = SpannedLexer::new(code, LexerConfig::default());
+
+		assert_eq!(
+			lexer
+				.map(|tok| tok.token)
+				.collect::<Vec<_>>(),
+			[
+				Ok(Token::Integer(1.into())),
+				Ok(Token::DotDot),
+				Ok(Token::Integer(2.into())),
+			]
+		);
+	}
+
+	#[test]
+	fn should_lex_longest_punctuation() {
+		let code = "... ..= .. ?? match";
+		let lexer = SpannedLexer::new(code, LexerConfig::default());
+
+		assert_eq!(
+			lexer
+				.map(|tok| tok.token)
+				.collect::<Vec<_>>(),
+			[
+				Ok(Token::DotDotDot),
+				Ok(Token::DotDotEqual),
+				Ok(Token::DotDot),
+				Ok(Token::QuestionQuestion),
+				Ok(Token::Match),
+			]
+		);
+	}
 }
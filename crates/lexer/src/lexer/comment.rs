@@ -9,17 +9,24 @@ use super::Stream;
 use crate::Token;
 
 pub fn line_comment(i: &mut Stream<'_>) -> PResult<Token> {
-	("//", till_line_ending)
-		.value(Token::LineComment)
+	preceded("//", till_line_ending)
+		.map(|text: &str| Token::LineComment(text.to_owned()))
 		.parse_next(i)
 }
 
 pub fn block_comment(i: &mut Stream<'_>) -> PResult<Token> {
 	preceded(
 		"/*",
-		repeat_till::<_, _, (), _, _, _, _>(0.., block_comment_inner, "*/"),
+		repeat_till::<_, _, (), _, _, _, _>(0.., block_comment_inner, "*/")
+			.recognize(),
 	)
-	.value(Token::BlockComment)
+	.map(|text: &str| {
+		Token::BlockComment(
+			text.strip_suffix("*/")
+				.unwrap()
+				.to_owned(),
+		)
+	})
 	.parse_next(i)
 }
 
@@ -44,7 +51,7 @@ mod tests {
 	fn lex_line_comment() {
 		assert_eq!(
 			line_comment.parse_next(&mut Located::new("//")),
-			Ok(Token::LineComment)
+			Ok(Token::LineComment(String::new()))
 		);
 	}
 
@@ -52,7 +59,9 @@ mod tests {
 	fn multiple_line_comments_should_be_allowed() {
 		assert_eq!(
 			line_comment.parse_next(&mut Located::new("// abc // def")),
-			Ok(Token::LineComment)
+			Ok(Token::LineComment(
+				" abc // def".to_owned()
+			))
 		);
 	}
 
@@ -65,7 +74,7 @@ mod tests {
 
 		assert_eq!(
 			src.next().unwrap().token,
-			Ok(Token::LineComment)
+			Ok(Token::LineComment(" abc".to_owned()))
 		);
 		// assert_eq!(
 		// 	src.next().unwrap().token,
@@ -73,7 +82,7 @@ mod tests {
 		// );
 		assert_eq!(
 			src.next().unwrap().token,
-			Ok(Token::LineComment)
+			Ok(Token::LineComment(" def".to_owned()))
 		);
 	}
 
@@ -81,12 +90,12 @@ mod tests {
 	fn lex_block_comment() {
 		assert_eq!(
 			block_comment.parse_next(&mut Located::new("/**/")),
-			Ok(Token::BlockComment)
+			Ok(Token::BlockComment(String::new()))
 		);
 
 		assert_eq!(
 			block_comment.parse_next(&mut Located::new("/* */")),
-			Ok(Token::BlockComment)
+			Ok(Token::BlockComment(" ".to_owned()))
 		);
 	}
 
@@ -94,7 +103,7 @@ mod tests {
 	fn block_comment_should_be_able_to_contain_newline() {
 		assert_eq!(
 			block_comment.parse_next(&mut Located::new("/*\n\n\n*/")),
-			Ok(Token::BlockComment)
+			Ok(Token::BlockComment("\n\n\n".to_owned()))
 		);
 	}
 
@@ -102,7 +111,7 @@ mod tests {
 	fn should_allow_nested_block_comment() {
 		assert_eq!(
 			block_comment.parse_next(&mut Located::new("/*/**/*/")),
-			Ok(Token::BlockComment)
+			Ok(Token::BlockComment("/**/".to_owned()))
 		);
 
 		let mut code = Located::new(indoc! {"
@@ -116,7 +125,10 @@ mod tests {
 
 		assert_eq!(
 			trace("block_comment", block_comment).parse_next(&mut code),
-			Ok(Token::BlockComment)
+			Ok(Token::BlockComment(
+				"\n    Outer comment\n    /*\n        Inner comment\n    */\n"
+					.to_owned()
+			))
 		);
 	}
 
@@ -0,0 +1,84 @@
+use winnow::{
+	ascii::digit1,
+	combinator::{alt, opt},
+	token::{one_of, take_while},
+	PResult, Parser,
+};
+
+use super::Stream;
+use crate::{LexError, Token};
+
+fn decimal(i: &mut Stream<'_>) -> PResult<()> {
+	(
+		digit1,
+		take_while(0.., |c: char| {
+			c.is_ascii_digit() || c == '_'
+		}),
+	)
+		.void()
+		.parse_next(i)
+}
+
+fn exponent(i: &mut Stream<'_>) -> PResult<()> {
+	(
+		one_of(['e', 'E']),
+		opt(one_of(['+', '-'])),
+		decimal,
+	)
+		.void()
+		.parse_next(i)
+}
+
+// NOTE: `1..2` and `1.abs` must not be lexed as floats
+pub fn float(i: &mut Stream<'_>) -> PResult<Result<Token, LexError>> {
+	let (literal, span) = alt((
+		(decimal, '.', decimal, opt(exponent)).void(),
+		(decimal, exponent).void(),
+	))
+	.recognize()
+	.with_span()
+	.parse_next(i)?;
+
+	let value = literal
+		.replace('_', "")
+		.parse::<f64>()
+		.expect("float literal is already validated");
+
+	Ok(if value.is_finite() {
+		Ok(Token::Float(value))
+	} else {
+		Err(LexError::FloatOutOfRange { span: span.into() })
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use pretty_assertions::assert_eq;
+	use winnow::Located;
+
+	use super::*;
+
+	fn lex(code: &str) -> Result<Token, LexError> {
+		float
+			.parse_next(&mut Located::new(code))
+			.unwrap()
+	}
+
+	#[test]
+	fn should_parse_float() {
+		assert_eq!(lex("0.5"), Ok(Token::Float(0.5)));
+		assert_eq!(lex("1e3"), Ok(Token::Float(1000.0)));
+		assert_eq!(lex("2.5E-1"), Ok(Token::Float(0.25)));
+		assert_eq!(lex("1_000.5"), Ok(Token::Float(1000.5)));
+	}
+
+	#[test]
+	fn out_of_range_float_should_be_error() {
+		assert_eq!(
+			lex("1e999"),
+			Err(LexError::FloatOutOfRange {
+				span: (0..5).into()
+			})
+		);
+	}
+}
@@ -26,8 +26,11 @@ pub fn identifier(input: &mut Stream<'_>) -> PResult<Token> {
 			"return" => Token::Return,
 			"break" => Token::Break,
 			"continue" => Token::Continue,
+			"panic" => Token::Panic,
+			"assert" => Token::Assert,
 			"use" => Token::Use,
 			"export" => Token::Export,
+			"match" => Token::Match,
 			_ => Token::Identifier(ident.to_owned()),
 		})
 		.parse_next(input)
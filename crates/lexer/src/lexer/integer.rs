@@ -1,10 +1,194 @@
-use winnow::{ascii::dec_int, PResult, Parser};
+use std::ops::Range;
+
+use num_bigint::BigInt;
+use winnow::{
+	ascii::digit1,
+	combinator::{alt, preceded},
+	token::take_while,
+	PResult, Parser,
+};
 
 use super::Stream;
-use crate::Token;
+use crate::{LexError, Token};
+
+/// Everything that may follow the first digit of a literal.
+///
+/// Letters are included so that `0xZZ` or `12ab` are reported as a
+/// whole instead of being split into a number and an identifier.
+fn literal_tail<'a>(i: &mut Stream<'a>) -> PResult<&'a str> {
+	take_while(0.., |c: char| {
+		c.is_ascii_alphanumeric() || c == '_'
+	})
+	.parse_next(i)
+}
+
+/// Strips `_` separators from `digits`, checking every digit against
+/// `radix`.
+fn strip_separators(
+	digits: &str,
+	radix: u32,
+	span: Range<usize>,
+) -> Result<String, LexError> {
+	let mut buf = String::with_capacity(digits.len());
+
+	for (offset, c) in digits.char_indices() {
+		match c {
+			'_' => {},
+			c if c.is_digit(radix) => buf.push(c),
+			c => {
+				let start = span.start + offset;
+
+				return Err(LexError::InvalidDigit {
+					digit: c,
+					radix,
+					span: (start..start + c.len_utf8()).into(),
+				});
+			},
+		}
+	}
+
+	Ok(buf)
+}
+
+fn radix(i: &mut Stream<'_>) -> PResult<u32> {
+	preceded(
+		'0',
+		alt((
+			'x'.value(16),
+			'o'.value(8),
+			'b'.value(2),
+		)),
+	)
+	.parse_next(i)
+}
+
+fn prefixed_integer(i: &mut Stream<'_>) -> PResult<Result<Token, LexError>> {
+	let (radix, prefix) = radix.with_span().parse_next(i)?;
+	let (digits, span) = literal_tail.with_span().parse_next(i)?;
+
+	Ok(
+		strip_separators(digits, radix, span.clone()).and_then(|digits| {
+			if digits.is_empty() {
+				return Err(LexError::MissingDigits {
+					span: (prefix.start..span.end).into(),
+				});
+			}
+
+			Ok(Token::Integer(
+				BigInt::parse_bytes(digits.as_bytes(), radix)
+					.expect("digits are already validated"),
+			))
+		}),
+	)
+}
+
+fn decimal_integer(i: &mut Stream<'_>) -> PResult<Result<Token, LexError>> {
+	let (digits, span) = (digit1, literal_tail)
+		.recognize()
+		.with_span()
+		.parse_next(i)?;
+
+	Ok(
+		strip_separators(digits, 10, span).map(|digits| {
+			Token::Integer(
+				digits
+					.parse()
+					.expect("digits are already validated"),
+			)
+		}),
+	)
+}
+
+pub fn integer(i: &mut Stream<'_>) -> PResult<Result<Token, LexError>> {
+	alt((prefixed_integer, decimal_integer)).parse_next(i)
+}
+
+#[cfg(test)]
+mod tests {
+	use pretty_assertions::assert_eq;
+	use winnow::Located;
+
+	use super::*;
+
+	fn lex(code: &str) -> Result<Token, LexError> {
+		integer
+			.parse_next(&mut Located::new(code))
+			.unwrap()
+	}
+
+	#[test]
+	fn should_parse_prefixed_integers() {
+		assert_eq!(
+			lex("0xFf"),
+			Ok(Token::Integer(255.into()))
+		);
+		assert_eq!(
+			lex("0o17"),
+			Ok(Token::Integer(15.into()))
+		);
+		assert_eq!(
+			lex("0b101"),
+			Ok(Token::Integer(5.into()))
+		);
+	}
+
+	#[test]
+	fn underscores_should_separate_digits() {
+		assert_eq!(
+			lex("1_000_000"),
+			Ok(Token::Integer(1_000_000.into()))
+		);
+		assert_eq!(
+			lex("0b1111_0000"),
+			Ok(Token::Integer(240.into()))
+		);
+	}
+
+	#[test]
+	fn integers_should_not_be_limited_in_size() {
+		assert_eq!(
+			lex("0xFFFFFFFFFFFFFFFFFFFF"),
+			Ok(Token::Integer(
+				"1208925819614629174706175"
+					.parse()
+					.unwrap()
+			))
+		);
+	}
+
+	#[test]
+	fn invalid_digit_should_be_error() {
+		assert_eq!(
+			lex("0xZZ"),
+			Err(LexError::InvalidDigit {
+				digit: 'Z',
+				radix: 16,
+				span: (2..3).into()
+			})
+		);
+		assert_eq!(
+			lex("0b102"),
+			Err(LexError::InvalidDigit {
+				digit: '2',
+				radix: 2,
+				span: (4..5).into()
+			})
+		);
+	}
 
-pub fn integer(i: &mut Stream<'_>) -> PResult<Token> {
-	dec_int
-		.map(Token::Integer)
-		.parse_next(i)
+	#[test]
+	fn prefix_without_digits_should_be_error() {
+		assert_eq!(
+			lex("0x"),
+			Err(LexError::MissingDigits {
+				span: (0..2).into()
+			})
+		);
+		assert_eq!(
+			lex("0o__"),
+			Err(LexError::MissingDigits {
+				span: (0..4).into()
+			})
+		);
+	}
 }
@@ -22,6 +22,8 @@ pub fn punctuation(i: &mut Stream<'_>) -> PResult<Token> {
 		":".value(Token::Colon),
 		"/=".value(Token::SlashEqual),
 		"/".value(Token::Slash),
+		"...".value(Token::DotDotDot),
+		"..=".value(Token::DotDotEqual),
 		"..".value(Token::DotDot),
 		".".value(Token::Dot),
 		"->".value(Token::Arrow),
@@ -35,6 +37,7 @@ pub fn punctuation(i: &mut Stream<'_>) -> PResult<Token> {
 		")".value(Token::RightParenthesis),
 		"(".value(Token::LeftParenthesis),
 		"&&".value(Token::AndAnd),
+		"??".value(Token::QuestionQuestion),
 		"%=".value(Token::PercentEqual),
 		"%".value(Token::Percent),
 		"!=".value(Token::BangEqual),
@@ -1,6 +1,7 @@
 use std::fmt::{self, Display, Formatter};
 
 use dyn_span::{HasSpan, Span};
+use num_bigint::BigInt;
 use serde::Serialize;
 use strum::EnumDiscriminants;
 
@@ -19,9 +20,7 @@ pub struct QuotedString {
 }
 
 impl From<QuotedString> for String {
-	fn from(value: QuotedString) -> Self {
-		value.content
-	}
+	fn from(value: QuotedString) -> Self { value.content }
 }
 
 #[derive(Debug, Clone, PartialEq, EnumDiscriminants, Serialize)]
@@ -55,6 +54,7 @@ pub enum Token {
 	// Boolean operators
 	AndAnd,
 	PipePipe,
+	QuestionQuestion,
 
 	// Parentheses
 	LeftParenthesis,
@@ -68,6 +68,8 @@ pub enum Token {
 	Bang,
 	Dot,
 	DotDot,
+	DotDotEqual,
+	DotDotDot,
 	Comma,
 	Colon,
 	Pipe,
@@ -89,16 +91,22 @@ pub enum Token {
 	Return,
 	Break,
 	Continue,
+	Panic,
+	Assert,
 	Use,
 	Export,
+	Match,
 
 	// extra
 	NewLine,
 
-	LineComment,
-	BlockComment,
+	/// Text after `//`.
+	LineComment(String),
+	/// Text between `/*` and `*/`.
+	BlockComment(String),
 
-	Integer(i32),
+	Integer(BigInt),
+	Float(f64),
 
 	String(QuotedString),
 
@@ -132,6 +140,7 @@ impl Token {
 			Token::RightAngledBracketEqual => ">=".to_owned(),
 			Token::AndAnd => "&&".to_owned(),
 			Token::PipePipe => "||".to_owned(),
+			Token::QuestionQuestion => "??".to_owned(),
 			Token::LeftParenthesis => "(".to_owned(),
 			Token::RightParenthesis => ")".to_owned(),
 			Token::LeftBrace => "{".to_owned(),
@@ -141,6 +150,8 @@ impl Token {
 			Token::Bang => "!".to_owned(),
 			Token::Dot => ".".to_owned(),
 			Token::DotDot => "..".to_owned(),
+			Token::DotDotEqual => "..=".to_owned(),
+			Token::DotDotDot => "...".to_owned(),
 			Token::Comma => ".".to_owned(),
 			Token::Colon => ":".to_owned(),
 			Token::Pipe => "|".to_owned(),
@@ -158,12 +169,16 @@ impl Token {
 			Token::Return => "return".to_owned(),
 			Token::Break => "break".to_owned(),
 			Token::Continue => "continue".to_owned(),
+			Token::Panic => "panic".to_owned(),
+			Token::Assert => "assert".to_owned(),
 			Token::Use => "use".to_owned(),
 			Token::Export => "export".to_owned(),
+			Token::Match => "match".to_owned(),
 			Token::NewLine => "\\n".to_owned(), // NOTE: escape backslash
-			Token::LineComment => unreachable!(), // NOTE: skipped token
-			Token::BlockComment => unreachable!(), // NOTE: skipped token
+			Token::LineComment(v) => format!("//{v}"),
+			Token::BlockComment(v) => format!("/*{v}*/"),
 			Token::Integer(v) => format!("{v}"),
+			Token::Float(v) => format!("{v:?}"),
 			Token::String(QuotedString { content, quote }) => match quote {
 				QuoteKind::Single => format!("'{content}'"),
 				QuoteKind::Double => format!(r#""{content}""#),
@@ -205,14 +220,10 @@ impl SpannedToken {
 }
 
 impl HasSpan for SpannedToken {
-	fn span(&self) -> Span {
-		self.span
-	}
+	fn span(&self) -> Span { self.span }
 
 	fn set_span<S>(&mut self, span: S)
-	where
-		S: Into<Span>,
-	{
+	where S: Into<Span> {
 		self.span = span.into();
 	}
 }
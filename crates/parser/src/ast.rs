@@ -3,6 +3,7 @@ use std::{collections::HashMap, hash::Hash};
 
 use box_tt::BoxNew;
 use dyn_span::{HasSpan, Span};
+use num_bigint::BigInt;
 use strum::AsRefStr;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -12,9 +13,7 @@ pub struct Nil {
 
 impl Nil {
 	pub fn new<S>(span: S) -> Self
-	where
-		S: Into<Span>,
-	{
+	where S: Into<Span> {
 		Self { span: span.into() }
 	}
 
@@ -26,14 +25,10 @@ impl Nil {
 }
 
 impl HasSpan for Nil {
-	fn span(&self) -> Span {
-		self.span
-	}
+	fn span(&self) -> Span { self.span }
 
 	fn set_span<S>(&mut self, span: S)
-	where
-		S: Into<Span>,
-	{
+	where S: Into<Span> {
 		self.span = span.into();
 	}
 }
@@ -46,9 +41,7 @@ pub struct Boolean {
 
 impl Boolean {
 	pub fn new<S>(value: bool, span: S) -> Self
-	where
-		S: Into<Span>,
-	{
+	where S: Into<Span> {
 		Self {
 			value,
 			span: span.into(),
@@ -62,20 +55,14 @@ impl Boolean {
 		}
 	}
 
-	pub fn value(&self) -> bool {
-		self.value
-	}
+	pub fn value(&self) -> bool { self.value }
 }
 
 impl HasSpan for Boolean {
-	fn span(&self) -> Span {
-		self.span
-	}
+	fn span(&self) -> Span { self.span }
 
 	fn set_span<S>(&mut self, span: S)
-	where
-		S: Into<Span>,
-	{
+	where S: Into<Span> {
 		self.span = span.into();
 	}
 }
@@ -83,37 +70,66 @@ impl HasSpan for Boolean {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Integer {
 	pub span: Span,
-	pub value: i32,
+	pub value: BigInt,
 }
 
 impl Integer {
-	pub fn new<S>(value: i32, span: S) -> Self
+	pub fn new<V, S>(value: V, span: S) -> Self
 	where
-		S: Into<Span>,
-	{
+		V: Into<BigInt>,
+		S: Into<Span>, {
 		Self {
-			value,
+			value: value.into(),
 			span: span.into(),
 		}
 	}
 
-	pub fn new_dummy(value: i32) -> Self {
+	pub fn new_dummy<V>(value: V) -> Self
+	where V: Into<BigInt> {
 		Self {
-			value,
+			value: value.into(),
 			span: Span::DUMMY_SPAN,
 		}
 	}
 }
 
 impl HasSpan for Integer {
-	fn span(&self) -> Span {
-		self.span
+	fn span(&self) -> Span { self.span }
+
+	fn set_span<S>(&mut self, span: S)
+	where S: Into<Span> {
+		self.span = span.into();
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Float {
+	pub span: Span,
+	pub value: f64,
+}
+
+impl Float {
+	pub fn new<S>(value: f64, span: S) -> Self
+	where S: Into<Span> {
+		Self {
+			value,
+			span: span.into(),
+		}
 	}
 
+	pub fn new_dummy(value: f64) -> Self {
+		Self {
+			value,
+			span: Span::DUMMY_SPAN,
+		}
+	}
+}
+
+impl HasSpan for Float {
+	fn span(&self) -> Span { self.span }
+
 	fn set_span<S>(&mut self, span: S)
-	where
-		S: Into<Span>,
-	{
+	where S: Into<Span> {
 		self.span = span.into();
 	}
 }
@@ -128,8 +144,7 @@ impl StringT {
 	pub fn new<T, S>(value: T, span: S) -> Self
 	where
 		T: Into<String>,
-		S: Into<Span>,
-	{
+		S: Into<Span>, {
 		Self {
 			span: span.into(),
 			value: value.into(),
@@ -137,29 +152,21 @@ impl StringT {
 	}
 
 	pub fn new_dummy<T>(value: T) -> Self
-	where
-		T: Into<String>,
-	{
+	where T: Into<String> {
 		Self {
 			span: Span::DUMMY_SPAN,
 			value: value.into(),
 		}
 	}
 
-	pub fn into_string(self) -> String {
-		self.value
-	}
+	pub fn into_string(self) -> String { self.value }
 }
 
 impl HasSpan for StringT {
-	fn span(&self) -> Span {
-		self.span
-	}
+	fn span(&self) -> Span { self.span }
 
 	fn set_span<S>(&mut self, span: S)
-	where
-		S: Into<Span>,
-	{
+	where S: Into<Span> {
 		self.span = span.into();
 	}
 }
@@ -169,6 +176,7 @@ pub enum Literal {
 	Nil(Nil),
 	Boolean(Boolean),
 	Integer(Integer),
+	Float(Float),
 	String(StringT),
 }
 
@@ -189,8 +197,7 @@ impl Ident {
 	pub fn new<T, S>(symbol: T, span: S) -> Self
 	where
 		T: Into<String>,
-		S: Into<Span>,
-	{
+		S: Into<Span>, {
 		Self {
 			span: span.into(),
 			symbol: symbol.into(),
@@ -198,28 +205,20 @@ impl Ident {
 	}
 
 	pub fn new_dummy<T>(symbol: T) -> Self
-	where
-		T: Into<String>,
-	{
+	where T: Into<String> {
 		Self {
 			span: Span::DUMMY_SPAN,
 			symbol: symbol.into(),
 		}
 	}
 
-	pub fn symbol(&self) -> &str {
-		&self.symbol
-	}
+	pub fn symbol(&self) -> &str { &self.symbol }
 
-	pub fn into_symbol(self) -> String {
-		self.symbol
-	}
+	pub fn into_symbol(self) -> String { self.symbol }
 }
 
 impl PartialEq for Ident {
-	fn eq(&self, other: &Self) -> bool {
-		self.symbol == other.symbol
-	}
+	fn eq(&self, other: &Self) -> bool { self.symbol == other.symbol }
 }
 
 impl Hash for Ident {
@@ -229,14 +228,10 @@ impl Hash for Ident {
 }
 
 impl HasSpan for Ident {
-	fn span(&self) -> Span {
-		self.span
-	}
+	fn span(&self) -> Span { self.span }
 
 	fn set_span<S>(&mut self, span: S)
-	where
-		S: Into<Span>,
-	{
+	where S: Into<Span> {
 		self.span = span.into();
 	}
 }
@@ -249,9 +244,7 @@ pub struct Array {
 
 impl Array {
 	pub fn new<S>(elements: Vec<Expr>, span: S) -> Self
-	where
-		S: Into<Span>,
-	{
+	where S: Into<Span> {
 		Self {
 			span: span.into(),
 			elements,
@@ -267,14 +260,10 @@ impl Array {
 }
 
 impl HasSpan for Array {
-	fn span(&self) -> Span {
-		self.span
-	}
+	fn span(&self) -> Span { self.span }
 
 	fn set_span<S>(&mut self, span: S)
-	where
-		S: Into<Span>,
-	{
+	where S: Into<Span> {
 		self.span = span.into();
 	}
 }
@@ -286,23 +275,94 @@ pub struct Record {
 }
 
 impl HasSpan for Record {
-	fn span(&self) -> Span {
-		self.span
-	}
+	fn span(&self) -> Span { self.span }
 
 	fn set_span<S>(&mut self, span: S)
+	where S: Into<Span> {
+		self.span = span.into();
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameter {
+	pub pattern: Pattern,
+	/// Evaluated when the argument is missing, e.g. `b = 1`.
+	pub default: Option<Expr>,
+}
+
+/// Parameter list of a closure: `|a, b = 1, ...rest|`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameters {
+	pub span: Span,
+	pub positional: Vec<Parameter>,
+	/// Collects any arguments past `positional` into an array.
+	pub rest: Option<Ident>,
+}
+
+impl Parameters {
+	/// Fails with the span of the first parameter without a default
+	/// that follows one with a default.
+	pub fn new<S>(
+		positional: Vec<Parameter>,
+		rest: Option<Ident>,
+		span: S,
+	) -> Result<Self, Span>
 	where
 		S: Into<Span>,
 	{
+		if let Some(p) = positional
+			.iter()
+			.skip_while(|p| p.default.is_none())
+			.find(|p| p.default.is_none())
+		{
+			return Err(p.pattern.span);
+		}
+
+		Ok(Self {
+			span: span.into(),
+			positional,
+			rest,
+		})
+	}
+
+	/// Number of parameters that must be given an argument.
+	pub fn required(&self) -> usize {
+		self.positional
+			.iter()
+			.filter(|p| p.default.is_none())
+			.count()
+	}
+}
+
+impl HasSpan for Parameters {
+	fn span(&self) -> Span { self.span }
+
+	fn set_span<S>(&mut self, span: S)
+	where S: Into<Span> {
 		self.span = span.into();
 	}
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct Parameters(pub Vec<Ident>);
+pub struct Arguments(pub Vec<Expr>);
 
+/// `use "./path.dyn"`, optionally binding some of the module's
+/// exports with `use (a, b) of "./path.dyn"`.
 #[derive(Debug, Clone, PartialEq)]
-pub struct Arguments(pub Vec<Expr>);
+pub struct Use {
+	pub span: Span,
+	pub path: StringT,
+	pub imports: Vec<Ident>,
+}
+
+impl HasSpan for Use {
+	fn span(&self) -> Span { self.span }
+
+	fn set_span<S>(&mut self, span: S)
+	where S: Into<Span> {
+		self.span = span.into();
+	}
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Function {
@@ -327,14 +387,10 @@ pub struct UnaryExpr {
 }
 
 impl HasSpan for UnaryExpr {
-	fn span(&self) -> Span {
-		self.span
-	}
+	fn span(&self) -> Span { self.span }
 
 	fn set_span<S>(&mut self, span: S)
-	where
-		S: Into<Span>,
-	{
+	where S: Into<Span> {
 		self.span = span.into();
 	}
 }
@@ -379,6 +435,20 @@ pub enum BinExprKind {
 
 	#[strum(serialize = "||")]
 	Or,
+
+	#[strum(serialize = "??")]
+	Coalesce,
+}
+
+impl BinExprKind {
+	/// Whether the right operand is only evaluated when the left one
+	/// doesn't decide the result already.
+	pub fn is_logical(&self) -> bool {
+		matches!(
+			self,
+			Self::And | Self::Or | Self::Coalesce
+		)
+	}
 }
 
 #[derive(Debug, Clone, PartialEq, BoxNew)]
@@ -391,9 +461,7 @@ pub struct BinExpr {
 
 impl BinExpr {
 	pub fn new<S>(op: BinExprKind, lhs: Expr, rhs: Expr, span: S) -> Self
-	where
-		S: Into<Span>,
-	{
+	where S: Into<Span> {
 		Self {
 			span: span.into(),
 			op,
@@ -404,14 +472,10 @@ impl BinExpr {
 }
 
 impl HasSpan for BinExpr {
-	fn span(&self) -> Span {
-		self.span
-	}
+	fn span(&self) -> Span { self.span }
 
 	fn set_span<S>(&mut self, span: S)
-	where
-		S: Into<Span>,
-	{
+	where S: Into<Span> {
 		self.span = span.into();
 	}
 }
@@ -429,42 +493,217 @@ pub enum ExprKind {
 	Index(Box<Expr>, Box<Expr>),
 	UnaryExpr(UnaryExpr),
 	BinExpr(BinExpr),
-	Assign(Ident, Box<Expr>),
-	AddAssign(Ident, Box<Expr>),
-	SubAssign(Ident, Box<Expr>),
-	MulAssign(Ident, Box<Expr>),
-	DivAssign(Ident, Box<Expr>),
-	ModAssign(Ident, Box<Expr>),
+	/// `start..end`, or `start..=end` if `inclusive`, with a third
+	/// operand for the step if there is one.
+	Range {
+		start: Box<Expr>,
+		end: Box<Expr>,
+		step: Option<Box<Expr>>,
+		inclusive: bool,
+	},
+	Assign(Place, Box<Expr>),
+	AddAssign(Place, Box<Expr>),
+	SubAssign(Place, Box<Expr>),
+	MulAssign(Place, Box<Expr>),
+	DivAssign(Place, Box<Expr>),
+	ModAssign(Place, Box<Expr>),
 	Block(Code),
 	Declare(Ident, Box<Expr>),
 	DeclareMut(Ident, Box<Expr>),
-	If {
-		condition: Box<Expr>,
-		yes: Code,
+	/// `let` or `let!` with an array or record pattern.
+	Destructure {
+		pattern: Box<Pattern>,
+		value: Box<Expr>,
+		mutable: bool,
 	},
-	IfElse {
+	If {
 		condition: Box<Expr>,
 		yes: Code,
-		no: Code,
+		no: Option<Else>,
 	},
 	For {
 		collection: Box<Expr>,
-		item: Ident,
+		item: Box<Pattern>,
 		body: Code,
 	},
+	Match {
+		scrutinee: Box<Expr>,
+		arms: Vec<MatchArm>,
+	},
 	Panic(Box<Expr>),
 	Assert(Box<Expr>),
 	Return(Box<Expr>),
 	Break(Box<Expr>),
 	Continue(Box<Expr>),
+	Use(Use),
+	Export(Box<Expr>),
 }
 
-impl From<BinExpr> for ExprKind {
-	fn from(value: BinExpr) -> Self {
-		Self::BinExpr(value)
+/// What runs when the condition of an `if` doesn't hold.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Else {
+	Block(Code),
+	/// Another `if`, chained with `else if`.
+	If(Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatternKind {
+	/// `_`, which matches anything without binding it.
+	Wildcard,
+	Literal(Literal),
+	Binding(Ident),
+	/// Matches arrays with exactly as many elements, or at least as
+	/// many if there is a rest pattern for the others.
+	Array {
+		elements: Vec<Pattern>,
+		rest: Option<Box<Pattern>>,
+	},
+	/// Matches records with at least the given fields.
+	Record(Vec<FieldPattern>),
+}
+
+/// `key: pattern` in a record pattern, where `(key)` is short for
+/// `(key: key)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldPattern {
+	pub key: Ident,
+	pub pattern: Pattern,
+	/// Bound instead when the field is missing, e.g. `age = 0`. Only
+	/// fields bound to a name have one.
+	pub default: Option<Expr>,
+}
+
+impl FieldPattern {
+	/// Span of the whole field, including its default.
+	pub fn span(&self) -> Span {
+		let span = self.key.span + self.pattern.span;
+
+		match &self.default {
+			Some(default) => span + default.span,
+			None => span,
+		}
 	}
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+	pub span: Span,
+	pub kind: PatternKind,
+}
+
+impl Pattern {
+	pub fn new<S>(kind: PatternKind, span: S) -> Self
+	where S: Into<Span> {
+		Self {
+			span: span.into(),
+			kind,
+		}
+	}
+
+	/// Binds `ident`, unless it is `_`.
+	pub fn from_ident(ident: Ident) -> Self {
+		let span = ident.span;
+
+		match ident.symbol() {
+			"_" => Self::new(PatternKind::Wildcard, span),
+			_ => Self::new(PatternKind::Binding(ident), span),
+		}
+	}
+
+	/// Variables bound by the pattern, in the order they are bound.
+	pub fn bindings(&self) -> Vec<&Ident> {
+		let mut bindings = vec![];
+		self.collect_bindings(&mut bindings);
+		bindings
+	}
+
+	fn collect_bindings<'p>(&'p self, bindings: &mut Vec<&'p Ident>) {
+		match &self.kind {
+			PatternKind::Wildcard | PatternKind::Literal(_) => {},
+			PatternKind::Binding(ident) => bindings.push(ident),
+			PatternKind::Array { elements, rest } => {
+				for element in elements {
+					element.collect_bindings(bindings);
+				}
+				if let Some(rest) = rest {
+					rest.collect_bindings(bindings);
+				}
+			},
+			PatternKind::Record(fields) =>
+				for field in fields {
+					field.pattern.collect_bindings(bindings);
+				},
+		}
+	}
+
+	/// Fields with a default, along with the name it is bound to.
+	pub fn defaults(&self) -> Vec<(&Ident, &Expr)> {
+		let mut defaults = vec![];
+		self.collect_defaults(&mut defaults);
+		defaults
+	}
+
+	fn collect_defaults<'p>(
+		&'p self,
+		defaults: &mut Vec<(&'p Ident, &'p Expr)>,
+	) {
+		match &self.kind {
+			PatternKind::Wildcard
+			| PatternKind::Literal(_)
+			| PatternKind::Binding(_) => {},
+			PatternKind::Array { elements, rest } => {
+				for element in elements {
+					element.collect_defaults(defaults);
+				}
+				if let Some(rest) = rest {
+					rest.collect_defaults(defaults);
+				}
+			},
+			PatternKind::Record(fields) =>
+				for field in fields {
+					match (&field.pattern.kind, &field.default) {
+						(PatternKind::Binding(ident), Some(default)) =>
+							defaults.push((ident, default)),
+						_ => field.pattern.collect_defaults(defaults),
+					}
+				},
+		}
+	}
+
+	/// The first binding whose name was already bound earlier in the
+	/// pattern.
+	pub fn duplicate(&self) -> Option<&Ident> {
+		let bindings = self.bindings();
+
+		(1..bindings.len())
+			.find(|&i| bindings[..i].contains(&bindings[i]))
+			.map(|i| bindings[i])
+	}
+}
+
+impl HasSpan for Pattern {
+	fn span(&self) -> Span { self.span }
+
+	fn set_span<S>(&mut self, span: S)
+	where S: Into<Span> {
+		self.span = span.into();
+	}
+}
+
+/// `pattern -> body`, or `pattern if guard -> body`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchArm {
+	pub span: Span,
+	pub pattern: Pattern,
+	pub guard: Option<Expr>,
+	pub body: Expr,
+}
+
+impl From<BinExpr> for ExprKind {
+	fn from(value: BinExpr) -> Self { Self::BinExpr(value) }
+}
+
 #[derive(Debug, Clone, PartialEq, BoxNew)]
 pub struct Expr {
 	pub span: Span,
@@ -473,9 +712,7 @@ pub struct Expr {
 
 impl Expr {
 	pub fn new<S>(kind: ExprKind, span: S) -> Self
-	where
-		S: Into<Span>,
-	{
+	where S: Into<Span> {
 		Self {
 			span: span.into(),
 			kind,
@@ -489,6 +726,15 @@ impl Expr {
 		}
 	}
 
+	/// Zero-width `nil` located at `at`, used for implicit values
+	/// like the operand of a bare `return`.
+	pub fn new_nil(at: usize) -> Self {
+		Self {
+			span: (at..at).into(),
+			kind: ExprKind::Literal(Literal::Nil(Nil::new(at..at))),
+		}
+	}
+
 	pub fn new_lalr_binexpr(
 		(start, (lhs, op, rhs), end): (usize, (Expr, BinExprKind, Expr), usize),
 	) -> Self {
@@ -546,14 +792,10 @@ impl Expr {
 }
 
 impl HasSpan for Expr {
-	fn span(&self) -> Span {
-		self.span
-	}
+	fn span(&self) -> Span { self.span }
 
 	fn set_span<S>(&mut self, span: S)
-	where
-		S: Into<Span>,
-	{
+	where S: Into<Span> {
 		self.span = span.into();
 	}
 }
@@ -567,6 +809,70 @@ impl From<BinExpr> for Expr {
 	}
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlaceKind {
+	Ident(Ident),
+	Prop(Box<Place>, Ident),
+	Index(Box<Place>, Box<Expr>),
+}
+
+/// Left-hand side of an assignment: a variable, optionally followed
+/// by property accesses and indexing like `a.b[2].c`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Place {
+	pub span: Span,
+	pub kind: PlaceKind,
+}
+
+impl Place {
+	pub fn new<S>(kind: PlaceKind, span: S) -> Self
+	where S: Into<Span> {
+		Self {
+			span: span.into(),
+			kind,
+		}
+	}
+
+	/// The variable that owns the assigned location.
+	pub fn root(&self) -> &Ident {
+		match &self.kind {
+			PlaceKind::Ident(i) => i,
+			PlaceKind::Prop(i, _) | PlaceKind::Index(i, _) => i.root(),
+		}
+	}
+}
+
+impl TryFrom<Expr> for Place {
+	/// The part of the expression that can't be assigned to.
+	type Error = Expr;
+
+	fn try_from(value: Expr) -> Result<Self, Self::Error> {
+		let kind = match value.kind {
+			ExprKind::Ident(i) => PlaceKind::Ident(i),
+			ExprKind::Prop(i, j) =>
+				PlaceKind::Prop(Box::new((*i).try_into()?), j),
+			ExprKind::Index(i, j) =>
+				PlaceKind::Index(Box::new((*i).try_into()?), j),
+			kind =>
+				return Err(Expr {
+					span: value.span,
+					kind,
+				}),
+		};
+
+		Ok(Self::new(kind, value.span))
+	}
+}
+
+impl HasSpan for Place {
+	fn span(&self) -> Span { self.span }
+
+	fn set_span<S>(&mut self, span: S)
+	where S: Into<Span> {
+		self.span = span.into();
+	}
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Code {
 	pub span: Span,
@@ -575,9 +881,7 @@ pub struct Code {
 
 impl Code {
 	pub fn new<S>(stmts: Vec<Expr>, span: S) -> Self
-	where
-		S: Into<Span>,
-	{
+	where S: Into<Span> {
 		Self {
 			span: span.into(),
 			stmts,
@@ -593,14 +897,10 @@ impl Code {
 }
 
 impl HasSpan for Code {
-	fn span(&self) -> Span {
-		self.span
-	}
+	fn span(&self) -> Span { self.span }
 
 	fn set_span<S>(&mut self, span: S)
-	where
-		S: Into<Span>,
-	{
+	where S: Into<Span> {
 		self.span = span.into();
 	}
 }
@@ -0,0 +1,533 @@
+//! Lossless concrete syntax tree.
+//!
+//! The [`ast`](crate::ast) leaves out whatever doesn't change what a
+//! script does: whitespace, comments, parentheses and separators. A
+//! [`Node`] keeps every token of the source instead, so printing it
+//! gives back the source byte for byte. Tokens between the children
+//! of a node, including trivia, belong to that node.
+
+use std::fmt::{self, Display, Formatter};
+
+use dyn_lexer::{lexer::LexerConfig, SpannedLexer, TokenKind};
+use dyn_span::{HasSpan, Span};
+
+use crate::{
+	ast::{
+		Code, Else, Expr, ExprKind, Ident, Parameters, Pattern, PatternKind,
+		Place, PlaceKind,
+	},
+	parse_code, ParseError,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+	Code,
+	Literal,
+	Ident,
+	TemplateString,
+	Array,
+	Record,
+	/// `name: value` in a record.
+	Field,
+	Function,
+	Parameters,
+	/// A positional parameter with its default value, if any.
+	Parameter,
+	Call,
+	Prop,
+	Index,
+	UnaryExpr,
+	BinExpr,
+	/// `..` or `..=`, with its step if any.
+	Range,
+	Place,
+	/// `=` and compound assignments like `+=`.
+	Assign,
+	Block,
+	/// Both `let` and `let!`, with a name or a pattern.
+	Declare,
+	/// `if`, with or without `else`.
+	If,
+	For,
+	Match,
+	/// `pattern -> body`, with its guard if any.
+	MatchArm,
+	Pattern,
+	Panic,
+	Assert,
+	Return,
+	Break,
+	Continue,
+	Use,
+	Export,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+	pub kind: TokenKind,
+	pub span: Span,
+	/// The token as written in the source.
+	pub text: String,
+}
+
+impl Token {
+	/// Whether the token is whitespace or a comment, which the parser
+	/// never sees. Line breaks aren't, as they separate statements.
+	pub fn is_trivia(&self) -> bool {
+		matches!(
+			self.kind,
+			TokenKind::Whitespace
+				| TokenKind::LineComment
+				| TokenKind::BlockComment
+		)
+	}
+}
+
+impl HasSpan for Token {
+	fn span(&self) -> Span { self.span }
+
+	fn set_span<S>(&mut self, span: S)
+	where S: Into<Span> {
+		self.span = span.into();
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Element {
+	Node(Node),
+	Token(Token),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+	pub kind: NodeKind,
+	pub span: Span,
+	pub children: Vec<Element>,
+}
+
+impl Node {
+	/// All tokens under the node, in source order.
+	pub fn tokens(&self) -> Box<dyn Iterator<Item = &Token> + '_> {
+		Box::new(self.children.iter().flat_map(
+			|child| -> Box<dyn Iterator<Item = &Token>> {
+				match child {
+					Element::Node(node) => node.tokens(),
+					Element::Token(token) => Box::new(std::iter::once(token)),
+				}
+			},
+		))
+	}
+
+	/// Child nodes, leaving out the tokens between them.
+	pub fn nodes(&self) -> impl Iterator<Item = &Node> {
+		self.children
+			.iter()
+			.filter_map(|child| match child {
+				Element::Node(node) => Some(node),
+				Element::Token(_) => None,
+			})
+	}
+}
+
+impl HasSpan for Node {
+	fn span(&self) -> Span { self.span }
+
+	fn set_span<S>(&mut self, span: S)
+	where S: Into<Span> {
+		self.span = span.into();
+	}
+}
+
+/// Prints the source the node was parsed from.
+impl Display for Node {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		for token in self.tokens() {
+			f.write_str(&token.text)?;
+		}
+
+		Ok(())
+	}
+}
+
+/// Parses `code` into a tree whose root spans all of it.
+pub fn parse_cst(code: &str) -> Result<Node, ParseError> {
+	Ok(from_ast(code, &parse_code(code)?))
+}
+
+/// Builds the tree of `code` from its already parsed `ast`.
+pub fn from_ast(code: &str, ast: &Code) -> Node {
+	let tokens = SpannedLexer::new(code, LexerConfig {
+		ignore_whitespace: false,
+		ignore_comments: false,
+		asi: false,
+	})
+	.filter_map(|token| {
+		Some(Token {
+			kind: TokenKind::from(&token.token.ok()?),
+			span: token.span,
+			text: code[token.span.range()].to_owned(),
+		})
+	})
+	.collect::<Vec<_>>();
+
+	let mut builder = Builder {
+		tokens: tokens.into_iter().peekable(),
+		stack: vec![vec![]],
+	};
+	builder.node(
+		NodeKind::Code,
+		Span::new(0, code.len()),
+		|b| {
+			for stmt in &ast.stmts {
+				b.expr(stmt);
+			}
+		},
+	);
+
+	let Some(Element::Node(root)) = builder.stack.pop().unwrap().pop() else {
+		unreachable!("the root is the only element left")
+	};
+
+	root
+}
+
+/// Builds nodes from the syntax tree, handing out the tokens in order
+/// to whichever node is innermost where they appear.
+struct Builder {
+	tokens: std::iter::Peekable<std::vec::IntoIter<Token>>,
+	/// Children of the nodes being built, from the root inwards.
+	stack: Vec<Vec<Element>>,
+}
+
+impl Builder {
+	fn tokens_before(&mut self, end: usize) {
+		while let Some(token) = self
+			.tokens
+			.next_if(|token| token.span.start() < end)
+		{
+			self.stack
+				.last_mut()
+				.unwrap()
+				.push(Element::Token(token));
+		}
+	}
+
+	fn node<F>(&mut self, kind: NodeKind, span: Span, children: F)
+	where F: FnOnce(&mut Self) {
+		self.tokens_before(span.start());
+
+		self.stack.push(vec![]);
+		children(self);
+		self.tokens_before(span.end());
+		let children = self.stack.pop().unwrap();
+
+		self.stack
+			.last_mut()
+			.unwrap()
+			.push(Element::Node(Node {
+				kind,
+				span,
+				children,
+			}));
+	}
+
+	fn ident(&mut self, i: &Ident) {
+		self.node(NodeKind::Ident, i.span, |_| {})
+	}
+
+	fn code(&mut self, i: &Code) {
+		self.node(NodeKind::Code, i.span, |b| {
+			for stmt in &i.stmts {
+				b.expr(stmt);
+			}
+		});
+	}
+
+	fn parameters(&mut self, i: &Parameters) {
+		self.node(NodeKind::Parameters, i.span, |b| {
+			for parameter in &i.positional {
+				let span = match &parameter.default {
+					Some(default) => parameter.pattern.span + default.span,
+					None => parameter.pattern.span,
+				};
+
+				b.node(NodeKind::Parameter, span, |b| {
+					b.pattern(&parameter.pattern);
+					if let Some(default) = &parameter.default {
+						b.expr(default);
+					}
+				});
+			}
+
+			if let Some(rest) = &i.rest {
+				b.ident(rest);
+			}
+		});
+	}
+
+	fn pattern(&mut self, i: &Pattern) {
+		self.node(NodeKind::Pattern, i.span, |b| {
+			match &i.kind {
+				PatternKind::Wildcard | PatternKind::Literal(_) => {},
+				PatternKind::Binding(i) => b.ident(i),
+				PatternKind::Array { elements, rest } => {
+					for element in elements {
+						b.pattern(element);
+					}
+					if let Some(rest) = rest {
+						b.pattern(rest);
+					}
+				},
+				PatternKind::Record(fields) =>
+					for field in fields {
+						b.node(NodeKind::Field, field.span(), |b| {
+							// `(x)` is short for `(x: x)`, with a single token
+							if field.key.span != field.pattern.span {
+								b.ident(&field.key);
+							}
+							b.pattern(&field.pattern);
+							if let Some(default) = &field.default {
+								b.expr(default);
+							}
+						});
+					},
+			}
+		});
+	}
+
+	fn place(&mut self, i: &Place) {
+		self.node(NodeKind::Place, i.span, |b| {
+			match &i.kind {
+				PlaceKind::Ident(i) => b.ident(i),
+				PlaceKind::Prop(i, j) => {
+					b.place(i);
+					b.ident(j);
+				},
+				PlaceKind::Index(i, j) => {
+					b.place(i);
+					b.expr(j);
+				},
+			}
+		});
+	}
+
+	fn expr(&mut self, i: &Expr) {
+		let kind = match &i.kind {
+			ExprKind::Literal(_) => NodeKind::Literal,
+			ExprKind::Ident(_) => NodeKind::Ident,
+			ExprKind::TemplateString(_) => NodeKind::TemplateString,
+			ExprKind::Array(_) => NodeKind::Array,
+			ExprKind::Record(_) => NodeKind::Record,
+			ExprKind::Function(_) => NodeKind::Function,
+			ExprKind::Call(..) => NodeKind::Call,
+			ExprKind::Prop(..) => NodeKind::Prop,
+			ExprKind::Index(..) => NodeKind::Index,
+			ExprKind::UnaryExpr(_) => NodeKind::UnaryExpr,
+			ExprKind::BinExpr(_) => NodeKind::BinExpr,
+			ExprKind::Range { .. } => NodeKind::Range,
+			ExprKind::Assign(..)
+			| ExprKind::AddAssign(..)
+			| ExprKind::SubAssign(..)
+			| ExprKind::MulAssign(..)
+			| ExprKind::DivAssign(..)
+			| ExprKind::ModAssign(..) => NodeKind::Assign,
+			ExprKind::Block(_) => NodeKind::Block,
+			ExprKind::Declare(..)
+			| ExprKind::DeclareMut(..)
+			| ExprKind::Destructure { .. } => NodeKind::Declare,
+			ExprKind::If { .. } => NodeKind::If,
+			ExprKind::For { .. } => NodeKind::For,
+			ExprKind::Match { .. } => NodeKind::Match,
+			ExprKind::Panic(_) => NodeKind::Panic,
+			ExprKind::Assert(_) => NodeKind::Assert,
+			ExprKind::Return(_) => NodeKind::Return,
+			ExprKind::Break(_) => NodeKind::Break,
+			ExprKind::Continue(_) => NodeKind::Continue,
+			ExprKind::Use(_) => NodeKind::Use,
+			ExprKind::Export(_) => NodeKind::Export,
+		};
+
+		self.node(kind, i.span, |b| match &i.kind {
+			ExprKind::Literal(_) | ExprKind::Ident(_) => {},
+			ExprKind::TemplateString(i) =>
+				for value in &i.values {
+					b.expr(value);
+				},
+			ExprKind::Array(i) =>
+				for element in &i.elements {
+					b.expr(element);
+				},
+			ExprKind::Record(i) =>
+				for (name, value) in &i.fields {
+					b.node(
+						NodeKind::Field,
+						name.span + value.span,
+						|b| {
+							b.ident(name);
+							b.expr(value);
+						},
+					);
+				},
+			ExprKind::Function(i) => {
+				b.parameters(&i.parameters);
+				b.expr(&i.body);
+			},
+			ExprKind::Call(i, j) => {
+				b.expr(i);
+				for argument in &j.0 {
+					b.expr(argument);
+				}
+			},
+			ExprKind::Prop(i, j) => {
+				b.expr(i);
+				b.ident(j);
+			},
+			ExprKind::Index(i, j) => {
+				b.expr(i);
+				b.expr(j);
+			},
+			ExprKind::UnaryExpr(i) => b.expr(&i.expr),
+			ExprKind::BinExpr(i) => {
+				b.expr(&i.lhs);
+				b.expr(&i.rhs);
+			},
+			ExprKind::Range {
+				start, end, step, ..
+			} => {
+				b.expr(start);
+				b.expr(end);
+				if let Some(step) = step {
+					b.expr(step);
+				}
+			},
+			ExprKind::Assign(i, j)
+			| ExprKind::AddAssign(i, j)
+			| ExprKind::SubAssign(i, j)
+			| ExprKind::MulAssign(i, j)
+			| ExprKind::DivAssign(i, j)
+			| ExprKind::ModAssign(i, j) => {
+				b.place(i);
+				b.expr(j);
+			},
+			ExprKind::Block(i) => b.code(i),
+			ExprKind::Declare(i, j) | ExprKind::DeclareMut(i, j) => {
+				b.ident(i);
+				b.expr(j);
+			},
+			ExprKind::Destructure { pattern, value, .. } => {
+				b.pattern(pattern);
+				b.expr(value);
+			},
+			ExprKind::If { condition, yes, no } => {
+				b.expr(condition);
+				b.code(yes);
+				match no {
+					Some(Else::Block(no)) => b.code(no),
+					Some(Else::If(no)) => b.expr(no),
+					None => {},
+				}
+			},
+			ExprKind::For {
+				collection,
+				item,
+				body,
+			} => {
+				b.expr(collection);
+				b.pattern(item);
+				b.code(body);
+			},
+			ExprKind::Match { scrutinee, arms } => {
+				b.expr(scrutinee);
+
+				for arm in arms {
+					b.node(NodeKind::MatchArm, arm.span, |b| {
+						b.pattern(&arm.pattern);
+						if let Some(guard) = &arm.guard {
+							b.expr(guard);
+						}
+						b.expr(&arm.body);
+					});
+				}
+			},
+			ExprKind::Panic(i)
+			| ExprKind::Assert(i)
+			| ExprKind::Return(i)
+			| ExprKind::Break(i)
+			| ExprKind::Continue(i)
+			| ExprKind::Export(i) => b.expr(i),
+			ExprKind::Use(i) => {
+				for import in &i.imports {
+					b.ident(import);
+				}
+				b.node(NodeKind::Literal, i.path.span, |_| {});
+			},
+		});
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+
+	use indoc::indoc;
+	use pretty_assertions::assert_eq;
+
+	use super::*;
+
+	#[test]
+	fn should_round_trip_examples() {
+		let examples = concat!(
+			env!("CARGO_MANIFEST_DIR"),
+			"/../../examples"
+		);
+
+		for entry in fs::read_dir(examples).unwrap() {
+			let source = fs::read_to_string(entry.unwrap().path()).unwrap();
+
+			// some examples show off syntax errors
+			if let Ok(cst) = parse_cst(&source) {
+				assert_eq!(cst.to_string(), source);
+			}
+		}
+	}
+
+	#[test]
+	fn trivia_should_belong_to_the_innermost_node() {
+		let cst = parse_cst(indoc! {"
+			// leading
+			f(1, /* one */ 2)
+		"})
+		.unwrap();
+
+		assert_eq!(
+			cst.tokens()
+				.filter(|token| token.is_trivia())
+				.map(|token| token.text.as_str())
+				.collect::<Vec<_>>(),
+			["// leading", " ", "/* one */", " "]
+		);
+
+		let call = cst.nodes().next().unwrap();
+		assert_eq!(call.kind, NodeKind::Call);
+		assert_eq!(
+			call.children
+				.iter()
+				.map(|child| match child {
+					Element::Node(node) => format!("{:?}", node.kind),
+					Element::Token(token) => token.text.clone(),
+				})
+				.collect::<Vec<_>>(),
+			[
+				"Ident",
+				"(",
+				"Literal",
+				",",
+				" ",
+				"/* one */",
+				" ",
+				"Literal",
+				")"
+			]
+		);
+	}
+}
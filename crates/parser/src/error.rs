@@ -0,0 +1,18 @@
+use dyn_lexer::LexError;
+use thiserror::Error;
+
+/// Errors reported to LALRPOP as user errors.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum SyntaxError {
+	#[error(transparent)]
+	LexError(#[from] LexError),
+
+	#[error("invalid left-hand side of assignment")]
+	InvalidAssignmentTarget,
+
+	#[error("parameter without a default value after one with a default")]
+	RequiredParameterAfterDefault,
+
+	#[error("identifier bound more than once in the same pattern")]
+	DuplicateBinding,
+}
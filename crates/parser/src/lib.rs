@@ -1,7 +1,11 @@
 pub mod ast;
+pub mod cst;
+mod error;
 mod macros;
 mod parser;
 mod visitor;
 
-pub use parser::{parse, parse_code, ParseError};
-pub use visitor::{Visit, VisitMut};
+pub use cst::parse_cst;
+pub use error::SyntaxError;
+pub use parser::{parse, parse_code, parse_code_recovering, ParseError};
+pub use visitor::{walk_expr, Visit, VisitMut};
@@ -6,7 +6,7 @@ macro_rules! n {
 			$crate::ast::ExprKind::Literal($crate::ast::Literal::Integer(
 				$crate::ast::Integer::new_dummy($n),
 			)),
-			span::Span::DUMMY_SPAN,
+			::dyn_span::Span::DUMMY_SPAN,
 		))
 	};
 
@@ -40,7 +40,7 @@ macro_rules! ident {
 			$crate::ast::ExprKind::Ident($crate::ast::Ident::new_dummy(
 				stringify!($id),
 			)),
-			span::Span::DUMMY_SPAN,
+			::dyn_span::Span::DUMMY_SPAN,
 		))
 	};
 
@@ -110,7 +110,7 @@ macro_rules! arr {
 					vec![$($ele),*]
 				)
 			),
-			span::Span::DUMMY_SPAN,
+			::dyn_span::Span::DUMMY_SPAN,
 		))
 	};
 
@@ -146,7 +146,7 @@ macro_rules! nil {
 			$crate::ast::ExprKind::Literal($crate::ast::Literal::Nil(
 				$crate::ast::Nil::new_dummy(),
 			)),
-			span::Span::DUMMY_SPAN,
+			::dyn_span::Span::DUMMY_SPAN,
 		))
 	};
 
@@ -177,7 +177,7 @@ macro_rules! tru {
 			$crate::ast::ExprKind::Literal($crate::ast::Literal::Boolean(
 				$crate::ast::Boolean::new_dummy(true),
 			)),
-			span::Span::DUMMY_SPAN,
+			::dyn_span::Span::DUMMY_SPAN,
 		))
 	};
 
@@ -208,7 +208,7 @@ macro_rules! fal {
 			$crate::ast::ExprKind::Literal($crate::ast::Literal::Boolean(
 				$crate::ast::Boolean::new_dummy(false),
 			)),
-			span::Span::DUMMY_SPAN,
+			::dyn_span::Span::DUMMY_SPAN,
 		))
 	};
 
@@ -297,3 +297,24 @@ macro_rules! call_ident {
 }
 
 pub(crate) use call_ident;
+
+macro_rules! params {
+	(
+		$($id:ident $start:literal..$end:literal),* ;
+		$pstart:literal..$pend:literal
+	) => {
+		$crate::ast::Parameters::new(
+			vec![$($crate::ast::Parameter {
+				pattern: $crate::ast::Pattern::from_ident(
+					$crate::ast::Ident::new(stringify!($id), $start..$end),
+				),
+				default: None,
+			}),*],
+			None,
+			$pstart..$pend,
+		)
+		.unwrap()
+	};
+}
+
+pub(crate) use params;
@@ -1,41 +1,124 @@
-use dyn_lexer::{
-	lexer::LexerConfig, LexError, SpannedLexer, SpannedToken, Token,
+use dyn_lexer::{lexer::LexerConfig, SpannedLexer, SpannedToken, Token};
+use dyn_span::{HasSpan, Spanned};
+use lalrpop_util::{lalrpop_mod, ErrorRecovery};
+
+use crate::{
+	ast::{Code, Expr},
+	SyntaxError,
 };
-use dyn_span::Spanned;
-use lalrpop_util::lalrpop_mod;
-
-use crate::ast::{Code, Expr};
-
-lalrpop_mod!(#[allow(clippy::type_complexity)] pub dynlang);
-
-pub type ParseError = lalrpop_util::ParseError<usize, Token, Spanned<LexError>>;
-
-fn lexer_adapter(
-	spanned_token: SpannedToken,
-) -> Result<(usize, Token, usize), Spanned<LexError>> {
-	match spanned_token {
-		SpannedToken {
-			token: Ok(token),
-			span,
-		} => Ok((span.start(), token, span.end())),
-		SpannedToken {
-			token: Err(error),
-			span,
-		} => Err(Spanned::new(span, error)),
+
+lalrpop_mod!(#[allow(clippy::type_complexity, clippy::ptr_arg)] pub dynlang);
+
+pub type ParseError =
+	lalrpop_util::ParseError<usize, Token, Spanned<SyntaxError>>;
+
+/// Feeds the tokens of `code` to the parser, collecting lexer errors
+/// into `errors` instead so that parsing can go on without them.
+fn tokens<'a>(
+	code: &'a str,
+	errors: &'a mut Vec<ParseError>,
+) -> impl Iterator<Item = Result<(usize, Token, usize), Spanned<SyntaxError>>> + 'a
+{
+	SpannedLexer::new(code, LexerConfig::default()).filter_map(
+		|spanned_token| match spanned_token {
+			SpannedToken {
+				token: Ok(token),
+				span,
+			} => Some(Ok((span.start(), token, span.end()))),
+			SpannedToken {
+				token: Err(error),
+				span,
+			} => {
+				errors.push(ParseError::User {
+					error: Spanned::new(span, error.into()),
+				});
+				None
+			},
+		},
+	)
+}
+
+/// Where `error` happened, to order errors by.
+fn location(error: &ParseError) -> usize {
+	match error {
+		ParseError::InvalidToken { location }
+		| ParseError::UnrecognizedEof { location, .. } => *location,
+		ParseError::UnrecognizedToken { token, .. }
+		| ParseError::ExtraToken { token } => token.0,
+		ParseError::User { error } => error.span().start(),
 	}
 }
 
+/// Puts the errors the parser recovered from together with those that
+/// couldn't be, in the order they appear.
+fn collect_errors(
+	mut errors: Vec<ParseError>,
+	recovered: Vec<ErrorRecovery<usize, Token, Spanned<SyntaxError>>>,
+	res: Result<(), ParseError>,
+) -> Vec<ParseError> {
+	errors.extend(
+		recovered
+			.into_iter()
+			.map(|recovery| recovery.error),
+	);
+	errors.extend(res.err());
+	errors.sort_by_key(location);
+	errors
+}
+
 pub fn parse(code: &str) -> Result<Expr, ParseError> {
-	dynlang::ExprParser::new().parse(
-		SpannedLexer::new(code, LexerConfig::default()).map(lexer_adapter),
-	)
+	let mut errors = vec![];
+	let mut recovered = vec![];
+	let res = dynlang::ExprParser::new().parse(
+		&mut recovered,
+		tokens(code, &mut errors),
+	);
+
+	let (expr, res) = match res {
+		Ok(expr) => (Some(expr), Ok(())),
+		Err(error) => (None, Err(error)),
+	};
+
+	match collect_errors(errors, recovered, res)
+		.into_iter()
+		.next()
+	{
+		Some(error) => Err(error),
+		None => Ok(expr.unwrap()),
+	}
 }
 
 pub fn parse_code(code: &str) -> Result<Code, ParseError> {
-	dynlang::CodeParser::new().parse(
-		SpannedLexer::new(code, LexerConfig::default()).map(lexer_adapter),
+	let (ast, errors) = parse_code_recovering(code);
+
+	match errors.into_iter().next() {
+		Some(error) => Err(error),
+		None => Ok(ast),
+	}
+}
+
+/// Parses `code` past syntax errors, skipping the statements they are
+/// in. Returns what could be parsed along with all errors, in order.
+pub fn parse_code_recovering(code: &str) -> (Code, Vec<ParseError>) {
+	let mut errors = vec![];
+	let mut recovered = vec![];
+	let res = dynlang::CodeParser::new().parse(
+		&mut recovered,
+		tokens(code, &mut errors),
+	);
+
+	let (ast, res) = match res {
+		Ok(ast) => (ast, Ok(())),
+		Err(error) => (
+			Code::new(vec![], 0..code.len()),
+			Err(error),
+		),
+	};
+
+	(
+		ast,
+		collect_errors(errors, recovered, res),
 	)
 }
 
-#[cfg(test)]
-mod tests;
+#[cfg(test)] mod tests;
@@ -4,10 +4,13 @@ use pretty_assertions::assert_eq;
 use super::*;
 use crate::{
 	ast::{
-		BinExpr, BinExprKind, ExprKind, Function, Parameters, UnaryExpr,
-		UnaryExprKind,
+		BinExpr, BinExprKind, Else, ExprKind, FieldPattern, Function, MatchArm,
+		Pattern, PatternKind, Place, PlaceKind, UnaryExpr, UnaryExprKind,
+	},
+	error::SyntaxError,
+	macros::{
+		arr, call, call_ident, code, fal, ident, n, nil, params, str, tru, var,
 	},
-	macros::{arr, call, call_ident, code, fal, ident, n, nil, str, tru, var},
 };
 
 #[test]
@@ -619,7 +622,10 @@ fn parse_assign() {
 	assert_eq!(
 		res,
 		Ok(Expr::new(
-			ExprKind::assign_box(var!(a 0..1), *n!(123 4)),
+			ExprKind::assign_box(
+				Place::new(PlaceKind::Ident(var!(a 0..1)), 0..1),
+				*n!(123 4)
+			),
 			0..7
 		))
 	)
@@ -633,9 +639,12 @@ fn parse_nested_assign() {
 		res,
 		Ok(Expr::new(
 			ExprKind::assign_box(
-				var!(a 0..1),
+				Place::new(PlaceKind::Ident(var!(a 0..1)), 0..1),
 				Expr::new(
-					ExprKind::assign_box(var!(b 4..5), *ident!(c 8..9)),
+					ExprKind::assign_box(
+						Place::new(PlaceKind::Ident(var!(b 4..5)), 4..5),
+						*ident!(c 8..9)
+					),
 					4..9
 				)
 			),
@@ -652,14 +661,17 @@ fn parse_nested_assign2() {
 		res,
 		Ok(Expr::new(
 			ExprKind::assign_box(
-				var!(a 0..1),
+				Place::new(PlaceKind::Ident(var!(a 0..1)), 0..1),
 				Expr::new(
 					ExprKind::unary_expr_box(UnaryExpr {
 						span: (4..16).into(),
 						op: UnaryExprKind::Not,
 						expr: Box::new(Expr::new(
 							ExprKind::assign_box(
-								var!(b 6..7),
+								Place::new(
+									PlaceKind::Ident(var!(b 6..7)),
+									6..7
+								),
 								*ident!(c 10..11) + *n!(2 14)
 							),
 							6..15
@@ -681,7 +693,7 @@ fn parse_mixed_assignments() {
 		res,
 		Ok(Expr::new(
 			ExprKind::assign_box(
-				var!(a 0..1),
+				Place::new(PlaceKind::Ident(var!(a 0..1)), 0..1),
 				Expr::new(
 					ExprKind::declare_box(
 						var!(b 8..9),
@@ -713,7 +725,8 @@ fn parse_if_expr() {
 		Ok(Expr::new(
 			ExprKind::If {
 				condition: tru!(3),
-				yes: code! { call_ident!(print 10..29 (*str!("it's true!" 16))); 10..29 }
+				yes: code! { call_ident!(print 10..29 (*str!("it's true!" 16))); 10..29 },
+				no: None,
 			},
 			0..31
 		))
@@ -742,7 +755,8 @@ fn parse_if_expr2() {
 					call_ident!(abc 15..20 ()),
 					call_ident!(def 21..26 ());
 					15..26
-				}
+				},
+				no: None,
 			},
 			0..27
 		))
@@ -761,15 +775,176 @@ fn parse_nested_if() {
 				yes: code! {
 					Expr::new(ExprKind::If {
 						condition: fal!(13),
-						yes: code! {; 20..20}
-					}, 10..22); 10..22
-				}
+						yes: code! {; 20..20},
+						no: None,
+					}, 10..21); 10..21
+				},
+				no: None,
 			},
 			0..23
 		))
 	)
 }
 
+#[test]
+fn parse_else_if_chain() {
+	let res = parse(r#"if a { 1 } else if b { 2 } else { 3 }"#);
+
+	assert_eq!(
+		res,
+		Ok(Expr::new(
+			ExprKind::If {
+				condition: ident!(a 3..4),
+				yes: code! { *n!(1 7); 7..8 },
+				no: Some(Else::If(Box::new(Expr::new(
+					ExprKind::If {
+						condition: ident!(b 19..20),
+						yes: code! { *n!(2 23); 23..24 },
+						no: Some(Else::Block(code! { *n!(3 34); 34..35 })),
+					},
+					16..37
+				)))),
+			},
+			0..37
+		))
+	)
+}
+
+#[test]
+fn parse_match_expr() {
+	let res = parse("match a { [x, ...r] if x -> r, (b, c: _) -> b }");
+
+	assert_eq!(
+		res,
+		Ok(Expr::new(
+			ExprKind::Match {
+				scrutinee: ident!(a 6..7),
+				arms: vec![
+					MatchArm {
+						span: (10..29).into(),
+						pattern: Pattern::new(
+							PatternKind::Array {
+								elements: vec![Pattern::new(
+									PatternKind::Binding(var!(x 11..12)),
+									11..12
+								)],
+								rest: Some(Box::new(Pattern::new(
+									PatternKind::Binding(var!(r 17..18)),
+									17..18
+								))),
+							},
+							10..19
+						),
+						guard: Some(*ident!(x 23..24)),
+						body: *ident!(r 28..29),
+					},
+					MatchArm {
+						span: (31..45).into(),
+						pattern: Pattern::new(
+							PatternKind::Record(vec![
+								FieldPattern {
+									key: var!(b 32..33),
+									pattern: Pattern::new(
+										PatternKind::Binding(var!(b 32..33)),
+										32..33
+									),
+									default: None,
+								},
+								FieldPattern {
+									key: var!(c 35..36),
+									pattern: Pattern::new(
+										PatternKind::Wildcard,
+										38..39
+									),
+									default: None,
+								},
+							]),
+							31..40
+						),
+						guard: None,
+						body: *ident!(b 44..45),
+					},
+				],
+			},
+			0..47
+		))
+	)
+}
+
+#[test]
+fn parse_destructuring_let() {
+	let res = parse("let (a, b: [c], d = 1) = e");
+
+	assert_eq!(
+		res,
+		Ok(Expr::new(
+			ExprKind::Destructure {
+				pattern: Box::new(Pattern::new(
+					PatternKind::Record(vec![
+						FieldPattern {
+							key: var!(a 5..6),
+							pattern: Pattern::from_ident(var!(a 5..6)),
+							default: None,
+						},
+						FieldPattern {
+							key: var!(b 8..9),
+							pattern: Pattern::new(
+								PatternKind::Array {
+									elements: vec![Pattern::from_ident(
+										var!(c 12..13)
+									)],
+									rest: None,
+								},
+								11..14
+							),
+							default: None,
+						},
+						FieldPattern {
+							key: var!(d 16..17),
+							pattern: Pattern::from_ident(var!(d 16..17)),
+							default: Some(*n!(1 20)),
+						},
+					]),
+					4..22
+				)),
+				value: ident!(e 25..26),
+				mutable: false,
+			},
+			0..26
+		))
+	)
+}
+
+#[test]
+fn parse_range_expr() {
+	let res = parse("0..=n + 1..2");
+
+	assert_eq!(
+		res,
+		Ok(Expr::new(
+			ExprKind::Range {
+				start: n!(0 0),
+				end: ident!(n 4..5) + n!(1 8),
+				step: Some(n!(2 11)),
+				inclusive: true,
+			},
+			0..12
+		))
+	)
+}
+
+#[test]
+fn binding_twice_in_pattern_should_fail() {
+	let res = parse("match a { [x, x] -> x }");
+
+	assert_eq!(
+		res,
+		Err(ParseError::User {
+			error: Spanned::new(14..15, SyntaxError::DuplicateBinding),
+		})
+	)
+}
+
 #[test]
 #[ignore]
 fn parse_else_expression() {
@@ -778,7 +953,7 @@ fn parse_else_expression() {
 	assert_eq!(
 		res,
 		Ok(Expr::new(
-			ExprKind::IfElse {
+			ExprKind::If {
 				condition: Box::new(Expr::new(
 					ExprKind::BinExpr(BinExpr::new(
 						BinExprKind::GreaterThan,
@@ -789,7 +964,9 @@ fn parse_else_expression() {
 					3..8
 				)),
 				yes: code! { call_ident!(fetch 11..18 ()); 11..18 },
-				no: code! { call_ident!(cancel 28..36 ()); 28..36 }
+				no: Some(Else::Block(
+					code! { call_ident!(cancel 28..36 ()); 28..36 }
+				))
 			},
 			0..38
 		))
@@ -909,7 +1086,10 @@ fn parse_add_assign() {
 	assert_eq!(
 		res,
 		Ok(Expr::new(
-			ExprKind::AddAssign(var!(a 0..1), n!(1 5)),
+			ExprKind::AddAssign(
+				Place::new(PlaceKind::Ident(var!(a 0..1)), 0..1),
+				n!(1 5)
+			),
 			0..6
 		))
 	);
@@ -922,7 +1102,10 @@ fn parse_sub_assign() {
 	assert_eq!(
 		res,
 		Ok(Expr::new(
-			ExprKind::SubAssign(var!(a 0..1), n!(1 5)),
+			ExprKind::SubAssign(
+				Place::new(PlaceKind::Ident(var!(a 0..1)), 0..1),
+				n!(1 5)
+			),
 			0..6
 		))
 	);
@@ -935,7 +1118,10 @@ fn parse_mul_assign() {
 	assert_eq!(
 		res,
 		Ok(Expr::new(
-			ExprKind::MulAssign(var!(a 0..1), n!(1 5)),
+			ExprKind::MulAssign(
+				Place::new(PlaceKind::Ident(var!(a 0..1)), 0..1),
+				n!(1 5)
+			),
 			0..6
 		))
 	);
@@ -948,7 +1134,10 @@ fn parse_div_assign() {
 	assert_eq!(
 		res,
 		Ok(Expr::new(
-			ExprKind::DivAssign(var!(a 0..1), n!(1 5)),
+			ExprKind::DivAssign(
+				Place::new(PlaceKind::Ident(var!(a 0..1)), 0..1),
+				n!(1 5)
+			),
 			0..6
 		))
 	);
@@ -961,7 +1150,10 @@ fn parse_mod_assign() {
 	assert_eq!(
 		res,
 		Ok(Expr::new(
-			ExprKind::ModAssign(var!(a 0..1), n!(1 5)),
+			ExprKind::ModAssign(
+				Place::new(PlaceKind::Ident(var!(a 0..1)), 0..1),
+				n!(1 5)
+			),
 			0..6
 		))
 	);
@@ -976,7 +1168,7 @@ fn parse_for_loop_expr() {
 		Ok(Expr::new(
 			ExprKind::For {
 				collection: ident!(arr 5..8),
-				item: var!(item 12..16),
+				item: Box::new(Pattern::from_ident(var!(item 12..16))),
 				body: code! {
 					call_ident!(print 19..30 (*ident!(item 25..29)));
 					19..30
@@ -1000,7 +1192,7 @@ fn parse_for_loop_expr_spanning_multiple_lines() {
 		Ok(Expr::new(
 			ExprKind::For {
 				collection: ident!(arr 5..8),
-				item: var!(item 12..16),
+				item: Box::new(Pattern::from_ident(var!(item 12..16))),
 				body: code! {
 					call_ident!(print 20..31 (*ident!(item 26..30)));
 					20..32
@@ -1027,7 +1219,7 @@ fn parse_for_loop_over_expression() {
 		Ok(Expr::new(
 			ExprKind::For {
 				collection: arr![*str!("some string" 8), *n!(12345 23); 5..30],
-				item: var!(item 34..38),
+				item: Box::new(Pattern::from_ident(var!(item 34..38))),
 				body: code! {
 					call_ident!(print 42..53 (*ident!(item 48..52)));
 					42..54
@@ -1056,7 +1248,7 @@ fn parse_for_loop_over_if_else_expression() {
 		Ok(Expr::new(
 			ExprKind::For {
 				collection: Box::new(Expr::new(
-					ExprKind::IfElse {
+					ExprKind::If {
 						condition: Box::new(Expr::new(
 							ExprKind::BinExpr(BinExpr::new(
 								BinExprKind::GreaterThan,
@@ -1069,13 +1261,13 @@ fn parse_for_loop_over_if_else_expression() {
 						yes: code! {
 							*str!("this" 18); 18..25
 						},
-						no: code! {
+						no: Some(Else::Block(code! {
 							*arr![*str!("or" 36), *str!("this" 42); 35..49]; 35..50
-						}
+						}))
 					},
 					5..51
 				)),
-				item: var!(item 55..59),
+				item: Box::new(Pattern::from_ident(var!(item 55..59))),
 				body: code! {
 					call_ident!(print 63..74 (*ident!(item 69..73))); 63..75
 				},
@@ -1100,7 +1292,7 @@ fn parse_for_loop_with_break_expression_in_it() {
 		Ok(Expr::new(
 			ExprKind::For {
 				collection: ident!(arr 5..8),
-				item: var!(x 12..13),
+				item: Box::new(Pattern::from_ident(var!(x 12..13))),
 				body: code! {
 					Expr::new(
 						ExprKind::If {
@@ -1119,9 +1311,10 @@ fn parse_for_loop_with_break_expression_in_it() {
 									ExprKind::Break(ident!(x 37..38)),
 									31..38
 								); 31..39
-							}
+							},
+							no: None,
 						},
-						17..42
+						17..41
 					); 17..42
 				},
 			},
@@ -1141,11 +1334,8 @@ fn parse_function_expr() {
 		res,
 		Ok(Expr::new(
 			ExprKind::Function(Function {
-				parameters: Parameters(vec![var!(x 1..2), var!(y 4..5)]),
-				body: code! {
-					*ident!(x 10..11) + *ident!(y 14..15);
-					0..0
-				}
+				parameters: params!(x 1..2, y 4..5; 1..5),
+				body: ident!(x 10..11) + ident!(y 14..15),
 			}),
 			0..15
 		))
@@ -1165,18 +1355,21 @@ fn parse_function_expr_with_block_body() {
 		res,
 		Ok(Expr::new(
 			ExprKind::Function(Function {
-				parameters: Parameters(vec![var!(x 1..2), var!(y 4..5)]),
-				body: code! {
-					Expr::new(
-						ExprKind::Declare(
-							var!(local 17..22),
-							n!(2 25) * ident!(x 29..30)
+				parameters: params!(x 1..2, y 4..5; 1..5),
+				body: Box::new(Expr::new(
+					ExprKind::Block(code! {
+						Expr::new(
+							ExprKind::Declare(
+								var!(local 17..22),
+								n!(2 25) * ident!(x 29..30)
+							),
+							13..30
 						),
-						13..30
-					),
-					call_ident!(kok 32..46 (*ident!(local 36..41) + *ident!(y 44..45)));
-					13..47
-				}
+						call_ident!(kok 32..46 (*ident!(local 36..41) + *ident!(y 44..45)));
+						13..47
+					}),
+					10..48
+				)),
 			}),
 			0..48
 		))
@@ -1196,11 +1389,14 @@ fn parse_iife() {
 		res,
 		Ok(*call!(
 			Box::new(Expr::new(ExprKind::Function(Function {
-				parameters: Parameters(vec![var!(x 1..2)]),
-				body: code!{
-					call_ident!(print 10..18 (*ident!(x 16..17)));
-					10..19
-				}
+				parameters: params!(x 1..2; 1..2),
+				body: Box::new(Expr::new(
+					ExprKind::Block(code! {
+						call_ident!(print 10..18 (*ident!(x 16..17)));
+						10..19
+					}),
+					7..20
+				)),
 			}), 0..20)); 0..25 (*n!(123 21))
 		))
 	);
@@ -1216,8 +1412,11 @@ fn parse_function_expression_with_zero_arguments() {
 		res,
 		Ok(Expr::new(
 			ExprKind::Function(Function {
-				parameters: Parameters(vec![]),
-				body: code! { *n!(123 8); 8..11 }
+				parameters: params!(; 0..2),
+				body: Box::new(Expr::new(
+					ExprKind::Block(code! { *n!(123 8); 8..11 }),
+					6..13
+				)),
 			}),
 			0..13
 		))
@@ -1235,8 +1434,8 @@ fn parse_lambda_expression_with_zero_arguments() {
 		res,
 		Ok(Expr::new(
 			ExprKind::Function(Function {
-				parameters: Parameters(vec![]),
-				body: code! { *n!(123 6); 0..0 }
+				parameters: params!(; 0..2),
+				body: n!(123 6),
 			}),
 			0..9
 		))
@@ -1254,26 +1453,20 @@ fn parse_nested_lambda_expression() {
 		res,
 		Ok(Expr::new(
 			ExprKind::Function(Function {
-				parameters: Parameters(vec![]),
-				body: code! {
-					Expr::new(
-						ExprKind::Function(
-							Function {
-								parameters: Parameters(vec![]),
-								body: code! {
-									Expr::new(
-										ExprKind::Function(Function {
-											parameters: Parameters(vec![]),
-											body: code! { *nil!(18); 10..10 }
-										}),
-										12..21
-									); 10..10
-								}
-							}
-						),
-					6..21);
-					10..10
-				}
+				parameters: params!(; 0..2),
+				body: Box::new(Expr::new(
+					ExprKind::Function(Function {
+						parameters: params!(; 6..8),
+						body: Box::new(Expr::new(
+							ExprKind::Function(Function {
+								parameters: params!(; 12..14),
+								body: nil!(18),
+							}),
+							12..21
+						)),
+					}),
+					6..21
+				)),
 			}),
 			0..21
 		))
@@ -1296,18 +1489,21 @@ fn parse_function_expr_with_multiple_argument_delimited_by_newline() {
 		res,
 		Ok(Expr::new(
 			ExprKind::Function(Function {
-				parameters: Parameters(vec![var!(x 3..4), var!(y 6..7)]),
-				body: code! {
-					Expr::new(
-						ExprKind::Declare(
-							var!(local 20..25),
-							n!(2 28) * ident!(x 32..33)
+				parameters: params!(x 3..4, y 6..7; 3..8),
+				body: Box::new(Expr::new(
+					ExprKind::Block(code! {
+						Expr::new(
+							ExprKind::Declare(
+								var!(local 20..25),
+								n!(2 28) * ident!(x 32..33)
+							),
+							16..33
 						),
-						16..33
-					),
-					call_ident!(kok 35..49 (*ident!(local 39..44) + *ident!(y 47..48)));
-					16..50
-				}
+						call_ident!(kok 35..49 (*ident!(local 39..44) + *ident!(y 47..48)));
+						16..50
+					}),
+					13..51
+				)),
 			}),
 			0..51
 		))
@@ -1,6 +1,7 @@
 use crate::ast::{
-	Array, BinExpr, Boolean, Code, Expr, ExprKind, Function, Ident, Integer,
-	Literal, Nil, Record, StringT, TemplateString, UnaryExpr,
+	Array, BinExpr, Boolean, Code, Else, Expr, ExprKind, Float, Function,
+	Ident, Integer, Literal, MatchArm, Nil, Pattern, PatternKind, Place,
+	PlaceKind, Record, StringT, TemplateString, UnaryExpr, Use,
 };
 
 pub trait Visit {
@@ -13,6 +14,9 @@ pub trait Visit {
 	#[allow(unused)]
 	fn visit_integer(&mut self, i: &Integer) {}
 
+	#[allow(unused)]
+	fn visit_float(&mut self, i: &Float) {}
+
 	#[allow(unused)]
 	fn visit_string(&mut self, i: &StringT) {}
 
@@ -21,6 +25,7 @@ pub trait Visit {
 			Literal::Nil(i) => self.visit_nil(i),
 			Literal::Boolean(i) => self.visit_boolean(i),
 			Literal::Integer(i) => self.visit_integer(i),
+			Literal::Float(i) => self.visit_float(i),
 			Literal::String(i) => self.visit_string(i),
 		}
 	}
@@ -48,104 +53,87 @@ pub trait Visit {
 	}
 
 	fn visit_function(&mut self, i: &Function) {
-		for i in &i.parameters.0 {
-			self.visit_ident(i);
+		for i in &i.parameters.positional {
+			self.visit_pattern(&i.pattern);
+			if let Some(default) = &i.default {
+				self.visit_expr(default);
+			}
+		}
+		if let Some(rest) = &i.parameters.rest {
+			self.visit_ident(rest);
 		}
 		self.visit_expr(&i.body);
 	}
 
 	fn visit_unaryexpr(&mut self, i: &UnaryExpr) { self.visit_expr(&i.expr); }
 
-	fn visit_binexpr(&mut self, i: &BinExpr) {
-		self.visit_expr(&i.lhs);
-		self.visit_expr(&i.rhs);
+	fn visit_use(&mut self, i: &Use) {
+		self.visit_string(&i.path);
+		for i in &i.imports {
+			self.visit_ident(i);
+		}
 	}
 
-	fn visit_expr(&mut self, i: &Expr) {
+	fn visit_place(&mut self, i: &Place) {
 		match &i.kind {
-			ExprKind::Literal(i) => self.visit_literal(i),
-			ExprKind::Ident(i) => self.visit_ident(i),
-			ExprKind::UnaryExpr(i) => self.visit_unaryexpr(i),
-			ExprKind::TemplateString(i) => self.visit_template_string(i),
-			ExprKind::Array(i) => self.visit_array(i),
-			ExprKind::Record(i) => self.visit_record(i),
-			ExprKind::Function(i) => self.visit_function(i),
-			ExprKind::Call(i, j) => {
-				for j in &j.0 {
-					self.visit_expr(j);
-				}
-				self.visit_expr(i);
-			},
-			ExprKind::Prop(i, j) => {
-				self.visit_expr(i);
+			PlaceKind::Ident(i) => self.visit_ident(i),
+			PlaceKind::Prop(i, j) => {
+				self.visit_place(i);
 				self.visit_ident(j);
 			},
-			ExprKind::Index(i, j) => {
-				self.visit_expr(i);
-				self.visit_expr(j);
-			},
-			ExprKind::BinExpr(i) => self.visit_binexpr(i),
-			ExprKind::Assign(i, j) => {
-				self.visit_ident(i);
-				self.visit_expr(j);
-			},
-			ExprKind::AddAssign(i, j) => {
-				self.visit_ident(i);
-				self.visit_expr(j);
-			},
-			ExprKind::SubAssign(i, j) => {
-				self.visit_ident(i);
-				self.visit_expr(j);
-			},
-			ExprKind::MulAssign(i, j) => {
-				self.visit_ident(i);
-				self.visit_expr(j);
-			},
-			ExprKind::DivAssign(i, j) => {
-				self.visit_ident(i);
-				self.visit_expr(j);
-			},
-			ExprKind::ModAssign(i, j) => {
-				self.visit_ident(i);
-				self.visit_expr(j);
-			},
-			ExprKind::Block(i) => {
-				self.visit_code(i);
-			},
-			ExprKind::Declare(i, j) => {
-				self.visit_ident(i);
+			PlaceKind::Index(i, j) => {
+				self.visit_place(i);
 				self.visit_expr(j);
 			},
-			ExprKind::DeclareMut(i, j) => {
-				self.visit_ident(i);
-				self.visit_expr(j);
-			},
-			ExprKind::If { condition, yes } => {
-				self.visit_expr(condition);
-				self.visit_code(yes);
-			},
-			ExprKind::IfElse { condition, yes, no } => {
-				self.visit_expr(condition);
-				self.visit_code(yes);
-				self.visit_code(no);
-			},
-			ExprKind::For {
-				collection,
-				item,
-				body,
-			} => {
-				self.visit_expr(collection);
-				self.visit_ident(item);
-				self.visit_code(body);
+		}
+	}
+
+	fn visit_binexpr(&mut self, i: &BinExpr) {
+		self.visit_expr(&i.lhs);
+		self.visit_expr(&i.rhs);
+	}
+
+	fn visit_pattern(&mut self, i: &Pattern) {
+		match &i.kind {
+			PatternKind::Wildcard => {},
+			PatternKind::Literal(i) => self.visit_literal(i),
+			PatternKind::Binding(i) => self.visit_ident(i),
+			PatternKind::Array { elements, rest } => {
+				for i in elements {
+					self.visit_pattern(i);
+				}
+				if let Some(rest) = rest {
+					self.visit_pattern(rest);
+				}
 			},
-			ExprKind::Panic(i) => self.visit_expr(i),
-			ExprKind::Assert(i) => self.visit_expr(i),
-			ExprKind::Return(i) => self.visit_expr(i),
-			ExprKind::Break(i) => self.visit_expr(i),
-			ExprKind::Continue(i) => self.visit_expr(i),
+			PatternKind::Record(fields) =>
+				for i in fields {
+					self.visit_ident(&i.key);
+					self.visit_pattern(&i.pattern);
+					if let Some(default) = &i.default {
+						self.visit_expr(default);
+					}
+				},
 		}
 	}
 
+	fn visit_match_arm(&mut self, i: &MatchArm) {
+		self.visit_pattern(&i.pattern);
+		if let Some(guard) = &i.guard {
+			self.visit_expr(guard);
+		}
+		self.visit_expr(&i.body);
+	}
+
+	fn visit_else(&mut self, i: &Else) {
+		match i {
+			Else::Block(i) => self.visit_code(i),
+			Else::If(i) => self.visit_expr(i),
+		}
+	}
+
+	fn visit_expr(&mut self, i: &Expr) { walk_expr(self, i) }
+
 	fn visit_code(&mut self, i: &Code) {
 		for i in &i.stmts {
 			self.visit_expr(i);
@@ -153,6 +141,113 @@ pub trait Visit {
 	}
 }
 
+/// Visits the children of `i`, which is what [`Visit::visit_expr`]
+/// does unless overridden.
+pub fn walk_expr<V>(v: &mut V, i: &Expr)
+where V: Visit + ?Sized {
+	match &i.kind {
+		ExprKind::Literal(i) => v.visit_literal(i),
+		ExprKind::Ident(i) => v.visit_ident(i),
+		ExprKind::UnaryExpr(i) => v.visit_unaryexpr(i),
+		ExprKind::TemplateString(i) => v.visit_template_string(i),
+		ExprKind::Array(i) => v.visit_array(i),
+		ExprKind::Record(i) => v.visit_record(i),
+		ExprKind::Function(i) => v.visit_function(i),
+		ExprKind::Call(i, j) => {
+			for j in &j.0 {
+				v.visit_expr(j);
+			}
+			v.visit_expr(i);
+		},
+		ExprKind::Prop(i, j) => {
+			v.visit_expr(i);
+			v.visit_ident(j);
+		},
+		ExprKind::Index(i, j) => {
+			v.visit_expr(i);
+			v.visit_expr(j);
+		},
+		ExprKind::BinExpr(i) => v.visit_binexpr(i),
+		ExprKind::Range {
+			start, end, step, ..
+		} => {
+			v.visit_expr(start);
+			v.visit_expr(end);
+			if let Some(step) = step {
+				v.visit_expr(step);
+			}
+		},
+		ExprKind::Assign(i, j) => {
+			v.visit_place(i);
+			v.visit_expr(j);
+		},
+		ExprKind::AddAssign(i, j) => {
+			v.visit_place(i);
+			v.visit_expr(j);
+		},
+		ExprKind::SubAssign(i, j) => {
+			v.visit_place(i);
+			v.visit_expr(j);
+		},
+		ExprKind::MulAssign(i, j) => {
+			v.visit_place(i);
+			v.visit_expr(j);
+		},
+		ExprKind::DivAssign(i, j) => {
+			v.visit_place(i);
+			v.visit_expr(j);
+		},
+		ExprKind::ModAssign(i, j) => {
+			v.visit_place(i);
+			v.visit_expr(j);
+		},
+		ExprKind::Block(i) => {
+			v.visit_code(i);
+		},
+		ExprKind::Declare(i, j) => {
+			v.visit_ident(i);
+			v.visit_expr(j);
+		},
+		ExprKind::DeclareMut(i, j) => {
+			v.visit_ident(i);
+			v.visit_expr(j);
+		},
+		ExprKind::Destructure { pattern, value, .. } => {
+			v.visit_pattern(pattern);
+			v.visit_expr(value);
+		},
+		ExprKind::If { condition, yes, no } => {
+			v.visit_expr(condition);
+			v.visit_code(yes);
+			if let Some(no) = no {
+				v.visit_else(no);
+			}
+		},
+		ExprKind::For {
+			collection,
+			item,
+			body,
+		} => {
+			v.visit_expr(collection);
+			v.visit_pattern(item);
+			v.visit_code(body);
+		},
+		ExprKind::Match { scrutinee, arms } => {
+			v.visit_expr(scrutinee);
+			for arm in arms {
+				v.visit_match_arm(arm);
+			}
+		},
+		ExprKind::Panic(i) => v.visit_expr(i),
+		ExprKind::Assert(i) => v.visit_expr(i),
+		ExprKind::Return(i) => v.visit_expr(i),
+		ExprKind::Break(i) => v.visit_expr(i),
+		ExprKind::Continue(i) => v.visit_expr(i),
+		ExprKind::Use(i) => v.visit_use(i),
+		ExprKind::Export(i) => v.visit_expr(i),
+	}
+}
+
 pub trait VisitMut {
 	#[allow(unused)]
 	fn visit_mut_nil(&mut self, i: &mut Nil) {}
@@ -163,6 +258,9 @@ pub trait VisitMut {
 	#[allow(unused)]
 	fn visit_mut_integer(&mut self, i: &mut Integer) {}
 
+	#[allow(unused)]
+	fn visit_mut_float(&mut self, i: &mut Float) {}
+
 	#[allow(unused)]
 	fn visit_mut_string(&mut self, i: &mut StringT) {}
 
@@ -171,6 +269,7 @@ pub trait VisitMut {
 			Literal::Nil(i) => self.visit_mut_nil(i),
 			Literal::Boolean(i) => self.visit_mut_boolean(i),
 			Literal::Integer(i) => self.visit_mut_integer(i),
+			Literal::Float(i) => self.visit_mut_float(i),
 			Literal::String(i) => self.visit_mut_string(i),
 		}
 	}
@@ -198,8 +297,14 @@ pub trait VisitMut {
 	}
 
 	fn visit_mut_function(&mut self, i: &mut Function) {
-		for i in &mut i.parameters.0 {
-			self.visit_mut_ident(i);
+		for i in &mut i.parameters.positional {
+			self.visit_mut_pattern(&mut i.pattern);
+			if let Some(default) = &mut i.default {
+				self.visit_mut_expr(default);
+			}
+		}
+		if let Some(rest) = &mut i.parameters.rest {
+			self.visit_mut_ident(rest);
 		}
 		self.visit_mut_expr(&mut i.body);
 	}
@@ -208,11 +313,71 @@ pub trait VisitMut {
 		self.visit_mut_expr(&mut i.expr);
 	}
 
+	fn visit_mut_use(&mut self, i: &mut Use) {
+		self.visit_mut_string(&mut i.path);
+		for i in &mut i.imports {
+			self.visit_mut_ident(i);
+		}
+	}
+
+	fn visit_mut_place(&mut self, i: &mut Place) {
+		match &mut i.kind {
+			PlaceKind::Ident(i) => self.visit_mut_ident(i),
+			PlaceKind::Prop(i, j) => {
+				self.visit_mut_place(i);
+				self.visit_mut_ident(j);
+			},
+			PlaceKind::Index(i, j) => {
+				self.visit_mut_place(i);
+				self.visit_mut_expr(j);
+			},
+		}
+	}
+
 	fn visit_mut_binexpr(&mut self, i: &mut BinExpr) {
 		self.visit_mut_expr(&mut i.lhs);
 		self.visit_mut_expr(&mut i.rhs);
 	}
 
+	fn visit_mut_pattern(&mut self, i: &mut Pattern) {
+		match &mut i.kind {
+			PatternKind::Wildcard => {},
+			PatternKind::Literal(i) => self.visit_mut_literal(i),
+			PatternKind::Binding(i) => self.visit_mut_ident(i),
+			PatternKind::Array { elements, rest } => {
+				for i in elements {
+					self.visit_mut_pattern(i);
+				}
+				if let Some(rest) = rest {
+					self.visit_mut_pattern(rest);
+				}
+			},
+			PatternKind::Record(fields) =>
+				for i in fields {
+					self.visit_mut_ident(&mut i.key);
+					self.visit_mut_pattern(&mut i.pattern);
+					if let Some(default) = &mut i.default {
+						self.visit_mut_expr(default);
+					}
+				},
+		}
+	}
+
+	fn visit_mut_match_arm(&mut self, i: &mut MatchArm) {
+		self.visit_mut_pattern(&mut i.pattern);
+		if let Some(guard) = &mut i.guard {
+			self.visit_mut_expr(guard);
+		}
+		self.visit_mut_expr(&mut i.body);
+	}
+
+	fn visit_mut_else(&mut self, i: &mut Else) {
+		match i {
+			Else::Block(i) => self.visit_mut_code(i),
+			Else::If(i) => self.visit_mut_expr(i),
+		}
+	}
+
 	fn visit_mut_expr(&mut self, i: &mut Expr) {
 		match &mut i.kind {
 			ExprKind::Literal(i) => self.visit_mut_literal(i),
@@ -237,28 +402,37 @@ pub trait VisitMut {
 				self.visit_mut_expr(j);
 			},
 			ExprKind::BinExpr(i) => self.visit_mut_binexpr(i),
+			ExprKind::Range {
+				start, end, step, ..
+			} => {
+				self.visit_mut_expr(start);
+				self.visit_mut_expr(end);
+				if let Some(step) = step {
+					self.visit_mut_expr(step);
+				}
+			},
 			ExprKind::Assign(i, j) => {
-				self.visit_mut_ident(i);
+				self.visit_mut_place(i);
 				self.visit_mut_expr(j);
 			},
 			ExprKind::AddAssign(i, j) => {
-				self.visit_mut_ident(i);
+				self.visit_mut_place(i);
 				self.visit_mut_expr(j);
 			},
 			ExprKind::SubAssign(i, j) => {
-				self.visit_mut_ident(i);
+				self.visit_mut_place(i);
 				self.visit_mut_expr(j);
 			},
 			ExprKind::MulAssign(i, j) => {
-				self.visit_mut_ident(i);
+				self.visit_mut_place(i);
 				self.visit_mut_expr(j);
 			},
 			ExprKind::DivAssign(i, j) => {
-				self.visit_mut_ident(i);
+				self.visit_mut_place(i);
 				self.visit_mut_expr(j);
 			},
 			ExprKind::ModAssign(i, j) => {
-				self.visit_mut_ident(i);
+				self.visit_mut_place(i);
 				self.visit_mut_expr(j);
 			},
 			ExprKind::Block(i) => {
@@ -272,14 +446,16 @@ pub trait VisitMut {
 				self.visit_mut_ident(i);
 				self.visit_mut_expr(j);
 			},
-			ExprKind::If { condition, yes } => {
-				self.visit_mut_expr(condition);
-				self.visit_mut_code(yes);
+			ExprKind::Destructure { pattern, value, .. } => {
+				self.visit_mut_pattern(pattern);
+				self.visit_mut_expr(value);
 			},
-			ExprKind::IfElse { condition, yes, no } => {
+			ExprKind::If { condition, yes, no } => {
 				self.visit_mut_expr(condition);
 				self.visit_mut_code(yes);
-				self.visit_mut_code(no);
+				if let Some(no) = no {
+					self.visit_mut_else(no);
+				}
 			},
 			ExprKind::For {
 				collection,
@@ -287,14 +463,22 @@ pub trait VisitMut {
 				body,
 			} => {
 				self.visit_mut_expr(collection);
-				self.visit_mut_ident(item);
+				self.visit_mut_pattern(item);
 				self.visit_mut_code(body);
 			},
+			ExprKind::Match { scrutinee, arms } => {
+				self.visit_mut_expr(scrutinee);
+				for arm in arms {
+					self.visit_mut_match_arm(arm);
+				}
+			},
 			ExprKind::Panic(i) => self.visit_mut_expr(i),
 			ExprKind::Assert(i) => self.visit_mut_expr(i),
 			ExprKind::Return(i) => self.visit_mut_expr(i),
 			ExprKind::Break(i) => self.visit_mut_expr(i),
 			ExprKind::Continue(i) => self.visit_mut_expr(i),
+			ExprKind::Use(i) => self.visit_mut_use(i),
+			ExprKind::Export(i) => self.visit_mut_expr(i),
 		}
 	}
 
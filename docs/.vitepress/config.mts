@@ -76,6 +76,10 @@ export default defineConfig({
 										text: 'Function',
 										link: 'syntax-overview/function',
 									},
+									{
+										text: 'Module',
+										link: 'syntax-overview/module',
+									},
 								],
 							},
 						],
@@ -92,6 +96,10 @@ export default defineConfig({
 								text: 'Syntax',
 								link: 'syntax',
 							},
+							{
+								text: 'Standard Library',
+								link: 'stdlib',
+							},
 						],
 					},
 				],
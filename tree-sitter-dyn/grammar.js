@@ -1,6 +1,12 @@
 /// <reference types="tree-sitter-cli/dsl" />
 // @ts-check
 
+// elements are separated by `,` or a newline, and newlines are extras
+const list = rule =>
+	optional(
+		seq(rule, repeat(seq(optional(','), rule)), optional(',')),
+	)
+
 module.exports = grammar({
 	name: 'dyn',
 
@@ -13,21 +19,23 @@ module.exports = grammar({
 				$._literal,
 				$.func,
 				$.binexpr,
+				$.range,
 				$.unaryexpr,
 				$.block,
 				$.let,
 				$.assign,
 				$.if,
 				$.iter,
+				$.match,
 			),
 
 		binexpr: $ =>
 			choice(
-				prec.left(5, seq($.expr, '*', $.expr)),
-				prec.left(5, seq($.expr, '/', $.expr)),
-				prec.left(5, seq($.expr, '%', $.expr)),
-				prec.left(4, seq($.expr, '+', $.expr)),
-				prec.left(4, seq($.expr, '-', $.expr)),
+				prec.left(6, seq($.expr, '*', $.expr)),
+				prec.left(6, seq($.expr, '/', $.expr)),
+				prec.left(6, seq($.expr, '%', $.expr)),
+				prec.left(5, seq($.expr, '+', $.expr)),
+				prec.left(5, seq($.expr, '-', $.expr)),
 				prec.left(3, seq($.expr, '==', $.expr)),
 				prec.left(3, seq($.expr, '!=', $.expr)),
 				prec.left(3, seq($.expr, '<', $.expr)),
@@ -38,6 +46,18 @@ module.exports = grammar({
 				prec.left(1, seq($.expr, '||', $.expr)),
 			),
 
+		// ranges don't chain, so `a..b..c` is a range with a step
+		range: $ =>
+			prec.left(
+				4,
+				seq(
+					$.expr,
+					choice('..', '..='),
+					$.expr,
+					optional(seq('..', $.expr)),
+				),
+			),
+
 		unaryexpr: $ =>
 			choice(seq('-', $.expr), seq('-', $.expr)),
 
@@ -56,7 +76,7 @@ module.exports = grammar({
 			seq(
 				'let',
 				optional('!'),
-				$.identifier,
+				$._binding,
 				'=',
 				$.expr,
 			),
@@ -66,7 +86,7 @@ module.exports = grammar({
 				'if',
 				$.expr,
 				$.block,
-				optional(seq('else', $.block)),
+				optional(seq('else', choice($.block, $.if))),
 			),
 
 		iter: $ =>
@@ -74,14 +94,57 @@ module.exports = grammar({
 				'iter',
 				$.expr,
 				'of',
-				$.identifier,
+				$._binding,
 				$.block,
 			),
 
+		match: $ => seq('match', $.expr, '{', list($.match_arm), '}'),
+
+		match_arm: $ =>
+			seq(
+				$.pattern,
+				optional(seq('if', $.expr)),
+				'->',
+				$.expr,
+			),
+
+		pattern: $ =>
+			choice(
+				'_',
+				$._literal,
+				seq('-', $.number),
+				$.identifier,
+				$.array_pattern,
+				$.record_pattern,
+			),
+
+		array_pattern: $ =>
+			seq(
+				'[',
+				list($.pattern),
+				optional(seq('...', $.identifier, optional(','))),
+				']',
+			),
+
+		record_pattern: $ => seq('(', list($.field_pattern), ')'),
+
+		// `(x)` is short for `(x: x)`, and the parser only allows
+		// defaults after names
+		field_pattern: $ =>
+			seq(
+				$.identifier,
+				optional(seq(':', $.pattern)),
+				optional(seq('=', $.expr)),
+			),
+
+		// patterns that can bind where a single name is expected otherwise
+		_binding: $ =>
+			choice($.identifier, $.array_pattern, $.record_pattern),
+
 		func: $ =>
 			seq(
 				optional(
-					seq('|', repeat($.identifier), '|'),
+					seq('|', list($._binding), '|'),
 				),
 				'->',
 				$.expr,
@@ -95,6 +158,14 @@ module.exports = grammar({
 
 		string: $ => /"[^"]*"/,
 
-		number: $ => /\d+/,
+		number: $ =>
+			token(
+				choice(
+					/0x[0-9a-fA-F_]+/,
+					/0o[0-7_]+/,
+					/0b[01_]+/,
+					/\d[\d_]*(\.\d[\d_]*)?([eE][+-]?\d[\d_]*)?/,
+				),
+			),
 	},
 })